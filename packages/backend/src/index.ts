@@ -1,4 +1,5 @@
-import type { ServerWebSocket } from "bun";
+import type { Server, ServerWebSocket } from "bun";
+import { timingSafeEqual } from "node:crypto";
 import type {
   ClientMessage,
   ServerMessage,
@@ -41,6 +42,48 @@ function resolvePort(): number {
 
 const PORT = resolvePort();
 
+/** Resolve bind address: CLI --host flag, otherwise Bun's default. */
+function resolveHostname(): string | undefined {
+  const args = process.argv;
+  const hostFlagIdx = args.indexOf("--host");
+  if (hostFlagIdx !== -1 && hostFlagIdx + 1 < args.length) {
+    return args[hostFlagIdx + 1];
+  }
+  return undefined;
+}
+
+const HOSTNAME = resolveHostname();
+
+/** Resolve Unix socket path: CLI --socket flag, served in addition to the TCP port. */
+function resolveSocket(): string | undefined {
+  const args = process.argv;
+  const socketFlagIdx = args.indexOf("--socket");
+  if (socketFlagIdx !== -1 && socketFlagIdx + 1 < args.length) {
+    return args[socketFlagIdx + 1];
+  }
+  return undefined;
+}
+
+const SOCKET = resolveSocket();
+
+/** Secret the launcher sends to POST /shutdown via the SHUTDOWN_TOKEN_HEADER header. */
+const SHUTDOWN_TOKEN = process.env["SHUTDOWN_TOKEN"];
+const SHUTDOWN_TOKEN_HEADER = process.env["SHUTDOWN_TOKEN_HEADER"] ?? "X-Shutdown-Token";
+
+/** Whether a /shutdown request carries the launcher's token (compared in constant time). */
+function hasShutdownToken(req: Request): boolean {
+  const sent = req.headers.get(SHUTDOWN_TOKEN_HEADER);
+  if (!SHUTDOWN_TOKEN || sent === null) return false;
+  const expected = Buffer.from(SHUTDOWN_TOKEN);
+  const actual = Buffer.from(sent);
+  return expected.length === actual.length && timingSafeEqual(expected, actual);
+}
+
+/** Version reported on /version, read from this package's package.json. */
+const BACKEND_VERSION = (
+  (await Bun.file(new URL("../package.json", import.meta.url)).json()) as { version: string }
+).version;
+
 // ── SQLite database & DAOs ─────────────────────────────────────────
 const db = openDatabase();
 const conversationsDao = new ConversationsDao(db);
@@ -406,36 +449,68 @@ function handleMessage(ws: ServerWebSocket<unknown>, raw: string | Buffer) {
   }
 }
 
-const server = Bun.serve({
-  port: PORT,
-  fetch(req, server) {
-    const url = new URL(req.url);
+/** HTTP routes, shared by the TCP and Unix socket listeners. */
+function handleRequest(req: Request, server: Server): Response | undefined {
+  const url = new URL(req.url);
 
-    if (url.pathname === "/ws") {
-      const upgraded = server.upgrade(req);
-      if (upgraded) return undefined;
-      return new Response("WebSocket upgrade failed", { status: 400 });
-    }
+  if (url.pathname === "/ws") {
+    const upgraded = server.upgrade(req);
+    if (upgraded) return undefined;
+    return new Response("WebSocket upgrade failed", { status: 400 });
+  }
+
+  if (url.pathname === "/health") {
+    return Response.json({ status: "ok", uptime: process.uptime() });
+  }
+
+  if (url.pathname === "/version") {
+    return Response.json({ version: BACKEND_VERSION });
+  }
 
-    if (url.pathname === "/health") {
-      return Response.json({ status: "ok", uptime: process.uptime() });
+  // Only started by the launcher (which sets SHUTDOWN_TOKEN) and only with its token.
+  if (url.pathname === "/shutdown" && SHUTDOWN_TOKEN) {
+    if (req.method !== "POST") {
+      return new Response("Method not allowed", { status: 405 });
     }
+    if (!hasShutdownToken(req)) {
+      return new Response("Forbidden", { status: 403 });
+    }
+    console.log("[shutdown] requested by the launcher");
+    setTimeout(() => process.exit(0), 0);
+    return new Response(null, { status: 202 });
+  }
 
-    return new Response("Toshik Babe Engine — WebSocket backend", {
-      status: 200,
-    });
+  return new Response("Toshik Babe Engine — WebSocket backend", {
+    status: 200,
+  });
+}
+
+const websocket = {
+  open(_ws: ServerWebSocket<unknown>) {
+    console.log("[ws] connection opened");
+  },
+  message(ws: ServerWebSocket<unknown>, message: string | Buffer) {
+    handleMessage(ws, message);
   },
-  websocket: {
-    open(_ws) {
-      console.log("[ws] connection opened");
-    },
-    message(ws, message) {
-      handleMessage(ws, message);
-    },
-    close(_ws, code, reason) {
-      console.log(`[ws] connection closed (code=${code}, reason=${reason})`);
-    },
+  close(_ws: ServerWebSocket<unknown>, code: number, reason: string) {
+    console.log(`[ws] connection closed (code=${code}, reason=${reason})`);
   },
+};
+
+const server = Bun.serve({
+  port: PORT,
+  hostname: HOSTNAME,
+  fetch: handleRequest,
+  websocket,
 });
 
 console.log(`Toshik Babe Engine backend running on http://localhost:${server.port}`);
+
+if (SOCKET) {
+  Bun.serve({ unix: SOCKET, fetch: handleRequest, websocket });
+  console.log(`Toshik Babe Engine backend also listening on unix:${SOCKET}`);
+}
+
+// Machine-readable readiness line for the launcher (TOSHIK_READY_LINE), printed once every
+// listener is up.
+console.log(JSON.stringify({ event: "ready", port: server.port }));
@@ -0,0 +1,86 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json::{json, Value};
+
+/// Append-only JSON-lines record of process-control commands (`audit.log` in app data).
+///
+/// Entries carry `timestamp` (ms since the Unix epoch), `action`, `args`, `result` and
+/// `runId`. Callers pass only non-secret arguments: for environment overrides, the variable
+/// names but never their values.
+pub(crate) struct AuditLog {
+    path: PathBuf,
+    /// Serializes appends so concurrent commands can't interleave entries.
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    pub(crate) fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Append an entry for `action`. Failures are logged, never returned: auditing must not
+    /// change the outcome of the command being audited.
+    pub(crate) fn record<T>(
+        &self,
+        action: &str,
+        args: Value,
+        result: &Result<T, String>,
+        run_id: Option<&str>,
+    ) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        let result = match result {
+            Ok(_) => json!("ok"),
+            Err(e) => json!({ "error": e }),
+        };
+        let entry = json!({
+            "timestamp": timestamp,
+            "action": action,
+            "args": args,
+            "result": result,
+            "runId": run_id,
+        });
+        if let Err(e) = self.append(&entry) {
+            log::warn!("Failed to write {}: {e}", self.path.display());
+        }
+    }
+
+    fn append(&self, entry: &Value) -> io::Result<()> {
+        let _guard = self
+            .lock
+            .lock()
+            .map_err(|_| io::Error::other("audit lock poisoned"))?;
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{entry}")
+    }
+
+    /// The last `limit` entries, oldest first. Lines that fail to parse are skipped.
+    pub(crate) fn read(&self, limit: usize) -> io::Result<Vec<Value>> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let entries: Vec<Value> = contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let skip = entries.len().saturating_sub(limit);
+        Ok(entries.into_iter().skip(skip).collect())
+    }
+}
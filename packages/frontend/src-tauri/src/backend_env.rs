@@ -0,0 +1,153 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+
+/// Fragments that make a variable name look like it holds a credential.
+const SECRET_MARKERS: &[&str] = &[
+    "KEY",
+    "SECRET",
+    "TOKEN",
+    "PASSWORD",
+    "PASSWD",
+    "CREDENTIAL",
+    "AUTH",
+];
+
+/// Shown instead of the value of a secret-looking variable.
+pub(crate) const REDACTED: &str = "********";
+
+/// Accept only portable variable names: `[A-Za-z_][A-Za-z0-9_]*`.
+pub(crate) fn validate_key(key: &str) -> Result<(), String> {
+    let mut chars = key.chars();
+    let valid_start = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    if valid_start && chars.all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid environment variable name {key:?}: use letters, digits and '_', not starting with a digit"
+        ))
+    }
+}
+
+/// Whether `key` looks like it names a secret, so its value must never be logged, audited
+/// or sent back to the webview.
+pub(crate) fn is_secret(key: &str) -> bool {
+    let key = key.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| key.contains(marker))
+}
+
+/// `value`, or [`REDACTED`] when `key` is secret-looking.
+pub(crate) fn display_value<'a>(key: &str, value: &'a str) -> &'a str {
+    if is_secret(key) {
+        REDACTED
+    } else {
+        value
+    }
+}
+
+/// `words` of a command line or argument list with credentials redacted: the value of a
+/// secret-looking `KEY=value`, and the word after a secret-looking `--option` or `Header:`
+/// (two for `Authorization: Bearer …`). Quotes around a word are kept.
+pub(crate) fn redact_words<'a>(words: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let mut redact_next = false;
+    words
+        .into_iter()
+        .map(|word| {
+            let bare = word.trim_start_matches(['-', '"', '\'']);
+            let lead = &word[..word.len() - bare.len()];
+            let quote = &bare[bare.trim_end_matches(['"', '\'']).len()..];
+            if std::mem::take(&mut redact_next) && !bare.is_empty() {
+                let scheme = bare.trim_end_matches(['"', '\'']).to_ascii_lowercase();
+                if matches!(scheme.as_str(), "bearer" | "basic" | "token") {
+                    redact_next = true;
+                    return word.to_string();
+                }
+                return format!("{lead}{REDACTED}{quote}");
+            }
+            if let Some((key, value)) = bare.split_once('=') {
+                if is_secret(key) && !value.is_empty() {
+                    return format!("{lead}{key}={REDACTED}{quote}");
+                }
+                return word.to_string();
+            }
+            let name = bare.strip_suffix(':').filter(|_| quote.is_empty());
+            if let Some(name) = name.or(word.starts_with('-').then_some(bare)) {
+                redact_next = is_secret(name);
+            }
+            word.to_string()
+        })
+        .collect()
+}
+
+/// [`redact_words`] over a command line split on spaces.
+pub(crate) fn redact_command(line: &str) -> String {
+    redact_words(line.split(' ')).join(" ")
+}
+
+/// The environment `cmd` will run with, secret-looking values redacted: the launcher's own
+/// environment (unless `inherited` is false because it was cleared) with `cmd`'s
+/// additions and removals applied.
+pub(crate) fn snapshot(cmd: &Command, inherited: bool) -> BTreeMap<String, String> {
+    let mut env: BTreeMap<String, String> = if inherited {
+        std::env::vars_os()
+            .map(|(key, value)| {
+                (
+                    key.to_string_lossy().into_owned(),
+                    value.to_string_lossy().into_owned(),
+                )
+            })
+            .collect()
+    } else {
+        BTreeMap::new()
+    };
+    for (key, value) in cmd.get_envs() {
+        let key = key.to_string_lossy().into_owned();
+        match value {
+            Some(value) => env.insert(key, value.to_string_lossy().into_owned()),
+            None => env.remove(&key),
+        };
+    }
+    for (key, value) in env.iter_mut() {
+        if is_secret(key) {
+            *value = REDACTED.to_string();
+        }
+    }
+    env
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_applies_overrides_and_redacts_secrets() {
+        let mut cmd = Command::new("bun");
+        cmd.env_clear()
+            .env("LOG_LEVEL", "debug")
+            .env("GIGACHAT_API_KEY", "sk-123");
+        let env = snapshot(&cmd, false);
+        assert_eq!(
+            env.into_iter().collect::<Vec<_>>(),
+            [
+                ("GIGACHAT_API_KEY".to_string(), REDACTED.to_string()),
+                ("LOG_LEVEL".to_string(), "debug".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn keys_are_validated_and_secrets_detected() {
+        for key in ["PORT", "_X", "log_level2"] {
+            assert!(validate_key(key).is_ok(), "{key:?} should be accepted");
+        }
+        for key in ["", "1ABC", "A-B", "A B", "PATH=x"] {
+            assert!(validate_key(key).is_err(), "{key:?} should be rejected");
+        }
+        assert!(is_secret("GIGACHAT_API_KEY"));
+        assert!(is_secret("db_password"));
+        assert!(!is_secret("LOG_LEVEL"));
+        assert_eq!(display_value("AUTH_HEADER", "Bearer x"), REDACTED);
+        assert_eq!(display_value("LOG_LEVEL", "debug"), "debug");
+    }
+}
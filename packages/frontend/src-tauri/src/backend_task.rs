@@ -0,0 +1,159 @@
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+/// Time a task may run before it is killed (`TOSHIK_TASK_TIMEOUT_MS`).
+pub(crate) const DEFAULT_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Bytes of each of stdout and stderr kept (`TOSHIK_MAX_TASK_OUTPUT_BYTES`).
+pub(crate) const DEFAULT_MAX_OUTPUT: usize = 1024 * 1024;
+
+/// How often a running task is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Result of a finished `run_backend_task`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskOutput {
+    /// `None` when the task was ended by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: String,
+    pub stderr: String,
+    /// Either stream went past the output cap and lost its tail.
+    pub truncated: bool,
+}
+
+/// Resolve `script` against `workspace`, refusing anything that isn't a file inside it.
+pub(crate) fn script_path(workspace: &Path, script: &Path) -> Result<PathBuf, String> {
+    let root = fs::canonicalize(workspace)
+        .map_err(|e| format!("Cannot resolve workspace {}: {e}", workspace.display()))?;
+    let path = fs::canonicalize(root.join(script))
+        .map_err(|e| format!("Cannot resolve task script {}: {e}", script.display()))?;
+    if !path.starts_with(&root) || !path.is_file() {
+        return Err(format!(
+            "Task script {} is not a file in the workspace",
+            script.display()
+        ));
+    }
+    Ok(path)
+}
+
+/// Keep the first `max` bytes of `reader` and discard the rest, so the task never blocks on
+/// a full pipe. Returns what was kept and whether anything was dropped.
+fn capture(mut reader: impl Read, max: usize) -> (Vec<u8>, bool) {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0; 8192];
+    loop {
+        match reader.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                let room = max.saturating_sub(kept.len());
+                kept.extend_from_slice(&buf[..n.min(room)]);
+                truncated |= n > room;
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => break,
+        }
+    }
+    (kept, truncated)
+}
+
+/// Run `cmd` to completion with its output captured, killing it once `timeout` passes.
+pub(crate) fn run(
+    mut cmd: Command,
+    timeout: Duration,
+    max_output: usize,
+) -> Result<TaskOutput, String> {
+    let mut child = cmd
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to start task: {e}"))?;
+    let stdout = child
+        .stdout
+        .take()
+        .map(|out| thread::spawn(move || capture(out, max_output)));
+    let stderr = child
+        .stderr
+        .take()
+        .map(|err| thread::spawn(move || capture(err, max_output)));
+
+    let deadline = Instant::now() + timeout;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) if Instant::now() >= deadline => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!(
+                    "Task did not finish within {timeout:?} and was killed"
+                ));
+            }
+            Ok(None) => thread::sleep(POLL_INTERVAL),
+            Err(e) => return Err(format!("Failed to wait for task: {e}")),
+        }
+    };
+
+    let collect = |reader: Option<thread::JoinHandle<(Vec<u8>, bool)>>| {
+        reader
+            .and_then(|handle| handle.join().ok())
+            .unwrap_or_default()
+    };
+    let (stdout, stdout_truncated) = collect(stdout);
+    let (stderr, stderr_truncated) = collect(stderr);
+    Ok(TaskOutput {
+        exit_code: status.code(),
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        truncated: stdout_truncated || stderr_truncated,
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    fn sh(script: &str) -> Command {
+        let mut cmd = Command::new("sh");
+        cmd.args(["-c", script]);
+        cmd
+    }
+
+    #[test]
+    fn output_is_capped_and_slow_tasks_are_killed() {
+        let timeout = Duration::from_secs(10);
+        let output = run(sh("printf 0123456789; echo oops >&2; exit 4"), timeout, 4).unwrap();
+        assert_eq!(output.exit_code, Some(4));
+        assert_eq!(output.stdout, "0123");
+        assert_eq!(output.stderr, "oops");
+        assert!(output.truncated);
+
+        let started = Instant::now();
+        let err = run(sh("sleep 5"), Duration::from_millis(100), 4).unwrap_err();
+        assert!(err.contains("killed"), "{err}");
+        assert!(started.elapsed() < Duration::from_secs(4));
+    }
+
+    #[test]
+    fn scripts_must_stay_inside_the_workspace() {
+        let dir = std::env::temp_dir().join(format!("toshik-task-{}", uuid::Uuid::new_v4()));
+        let workspace = dir.join("workspace");
+        fs::create_dir_all(workspace.join("scripts")).unwrap();
+        fs::write(workspace.join("scripts/seed.ts"), "").unwrap();
+        fs::write(dir.join("outside.ts"), "").unwrap();
+
+        let seed = script_path(&workspace, Path::new("scripts/seed.ts")).unwrap();
+        assert!(seed.ends_with("scripts/seed.ts"));
+        assert!(script_path(&workspace, Path::new("../outside.ts")).is_err());
+        assert!(script_path(&workspace, &dir.join("outside.ts")).is_err());
+        assert!(script_path(&workspace, Path::new("scripts")).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
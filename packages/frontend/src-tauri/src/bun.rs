@@ -0,0 +1,496 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::{Mutex, PoisonError};
+
+use serde::{Serialize, Serializer};
+
+use crate::error::BackendError;
+
+/// bun releases before 1.0 do not understand `--env-file`.
+pub(crate) const ENV_FILE_MIN_VERSION: BunVersion = BunVersion::new(1, 0, 0);
+
+/// File name of the bun executable on this platform.
+const EXECUTABLE_NAME: &str = if cfg!(windows) { "bun.exe" } else { "bun" };
+
+/// Extensions of sources bun interprets, which `Invocation::Direct` can't execute.
+const SCRIPT_EXTENSIONS: &[&str] = &["ts", "tsx", "mts", "cts", "js", "jsx", "mjs", "cjs"];
+
+/// Versions reported by `bun --version`, detected once per session for each executable.
+static DETECTED_VERSIONS: Mutex<DetectedVersions> = Mutex::new(DetectedVersions {
+    by_path: BTreeMap::new(),
+    last: None,
+});
+
+struct DetectedVersions {
+    /// Keyed by canonical path, so changing `bunPath` is noticed but symlinks aren't rerun.
+    by_path: BTreeMap<PathBuf, BunVersion>,
+    /// The executable checked most recently, for `detected_version`.
+    last: Option<BunVersion>,
+}
+
+/// A `major.minor.patch` version of bun (or of the backend, for `verify_backend_version`).
+/// Pre-release and build suffixes are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct BunVersion {
+    major: u64,
+    minor: u64,
+    patch: u64,
+}
+
+impl BunVersion {
+    pub(crate) const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse `1.1.38`, `v1.2.0` or `1.2.0-canary.5+abc`; missing minor/patch default to 0.
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        let core = raw.trim().trim_start_matches('v');
+        let core = core.split(['-', '+']).next()?;
+        let mut parts = core.split('.');
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        let patch = parts.next().map_or(Some(0), |p| p.parse().ok())?;
+        if parts.next().is_some() {
+            return None;
+        }
+        Some(Self::new(major, minor, patch))
+    }
+
+    /// Whether this satisfies a caret requirement on `base`: the same major version (the
+    /// same minor one below 1.0) and not older.
+    pub(crate) fn is_compatible_with(self, base: Self) -> bool {
+        let same_line = if base.major == 0 {
+            self.major == 0 && self.minor == base.minor
+        } else {
+            self.major == base.major
+        };
+        same_line && self >= base
+    }
+}
+
+impl Serialize for BunVersion {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl fmt::Display for BunVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// How the backend entry is started (`TOSHIK_BUN_INVOCATION`). The launcher's `--port`,
+/// `--host` and `--run-id` arguments follow the entry in every case.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Invocation {
+    /// `bun run <script>` (default).
+    Run,
+    /// `bun x <package>`, for a backend published as a package binary.
+    X,
+    /// Execute a prebuilt bundle (`bun build --compile`) itself; bun is not involved.
+    Direct,
+}
+
+impl Invocation {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "run" => Some(Self::Run),
+            "x" => Some(Self::X),
+            "direct" => Some(Self::Direct),
+            _ => None,
+        }
+    }
+
+    /// The bun subcommand, or `None` when the entry is executed itself.
+    pub(crate) fn subcommand(self) -> Option<&'static str> {
+        match self {
+            Self::Run => Some("run"),
+            Self::X => Some("x"),
+            Self::Direct => None,
+        }
+    }
+
+    /// Reject combinations this invocation can't start: `entry` is `TOSHIK_BACKEND_ENTRY`
+    /// (`None` means the resolved `index.ts`), `inspect` whether the inspector is wanted.
+    pub(crate) fn validate(self, entry: Option<&Path>, inspect: bool) -> Result<(), BackendError> {
+        let invalid = |reason: String| BackendError::InvocationInvalid {
+            invocation: self,
+            reason,
+        };
+        match self {
+            Self::Run => Ok(()),
+            Self::X if entry.is_none() => Err(invalid(
+                "TOSHIK_BACKEND_ENTRY must name the package to run".into(),
+            )),
+            Self::X => Ok(()),
+            Self::Direct => {
+                let entry = entry.ok_or_else(|| {
+                    invalid("TOSHIK_BACKEND_ENTRY must name the prebuilt executable".into())
+                })?;
+                let is_script = entry
+                    .extension()
+                    .and_then(|ext| ext.to_str())
+                    .is_some_and(|ext| {
+                        SCRIPT_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str())
+                    });
+                if is_script {
+                    return Err(invalid(format!(
+                        "{} is a script, not an executable; use run",
+                        entry.display()
+                    )));
+                }
+                if inspect {
+                    return Err(invalid("the inspector needs bun run or bun x".into()));
+                }
+                executable_problem(entry).map_or(Ok(()), |reason| {
+                    Err(invalid(format!("{}: {reason}", entry.display())))
+                })
+            }
+        }
+    }
+}
+
+impl fmt::Display for Invocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Run => "run",
+            Self::X => "x",
+            Self::Direct => "direct",
+        })
+    }
+}
+
+/// Where bun's inspector listens when the backend is started with `--inspect`. The random
+/// path keeps other local processes from guessing the URL.
+#[derive(Debug, Clone)]
+pub(crate) struct Inspector {
+    pub addr: SocketAddr,
+    pub path: String,
+}
+
+impl Inspector {
+    /// The flag passed to `bun run`.
+    pub(crate) fn flag(&self) -> String {
+        format!("--inspect={}/{}", self.addr, self.path)
+    }
+
+    /// WebSocket URL a debugger (e.g. debug.bun.sh) connects to.
+    pub(crate) fn url(&self) -> String {
+        format!("ws://{}/{}", self.addr, self.path)
+    }
+}
+
+/// `--env-file=<path>`, built from the raw `OsStr` so paths with spaces or that aren't
+/// valid UTF-8 reach bun unchanged (`display()` would replace invalid bytes).
+pub(crate) fn env_file_arg(path: &Path) -> OsString {
+    let mut arg = OsString::from("--env-file=");
+    arg.push(path.as_os_str());
+    arg
+}
+
+/// Whether `output` from a backend that failed to start says `port` was taken, either as
+/// `Bun.serve` reports it (`Failed to start server. Is port 3001 in use?`) or as a
+/// Node-style `listen` error (`EADDRINUSE: address already in use`).
+pub(crate) fn reports_port_in_use(output: &str, port: u16) -> bool {
+    let output = output.to_ascii_lowercase();
+    output.contains("eaddrinuse")
+        || output.contains("address already in use")
+        || output.contains(&format!("is port {port} in use"))
+}
+
+/// The bun to run: `configured` (`TOSHIK_BUN_PATH`) if set, else plain `bun` resolved
+/// through `PATH`. A configured directory gets the platform's executable name appended;
+/// either way the result must be an executable file.
+pub(crate) fn executable(configured: Option<&Path>) -> Result<PathBuf, BackendError> {
+    let Some(path) = configured else {
+        return Ok(PathBuf::from("bun"));
+    };
+    let path = if path.is_dir() {
+        path.join(EXECUTABLE_NAME)
+    } else {
+        path.to_path_buf()
+    };
+    match executable_problem(&path) {
+        Some(reason) => Err(BackendError::BunPathInvalid { path, reason }),
+        None => Ok(path),
+    }
+}
+
+/// Why `path` can't be executed, or `None` if it is an executable file.
+fn executable_problem(path: &Path) -> Option<String> {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) => return Some(e.to_string()),
+    };
+    if !metadata.is_file() {
+        return Some("not a file".into());
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        if metadata.permissions().mode() & 0o111 == 0 {
+            return Some("not executable".into());
+        }
+    }
+    None
+}
+
+/// Run `bun <arg>` and return its trimmed stdout.
+fn run_bun(bun: &Path, arg: &str) -> Result<String, BackendError> {
+    let output = Command::new(bun).arg(arg).output().map_err(|e| {
+        if e.kind() == io::ErrorKind::NotFound {
+            BackendError::BunNotFound(e)
+        } else {
+            BackendError::BunCheckFailed(format!("could not run `bun {arg}`: {e}"))
+        }
+    })?;
+    if !output.status.success() {
+        return Err(BackendError::BunCheckFailed(format!(
+            "`bun {arg}` exited with {}",
+            output.status
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn detect_version(bun: &Path) -> Result<BunVersion, BackendError> {
+    let stdout = run_bun(bun, "--version")?;
+    BunVersion::parse(&stdout).ok_or_else(|| {
+        BackendError::BunCheckFailed(format!("unrecognised `bun --version` output: {stdout:?}"))
+    })
+}
+
+/// Detect the version of `bun` once and cache it; failures are not cached so a later
+/// attempt can succeed after bun is installed.
+fn version(bun: &Path) -> Result<BunVersion, BackendError> {
+    let key = fs::canonicalize(bun).unwrap_or_else(|_| bun.to_path_buf());
+    let detected = || {
+        DETECTED_VERSIONS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    };
+    let cached = detected().by_path.get(&key).copied();
+    let version = match cached {
+        Some(version) => version,
+        None => {
+            let version = detect_version(bun)?;
+            log::info!("Detected bun {version} at {}", key.display());
+            version
+        }
+    };
+    let mut detected = detected();
+    detected.by_path.insert(key, version);
+    detected.last = Some(version);
+    Ok(version)
+}
+
+/// The version of the bun last checked before a spawn, if bun has been run yet.
+pub(crate) fn detected_version() -> Option<BunVersion> {
+    DETECTED_VERSIONS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .last
+}
+
+/// Return the version of `bun`, or `BunTooOld` if it is below `required`.
+pub(crate) fn ensure_compatible(
+    bun: &Path,
+    required: BunVersion,
+) -> Result<BunVersion, BackendError> {
+    let found = version(bun)?;
+    if found < required {
+        return Err(BackendError::BunTooOld { found, required });
+    }
+    Ok(found)
+}
+
+/// Toolchain details reported by `check_bun`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BunInfo {
+    /// Resolved executable (`TOSHIK_BUN_PATH` if set), or plain `bun` if it could not be
+    /// located on `PATH`.
+    pub path: String,
+    pub version: String,
+    /// Output of `bun --revision`, e.g. `1.1.38+bf2f153f5`.
+    pub revision: String,
+}
+
+/// Probe the bun toolchain without touching the cached version used for spawning.
+pub(crate) fn check(configured: Option<&Path>) -> Result<BunInfo, BackendError> {
+    let bun = executable(configured)?;
+    let version = detect_version(&bun)?;
+    let revision = run_bun(&bun, "--revision")?;
+    let path = if configured.is_some() {
+        Some(bun)
+    } else {
+        find_on_path()
+    }
+    .map(|p| p.display().to_string())
+    .unwrap_or_else(|| "bun".to_string());
+    Ok(BunInfo {
+        path,
+        version: version.to_string(),
+        revision,
+    })
+}
+
+/// Locate the `bun` executable the OS would pick when spawning by name.
+fn find_on_path() -> Option<PathBuf> {
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(EXECUTABLE_NAME))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh directory holding an executable named like bun, removed on drop.
+    struct FakeBun(PathBuf);
+
+    impl FakeBun {
+        fn new() -> Self {
+            let dir = env::temp_dir().join(format!("toshik-bun-{}", uuid::Uuid::new_v4()));
+            fs::create_dir_all(&dir).unwrap();
+            let exe = dir.join(EXECUTABLE_NAME);
+            fs::write(&exe, "#!/bin/sh\n").unwrap();
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                fs::set_permissions(&exe, fs::Permissions::from_mode(0o755)).unwrap();
+            }
+            Self(dir)
+        }
+    }
+
+    impl Drop for FakeBun {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn directory_gets_the_platform_executable_name() {
+        let fake = FakeBun::new();
+        assert_eq!(
+            executable(Some(&fake.0)).unwrap(),
+            fake.0.join(EXECUTABLE_NAME)
+        );
+    }
+
+    #[test]
+    fn file_path_is_used_as_is() {
+        let fake = FakeBun::new();
+        let exe = fake.0.join(EXECUTABLE_NAME);
+        assert_eq!(executable(Some(&exe)).unwrap(), exe);
+    }
+
+    #[test]
+    fn unusable_paths_are_rejected() {
+        let fake = FakeBun::new();
+        assert!(executable(Some(&fake.0.join("missing"))).is_err());
+
+        let empty = fake.0.join("empty");
+        fs::create_dir(&empty).unwrap();
+        assert!(executable(Some(&empty)).is_err(), "directory without bun");
+
+        #[cfg(unix)]
+        {
+            let plain = fake.0.join("plain");
+            fs::write(&plain, "").unwrap();
+            assert!(executable(Some(&plain)).is_err(), "file without exec bit");
+        }
+    }
+
+    #[test]
+    fn unset_falls_back_to_path_lookup() {
+        assert_eq!(executable(None).unwrap(), PathBuf::from("bun"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn versions_are_cached_per_executable() {
+        let (old, new) = (FakeBun::new(), FakeBun::new());
+        let (old_exe, new_exe) = (old.0.join(EXECUTABLE_NAME), new.0.join(EXECUTABLE_NAME));
+        fs::write(&old_exe, "#!/bin/sh\necho 0.9.0\n").unwrap();
+        fs::write(&new_exe, "#!/bin/sh\necho 1.2.3\n").unwrap();
+
+        let required = BunVersion::new(1, 0, 0);
+        assert!(matches!(
+            ensure_compatible(&old_exe, required),
+            Err(BackendError::BunTooOld { .. })
+        ));
+        assert_eq!(
+            ensure_compatible(&new_exe, required).unwrap(),
+            BunVersion::new(1, 2, 3)
+        );
+        // Cached per executable: the script isn't run again.
+        fs::write(&old_exe, "#!/bin/sh\nexit 1\n").unwrap();
+        assert_eq!(version(&old_exe).unwrap(), BunVersion::new(0, 9, 0));
+    }
+
+    #[test]
+    fn env_file_arg_keeps_the_path_verbatim() {
+        let path = Path::new("/home/user/my work/проект/.env");
+        assert_eq!(
+            env_file_arg(path),
+            OsString::from("--env-file=/home/user/my work/проект/.env")
+        );
+
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::{OsStrExt, OsStringExt};
+            let raw = OsStr::from_bytes(b"/tmp/caf\xe9 dir/.env");
+            let arg = env_file_arg(Path::new(raw)).into_vec();
+            assert_eq!(arg, [&b"--env-file="[..], raw.as_bytes()].concat());
+        }
+    }
+
+    #[test]
+    fn direct_invocation_needs_an_executable_entry() {
+        let fake = FakeBun::new();
+        let exe = fake.0.join(EXECUTABLE_NAME);
+        assert!(Invocation::Direct.validate(Some(&exe), false).is_ok());
+        assert!(Invocation::Direct.validate(Some(&exe), true).is_err());
+        assert!(Invocation::Direct.validate(None, false).is_err());
+
+        let script = fake.0.join("index.TS");
+        fs::write(&script, "").unwrap();
+        assert!(Invocation::Direct.validate(Some(&script), false).is_err());
+        assert!(Invocation::Run.validate(Some(&script), true).is_ok());
+        assert!(Invocation::X.validate(None, false).is_err());
+    }
+
+    #[test]
+    fn port_in_use_is_recognised_in_bun_and_node_errors() {
+        for output in [
+            "error: Failed to start server. Is port 3001 in use?\n  syscall: \"listen\"",
+            "EADDRINUSE: Failed to start server",
+            "Error: listen EADDRINUSE: address already in use 127.0.0.1:3001",
+        ] {
+            assert!(reports_port_in_use(output, 3001), "{output}");
+        }
+        for output in [
+            "error: Cannot find module \"hono\"",
+            "Listening on port 3001",
+            "Failed to start server. Is port 3002 in use?",
+        ] {
+            assert!(!reports_port_in_use(output, 3001), "{output}");
+        }
+    }
+}
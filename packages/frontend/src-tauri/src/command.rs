@@ -0,0 +1,302 @@
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::bun::{self, BunVersion};
+use crate::config::LauncherConfig;
+use crate::http::ShutdownToken;
+use crate::shell;
+
+/// Program, arguments and environment the backend is started with, worked out without
+/// spawning anything. The backend inherits the launcher's working directory.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CommandSpec {
+    pub program: PathBuf,
+    pub args: Vec<OsString>,
+    /// Start from an empty environment instead of the launcher's (`TOSHIK_ISOLATED_ENV`).
+    pub env_clear: bool,
+    /// Set on top of the starting environment.
+    pub env: BTreeMap<OsString, OsString>,
+}
+
+/// The parts of a launch that are decided before its command is built.
+pub(crate) struct LaunchInputs<'a> {
+    /// bun, its subcommand and version; `None` when the entry is executed itself.
+    pub bun: Option<(&'a Path, &'static str, BunVersion)>,
+    pub script: &'a Path,
+    /// `.env` next to the resolved script, passed to bun if it exists.
+    pub env_file: Option<&'a Path>,
+    pub port: u16,
+    pub run_id: &'a str,
+    pub socket: Option<&'a Path>,
+    pub shutdown_token: &'a ShutdownToken,
+    /// Values read from the vault for `vault_secrets`.
+    pub secrets: &'a BTreeMap<String, String>,
+    /// Per-launch variables given to `start_backend`.
+    pub env: Option<&'a HashMap<String, String>>,
+    /// Looks up a variable of the launcher's own environment, for `env_allowlist`.
+    pub parent_env: &'a dyn Fn(&str) -> Option<OsString>,
+}
+
+/// Combine `config` with `launch` into the backend's command line. Later variable layers
+/// win: allowlist, `backend_env`, vault secrets, per-launch `env`, then run id and token.
+pub(crate) fn build_command_spec(config: &LauncherConfig, launch: &LaunchInputs) -> CommandSpec {
+    let (program, mut args) = match launch.bun {
+        Some((bun, subcommand, _)) => (bun.to_path_buf(), vec![OsString::from(subcommand)]),
+        None => (launch.script.to_path_buf(), Vec::new()),
+    };
+
+    let mut env = BTreeMap::new();
+    if config.isolated_env {
+        for key in &config.env_allowlist {
+            if let Some(value) = (launch.parent_env)(key) {
+                env.insert(key.into(), value);
+            }
+        }
+    }
+    let overrides = config.settings.backend_env.iter().chain(launch.secrets);
+    for (key, value) in overrides.chain(launch.env.into_iter().flatten()) {
+        env.insert(key.into(), value.into());
+    }
+    env.insert("TOSHIK_RUN_ID".into(), launch.run_id.into());
+    env.insert(
+        "SHUTDOWN_TOKEN".into(),
+        (&launch.shutdown_token.secret).into(),
+    );
+    env.insert(
+        "SHUTDOWN_TOKEN_HEADER".into(),
+        (&launch.shutdown_token.header).into(),
+    );
+
+    if let Some(env_path) = launch.env_file.filter(|path| path.exists()) {
+        match launch.bun {
+            Some((_, _, version)) if version >= bun::ENV_FILE_MIN_VERSION => {
+                args.push(bun::env_file_arg(env_path));
+            }
+            Some((_, _, version)) => log::warn!(
+                "bun {version} does not support --env-file, not passing {}",
+                env_path.display()
+            ),
+            None => log::info!(
+                "--env-file is a bun flag, not passing {} to a direct executable",
+                env_path.display()
+            ),
+        }
+    }
+
+    if let Some(ref inspector) = config.inspector {
+        args.push(inspector.flag().into());
+    }
+
+    if launch.bun.is_some() {
+        args.push(launch.script.into());
+    }
+    args.extend([
+        "--port".into(),
+        launch.port.to_string().into(),
+        "--host".into(),
+        config.host.to_string().into(),
+        "--run-id".into(),
+        launch.run_id.into(),
+    ]);
+    if let Some(socket) = launch.socket {
+        args.extend(["--socket".into(), socket.into()]);
+    }
+
+    CommandSpec {
+        program,
+        args,
+        env_clear: config.isolated_env,
+        env,
+    }
+}
+
+impl CommandSpec {
+    /// A `Command` for this spec; stdio is left to the caller.
+    pub(crate) fn command(&self) -> Command {
+        let mut cmd = Command::new(&self.program);
+        cmd.args(&self.args);
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        cmd.envs(&self.env);
+        cmd
+    }
+
+    /// Like [`command`](Self::command), but run through `shell -lc 'exec …'` so the backend
+    /// starts with the environment of a login shell. `exec` keeps the pid, so signals still
+    /// reach the backend itself.
+    #[cfg(unix)]
+    pub(crate) fn login_shell_command(&self, shell: &Path) -> Command {
+        let mut cmd = Command::new(shell);
+        cmd.arg("-lc")
+            .arg(shell::exec_line(self.program.as_os_str(), &self.args));
+        if self.env_clear {
+            cmd.env_clear();
+        }
+        cmd.envs(&self.env);
+        cmd
+    }
+
+    #[cfg(not(unix))]
+    pub(crate) fn login_shell_command(&self, _shell: &Path) -> Command {
+        self.command()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bun::Inspector;
+    use std::fs;
+
+    fn count(args: &[OsString], flag: &str) -> usize {
+        args.iter().filter(|arg| *arg == flag).count()
+    }
+
+    fn value_after<'a>(args: &'a [OsString], flag: &str) -> Option<&'a OsString> {
+        let at = args.iter().position(|arg| arg == flag)?;
+        args.get(at + 1)
+    }
+
+    #[test]
+    fn invariants_hold_across_config_permutations() {
+        let dir = std::env::temp_dir().join(format!("toshik-spec-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join(".env");
+        fs::write(&existing, "A=1\n").unwrap();
+        let missing = dir.join("missing.env");
+        let script = dir.join("index.ts");
+        let bun_path = PathBuf::from("bun");
+        let socket = dir.join("run.sock");
+        let token = ShutdownToken::generate("X-Shutdown-Token");
+        let secrets = BTreeMap::from([("VAULT_TOKEN".to_string(), "from-vault".to_string())]);
+        let parent_env = |key: &str| (key == "PATH").then(|| OsString::from("/usr/bin"));
+        // A caller passing the launcher's own names must not displace them.
+        let hostile = HashMap::from([
+            ("PORT".to_string(), "1".to_string()),
+            ("TOSHIK_RUN_ID".to_string(), "forged".to_string()),
+        ]);
+
+        let buns = [
+            None,
+            Some((bun_path.as_path(), "run", BunVersion::new(0, 9, 0))),
+            Some((bun_path.as_path(), "run", BunVersion::new(1, 1, 0))),
+            Some((bun_path.as_path(), "x", BunVersion::new(1, 1, 0))),
+        ];
+        let env_files = [None, Some(existing.as_path()), Some(missing.as_path())];
+        let inspectors = [
+            None,
+            Some(Inspector {
+                addr: "127.0.0.1:6499".parse().unwrap(),
+                path: "abc".into(),
+            }),
+        ];
+        let mut cases = Vec::new();
+        for bun in buns {
+            for env_file in env_files {
+                for inspector in &inspectors {
+                    for isolated in [false, true] {
+                        for socket in [None, Some(socket.as_path())] {
+                            for env in [None, Some(&hostile)] {
+                                cases.push((bun, env_file, inspector, isolated, socket, env));
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        assert_eq!(cases.len(), 4 * 3 * 2 * 2 * 2 * 2);
+
+        for (i, (bun, env_file, inspector, isolated, socket, env)) in cases.into_iter().enumerate()
+        {
+            let case = format!("{bun:?} {env_file:?} {inspector:?} {isolated} {socket:?} {env:?}");
+            let mut config = LauncherConfig {
+                inspector: inspector.clone(),
+                isolated_env: isolated,
+                env_allowlist: vec!["PATH".into(), "HOME".into()],
+                ..LauncherConfig::default()
+            };
+            config
+                .settings
+                .backend_env
+                .insert("PORT".into(), "2".into());
+            let port = 3000 + i as u16;
+            let launch = LaunchInputs {
+                bun,
+                script: &script,
+                env_file,
+                port,
+                run_id: "run-1",
+                socket,
+                shutdown_token: &token,
+                secrets: &secrets,
+                env,
+                parent_env: &parent_env,
+            };
+            let spec = build_command_spec(&config, &launch);
+            let args = &spec.args;
+
+            assert_eq!(count(args, "--port"), 1, "{case}");
+            assert_eq!(
+                value_after(args, "--port"),
+                Some(&port.to_string().into()),
+                "{case}"
+            );
+            assert_eq!(count(args, "--host"), 1, "{case}");
+            assert_eq!(count(args, "--run-id"), 1, "{case}");
+            assert_eq!(
+                count(args, "--socket"),
+                usize::from(socket.is_some()),
+                "{case}"
+            );
+
+            let starting = |prefix: &str| {
+                args.iter()
+                    .filter(|arg| arg.to_string_lossy().starts_with(prefix))
+                    .count()
+            };
+            let supported = matches!(bun, Some((_, _, v)) if v >= bun::ENV_FILE_MIN_VERSION);
+            if env_file == Some(existing.as_path()) && supported {
+                assert!(args.contains(&bun::env_file_arg(&existing)), "{case}");
+                assert_eq!(starting("--env-file"), 1, "{case}");
+            } else {
+                assert_eq!(starting("--env-file"), 0, "{case}");
+            }
+            assert_eq!(
+                starting("--inspect"),
+                usize::from(inspector.is_some()),
+                "{case}"
+            );
+
+            let script_args = args.iter().filter(|arg| *arg == script.as_os_str()).count();
+            match bun {
+                Some((path, subcommand, _)) => {
+                    assert_eq!(spec.program, path, "{case}");
+                    assert_eq!(args[0], subcommand, "{case}");
+                    assert_eq!(script_args, 1, "{case}");
+                }
+                None => {
+                    assert_eq!(spec.program, script, "{case}");
+                    assert_eq!(script_args, 0, "{case}");
+                }
+            }
+
+            let var = |key: &str| spec.env.get(&OsString::from(key)).cloned();
+            assert_eq!(spec.env_clear, isolated, "{case}");
+            assert_eq!(var("TOSHIK_RUN_ID"), Some("run-1".into()), "{case}");
+            assert_eq!(
+                var("SHUTDOWN_TOKEN"),
+                Some(token.secret.clone().into()),
+                "{case}"
+            );
+            let expected_port = if env.is_some() { "1" } else { "2" };
+            assert_eq!(var("PORT"), Some(expected_port.into()), "{case}");
+            assert_eq!(var("PATH").is_some(), isolated, "{case}");
+            assert_eq!(var("VAULT_TOKEN"), Some("from-vault".into()), "{case}");
+            assert_eq!(var("HOME"), None, "{case}");
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,980 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::backend_env;
+use crate::backend_task;
+use crate::bun::{BunVersion, Inspector, Invocation};
+use crate::container::{self, ContainerConfig};
+use crate::diagnostics::DumpSignal;
+use crate::http::{self, HttpConfig};
+use crate::integrity;
+use crate::logs::{
+    self, LineOptions, LinePrefix, LogFormat, LogMode, LogSink, SessionBanner, StreamTaps,
+};
+use crate::net::{self, Transport};
+use crate::paths::AppPaths;
+use crate::ready::{BodyMatcher, HealthCheck, ProbeKind, ReadyEndpoint};
+use crate::resolve::PathMode;
+use crate::schedule::SchedulePolicy;
+use crate::settings::Settings;
+use crate::syslog;
+
+/// Oldest bun release the launcher is known to work with.
+const DEFAULT_MIN_BUN_VERSION: BunVersion = BunVersion::new(1, 0, 0);
+
+/// Variables an isolated backend still inherits: enough to find executables, a home and a
+/// temp directory (`SYSTEMROOT` keeps Windows networking working).
+const DEFAULT_ENV_ALLOWLIST: &[&str] = &[
+    "PATH",
+    "HOME",
+    "USERPROFILE",
+    "TMPDIR",
+    "TEMP",
+    "TMP",
+    "SYSTEMROOT",
+];
+
+/// Environment variable behind each field of the serialized config. Fields missing here
+/// only come from defaults or [`Settings`].
+const ENV_VARS: &[(&str, &str)] = &[
+    ("minBunVersion", "TOSHIK_MIN_BUN_VERSION"),
+    ("bunPath", "TOSHIK_BUN_PATH"),
+    ("bunInvocation", "TOSHIK_BUN_INVOCATION"),
+    ("useLoginShell", "TOSHIK_LOGIN_SHELL"),
+    ("backendEntry", "TOSHIK_BACKEND_ENTRY"),
+    ("verifyIntegrity", "TOSHIK_SKIP_INTEGRITY_CHECK"),
+    ("isolatedEnv", "TOSHIK_ISOLATED_ENV"),
+    ("envAllowlist", "TOSHIK_ENV_ALLOWLIST"),
+    ("vaultSecrets", "TOSHIK_VAULT_SECRETS"),
+    ("streamLogs", "TOSHIK_STREAM_LOGS"),
+    ("normalizeOutput", "TOSHIK_RAW_OUTPUT"),
+    ("maxLogLine", "TOSHIK_MAX_LOG_LINE_BYTES"),
+    ("maxLogDir", "TOSHIK_MAX_LOG_DIR_BYTES"),
+    ("crashLines", "TOSHIK_CRASH_LINES"),
+    ("logSink", "TOSHIK_LOG_SINK"),
+    ("logMode", "TOSHIK_LOG_MODE"),
+    ("logPrefix", "TOSHIK_LOG_PREFIX"),
+    ("logBanner", "TOSHIK_LOG_BANNER"),
+    ("quiet", "TOSHIK_QUIET"),
+    ("backendLogFormat", "TOSHIK_BACKEND_LOG_FORMAT"),
+    ("startOnFrontendReady", "TOSHIK_START_ON_READY"),
+    ("autostart", "TOSHIK_AUTOSTART"),
+    ("workersEnv", "TOSHIK_WORKERS_ENV"),
+    ("autoWorkers", "TOSHIK_AUTO_WORKERS"),
+    ("uvThreadpool", "TOSHIK_UV_THREADPOOL"),
+    ("warmPorts", "TOSHIK_WARM_PORTS"),
+    ("allowPrivilegedPorts", "TOSHIK_ALLOW_PRIVILEGED_PORTS"),
+    ("detached", "TOSHIK_DETACHED"),
+    ("restartSchedulePolicy", "TOSHIK_RESTART_SCHEDULE_POLICY"),
+    ("hangDiagnostics", "TOSHIK_HANG_DIAGNOSTICS"),
+    ("hangSignal", "TOSHIK_HANG_SIGNAL"),
+    ("scriptPathMode", "TOSHIK_SCRIPT_PATHS"),
+    ("ephemeralPort", "TOSHIK_EPHEMERAL_PORT"),
+    ("host", "TOSHIK_IPV6"),
+    ("migrationCommand", "TOSHIK_MIGRATION_COMMAND"),
+    ("taskTimeoutMs", "TOSHIK_TASK_TIMEOUT_MS"),
+    ("maxTaskOutput", "TOSHIK_MAX_TASK_OUTPUT_BYTES"),
+    ("transport", "TOSHIK_TRANSPORT"),
+    ("readyRequireAll", "TOSHIK_READY_REQUIRE_ALL"),
+    ("readyLine", "TOSHIK_READY_LINE"),
+    ("readyEndpoints", "TOSHIK_READY_ENDPOINTS"),
+    ("forcePort", "TOSHIK_FORCE_PORT"),
+    ("warmupPath", "TOSHIK_WARMUP_PATH"),
+    ("warmupRequired", "TOSHIK_WARMUP_REQUIRED"),
+    ("postStartHook", "TOSHIK_POST_START_HOOK"),
+    ("postStartHookFatal", "TOSHIK_POST_START_HOOK_NONFATAL"),
+    ("container.image", "TOSHIK_CONTAINER_IMAGE"),
+    ("container.runtime", "TOSHIK_CONTAINER_RUNTIME"),
+    ("container.args", "TOSHIK_CONTAINER_ARGS"),
+    ("http.connectTimeoutMs", "TOSHIK_HTTP_CONNECT_TIMEOUT_MS"),
+    ("http.readTimeoutMs", "TOSHIK_HTTP_READ_TIMEOUT_MS"),
+    ("http.getRetries", "TOSHIK_HTTP_RETRIES"),
+    ("http.proxyConcurrency", "TOSHIK_PROXY_CONCURRENCY"),
+    ("http.proxyQueueTimeoutMs", "TOSHIK_PROXY_QUEUE_TIMEOUT_MS"),
+    ("http.proxyMaxResponse", "TOSHIK_PROXY_MAX_RESPONSE_BYTES"),
+    ("http.proxyReadTimeoutMs", "TOSHIK_PROXY_READ_TIMEOUT_MS"),
+    ("shutdown.termGraceMs", "TOSHIK_TERM_GRACE_MS"),
+    ("shutdown.killReapMs", "TOSHIK_KILL_REAP_MS"),
+    ("shutdownTokenHeader", "TOSHIK_SHUTDOWN_TOKEN_HEADER"),
+    ("healthCheck.probe", "TOSHIK_READINESS_PROBE"),
+    ("healthCheck.path", "TOSHIK_HEALTH_PATH"),
+    ("healthCheck.matcher", "TOSHIK_HEALTH_MATCH"),
+];
+
+/// Fields of the serialized config that are structs of their own, with their fields
+/// addressed as `http.getRetries`.
+pub(crate) const SECTIONS: &[&str] = &["container", "http", "shutdown", "healthCheck", "settings"];
+
+/// The environment variable behind `field`, named as in [`ENV_VARS`].
+pub(crate) fn env_var(field: &str) -> Option<&'static str> {
+    ENV_VARS
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|&(_, var)| var)
+}
+
+/// How long stopping a spawned backend may take. The worst case is `term_grace +
+/// kill_reap`, plus up to 2 s for log readers to drain when log streaming is on.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ShutdownTimeouts {
+    /// Time between SIGTERM and SIGKILL (`TOSHIK_TERM_GRACE_MS`, default 3000), and
+    /// before that, between an accepted `POST /shutdown` and SIGTERM. Windows has no
+    /// SIGTERM, so there only the `/shutdown` request is graceful.
+    #[serde(rename = "termGraceMs", serialize_with = "http::millis")]
+    pub term_grace: Duration,
+    /// Time to wait for the killed process to be reaped before giving up on it
+    /// (`TOSHIK_KILL_REAP_MS`, default 1000).
+    #[serde(rename = "killReapMs", serialize_with = "http::millis")]
+    pub kill_reap: Duration,
+}
+
+impl Default for ShutdownTimeouts {
+    fn default() -> Self {
+        Self {
+            term_grace: Duration::from_secs(3),
+            kill_reap: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Where a config field's effective value came from, as returned by `config_sources`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "source")]
+pub(crate) enum ConfigSource {
+    Default,
+    Env {
+        var: &'static str,
+    },
+    /// `settings.json`, changed through commands.
+    Settings,
+}
+
+/// Launcher settings, read once at startup from `TOSHIK_*` environment variables, plus the
+/// persisted [`Settings`] loaded during app setup.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LauncherConfig {
+    /// Minimum bun version required to spawn the backend (`TOSHIK_MIN_BUN_VERSION`).
+    pub min_bun_version: BunVersion,
+    /// bun executable, or a directory containing it, to use instead of the one on `PATH`
+    /// (`TOSHIK_BUN_PATH`).
+    pub bun_path: Option<PathBuf>,
+    /// How the backend is started (`TOSHIK_BUN_INVOCATION=run|x|direct`, default run).
+    pub bun_invocation: Invocation,
+    /// Find bun and start the backend through `$SHELL -lc` (`TOSHIK_LOGIN_SHELL=1`), so
+    /// PATH entries from rc files (nvm, asdf) apply to a GUI-launched app. bun found this
+    /// way is also used by `install_backend_deps` and `run_backend_task`. Unix only; on
+    /// Windows the backend is spawned directly.
+    pub use_login_shell: bool,
+    /// What is started instead of `packages/backend/src/index.ts`: a script for `run`, a
+    /// package for `x`, a prebuilt executable for `direct` (`TOSHIK_BACKEND_ENTRY`). The
+    /// workspace `.env` is still looked up from the resolved script, if there is one.
+    pub backend_entry: Option<PathBuf>,
+    /// Check a `direct` executable against the SHA-256 embedded at build time before
+    /// spawning it. On whenever a hash was embedded; `TOSHIK_SKIP_INTEGRITY_CHECK=1` turns
+    /// it off, in debug builds only.
+    pub verify_integrity: bool,
+    /// Start the backend with a cleared environment (`TOSHIK_ISOLATED_ENV=1`).
+    ///
+    /// Without this the child inherits everything the app was launched with, so tokens and
+    /// other developer-machine state leak into the backend. When set, only `env_allowlist`,
+    /// the variables passed to `start_backend` and the `--env-file` reach it.
+    pub isolated_env: bool,
+    /// Variable the `workers` argument of `start_backend` is passed in
+    /// (`TOSHIK_WORKERS_ENV`, default `BACKEND_WORKERS`).
+    pub workers_env: String,
+    /// Without a `workers` argument, use one worker per logical CPU
+    /// (`TOSHIK_AUTO_WORKERS=1`); otherwise the variable is left unset.
+    pub auto_workers: bool,
+    /// Also set `UV_THREADPOOL_SIZE` to the worker count (`TOSHIK_UV_THREADPOOL=1`).
+    pub uv_threadpool: bool,
+    /// Variables copied from the launcher's environment when `isolated_env` is set
+    /// (`TOSHIK_ENV_ALLOWLIST`, comma-separated; replaces the default list).
+    pub env_allowlist: Vec<String>,
+    /// Variables filled from the webview's Stronghold vault on every spawn, as variable
+    /// name -> store key (`TOSHIK_VAULT_SECRETS=GIGACHAT_API_KEY=apikey:gigachat,...`).
+    /// Their values are never logged or shown in environment snapshots.
+    pub vault_secrets: BTreeMap<String, String>,
+    /// Pipe the backend's stdout/stderr through reader threads that also emit
+    /// `backend://log-batch` events, instead of redirecting straight to the file
+    /// (`TOSHIK_STREAM_LOGS=1`).
+    pub stream_logs: bool,
+    /// Have the reader threads turn CRLF into LF and invalid UTF-8 into U+FFFD before
+    /// lines reach the file and events; `TOSHIK_RAW_OUTPUT=1` keeps the bytes as printed.
+    /// Output redirected straight to the file is never rewritten.
+    pub normalize_output: bool,
+    /// Longest backend log line kept, in bytes (`TOSHIK_MAX_LOG_LINE_BYTES`, default 1 MiB).
+    /// Anything beyond is dropped and replaced by a `…[truncated]` marker.
+    pub max_log_line: usize,
+    /// Ceiling on all log files in the log directory together, enforced on every start by
+    /// deleting the oldest rotated files (`TOSHIK_MAX_LOG_DIR_BYTES`, default 50 MiB).
+    pub max_log_dir: u64,
+    /// Recent backend lines kept in memory and attached to crash reports
+    /// (`TOSHIK_CRASH_LINES`, default 500; 0 keeps none). Only streamed output is seen.
+    pub crash_lines: usize,
+    /// Where backend output goes (`TOSHIK_LOG_SINK=file|syslog|both`, default file).
+    /// `syslog` and `both` forward each line to the system log from the reader threads, so
+    /// they imply `stream_logs`; without a system log (Windows) the file is used.
+    pub log_sink: LogSink,
+    /// Whether each `start_backend` appends to the backend log (default) or starts it empty,
+    /// after moving the previous session's to `<log>.prev` (`TOSHIK_LOG_MODE=truncate`).
+    pub log_mode: LogMode,
+    /// Timestamp and stream tag put before each backend line in the file, e.g.
+    /// `TOSHIK_LOG_PREFIX=1` for `{ts} [{stream}] ` or a template of its own. Off by default
+    /// so the file keeps the raw output; applied by the reader threads, so it implies
+    /// `stream_logs`.
+    pub log_prefix: Option<LinePrefix>,
+    /// Line written to the log at each launch so runs can be told apart in an appended log,
+    /// e.g. `TOSHIK_LOG_BANNER=1` for `===== session {run_id} started {ts} port={port} =====`
+    /// or a template of its own. Off by default.
+    pub log_banner: Option<SessionBanner>,
+    /// Discard the backend's stdout and keep only stderr in the log (`TOSHIK_QUIET=1`).
+    ///
+    /// Meant for deployments that care about disk usage: informational backend output is
+    /// lost, including `backend://log-batch` lines for stdout. Ignored with
+    /// `TOSHIK_READY_LINE`, which reads the ready line from stdout.
+    pub quiet: bool,
+    /// Format of the backend's own output (`TOSHIK_BACKEND_LOG_FORMAT=text|json`).
+    /// `json` is applied by the reader threads, so it implies `stream_logs`.
+    pub backend_log_format: LogFormat,
+    /// Leave starting the backend to the launcher once the webview reports it has mounted
+    /// (`frontend_ready`), so spawning bun doesn't compete with first paint
+    /// (`TOSHIK_START_ON_READY=1`).
+    pub start_on_frontend_ready: bool,
+    /// Spawn the backend during app setup so it is up before the webview asks for it
+    /// (`TOSHIK_AUTOSTART=1`). The frontend reads the port with `backend_port`.
+    pub autostart: bool,
+    /// Let the backend outlive the app (`TOSHIK_DETACHED=1`), e.g. for long-running tasks.
+    ///
+    /// This changes the lifecycle contract: the backend is spawned in its own session
+    /// (process group on Windows) and closing or relaunching the app leaves it running
+    /// instead of stopping it. `backend.pid` and `session.json` are kept, so the next
+    /// launch can reattach with `recover_previous_session`. `stop_backend` still stops it
+    /// while this app instance owns it. Output goes straight to the file, since reader
+    /// threads die with the app, so `stream_logs` and what implies it are ignored.
+    pub detached: bool,
+    /// Whether the backend script path is canonicalized or kept as found, which decides
+    /// where `.env` is looked up in symlinked workspaces
+    /// (`TOSHIK_SCRIPT_PATHS=canonical|logical`, default canonical).
+    pub script_path_mode: PathMode,
+    /// Let the OS assign the backend a free port instead of scanning 3001-3010
+    /// (`TOSHIK_EPHEMERAL_PORT=1`). The port then differs on every launch; subscribe to
+    /// `backend://port-changed` to follow it.
+    pub ephemeral_port: bool,
+    /// Scan for a free port in the background during setup, so the first start can skip
+    /// the scan (`TOSHIK_WARM_PORTS=1`). The port is checked again at spawn time and the
+    /// scan runs after all if it was taken meanwhile.
+    pub warm_ports: bool,
+    /// Loopback address for the backend: `::1` with `TOSHIK_IPV6=1` for IPv6-only or
+    /// IPv6-preferring systems, `127.0.0.1` otherwise. Passed to bun as `--host`.
+    pub host: IpAddr,
+    /// `TOSHIK_TRANSPORT=both` also has the backend listen on a Unix socket, for clients
+    /// that prefer it; TCP stays on since the launcher's own requests use it. Windows has
+    /// no Unix sockets here and keeps `tcp`.
+    pub transport: Transport,
+    /// With both transports, only count the backend as ready once TCP and the socket both
+    /// accept connections (`TOSHIK_READY_REQUIRE_ALL=1`); otherwise either one is enough.
+    pub ready_require_all: bool,
+    /// Wait for the backend to print `{"event":"ready","port":N}` on stdout and take the
+    /// port from it, correcting the stored one if they differ (`TOSHIK_READY_LINE=1`).
+    /// Without the line within a few seconds readiness falls back to probing the stored
+    /// port. Stdout is only read by the reader threads, so this implies `stream_logs`.
+    pub ready_line: bool,
+    /// Paths that must all answer 2xx, in order, once the backend accepts connections and
+    /// before it is declared ready, each polled for its own timeout
+    /// (`TOSHIK_READY_ENDPOINTS=/health:2000,/ready:10000`). The first that doesn't pass is
+    /// named in `backend://not-ready`.
+    pub ready_endpoints: Vec<ReadyEndpoint>,
+    /// Use exactly this port instead of scanning, failing if it is taken
+    /// (`TOSHIK_FORCE_PORT`). Intended for end-to-end tests that need a known port; only
+    /// honoured in debug builds.
+    pub force_port: Option<u16>,
+    /// Accept ports below 1024 for `TOSHIK_FORCE_PORT` and `attach_backend`
+    /// (`TOSHIK_ALLOW_PRIVILEGED_PORTS=1`).
+    pub allow_privileged_ports: bool,
+    /// Path fetched with a GET once the backend accepts connections, before it is declared
+    /// ready, so the first real request doesn't pay for lazy route compilation
+    /// (`TOSHIK_WARMUP_PATH`, e.g. `/`).
+    pub warmup_path: Option<String>,
+    /// Without a 2xx warmup response the backend is never declared ready
+    /// (`TOSHIK_WARMUP_REQUIRED=1`). Otherwise a failed warmup is only reported.
+    pub warmup_required: bool,
+    /// Shell command run once the backend accepts connections, e.g. a migration
+    /// (`TOSHIK_POST_START_HOOK`). Output goes to `hook.log` next to the backend log.
+    pub post_start_hook: Option<String>,
+    /// Stop the backend when the hook fails (default). `TOSHIK_POST_START_HOOK_NONFATAL=1`
+    /// only reports the failure.
+    pub post_start_hook_fatal: bool,
+    /// Shell command `upgrade_backend` runs between stopping the old backend and starting
+    /// the new one, e.g. `bun run migrate` (`TOSHIK_MIGRATION_COMMAND`). Output goes to
+    /// `migration.log` next to the backend log.
+    pub migration_command: Option<String>,
+    /// Time a `run_backend_task` script may run before it is killed
+    /// (`TOSHIK_TASK_TIMEOUT_MS`, default 5 minutes).
+    #[serde(rename = "taskTimeoutMs", serialize_with = "http::millis")]
+    pub task_timeout: Duration,
+    /// Bytes of a task's stdout and of its stderr returned by `run_backend_task`
+    /// (`TOSHIK_MAX_TASK_OUTPUT_BYTES`, default 1 MiB each).
+    pub max_task_output: usize,
+    /// Start the backend with `docker run` or `podman run` instead of bun when an image is
+    /// set and the runtime is installed; bun is used otherwise.
+    pub container: ContainerConfig,
+    /// Timeouts and retries for requests to the backend.
+    pub http: HttpConfig,
+    /// Grace periods used by `stop_backend`, restarts and cleanup on exit.
+    pub shutdown: ShutdownTimeouts,
+    /// Header the per-launch shutdown token is sent in when the launcher asks the backend
+    /// to exit with `POST /shutdown` (`TOSHIK_SHUTDOWN_TOKEN_HEADER`, default
+    /// `X-Shutdown-Token`).
+    pub shutdown_token_header: String,
+    /// What `schedule_restart` does while a restart is already pending
+    /// (`TOSHIK_RESTART_SCHEDULE_POLICY=replace|reject`, default replace).
+    pub restart_schedule_policy: SchedulePolicy,
+    /// Before `restart_backend` stops a backend that fails its health check, write what
+    /// can be seen of the hung process to `hang-<timestamp>.txt` next to the backend log
+    /// (`TOSHIK_HANG_DIAGNOSTICS=1`).
+    pub hang_diagnostics: bool,
+    /// Signal sent after that snapshot so the runtime can dump its heap or stacks
+    /// (`TOSHIK_HANG_SIGNAL=usr1|usr2|quit`); none by default.
+    pub hang_signal: Option<DumpSignal>,
+    /// Probe a restarted or drained-in backend must pass before it counts as up.
+    pub health_check: HealthCheck,
+    /// Loaded from `settings.json` in setup; changed through commands.
+    pub settings: Settings,
+    /// Enable bun's inspector; never set from the environment, only for the launch made by
+    /// `start_backend_verbose`.
+    #[serde(skip)]
+    pub inspector: Option<Inspector>,
+}
+
+impl Default for LauncherConfig {
+    /// The configuration with no `TOSHIK_*` variable set.
+    fn default() -> Self {
+        Self {
+            min_bun_version: DEFAULT_MIN_BUN_VERSION,
+            bun_path: None,
+            bun_invocation: Invocation::Run,
+            use_login_shell: false,
+            backend_entry: None,
+            verify_integrity: integrity::EXPECTED_SHA256.is_some(),
+            isolated_env: false,
+            workers_env: DEFAULT_WORKERS_ENV.into(),
+            auto_workers: false,
+            uv_threadpool: false,
+            env_allowlist: DEFAULT_ENV_ALLOWLIST
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+            vault_secrets: BTreeMap::new(),
+            stream_logs: false,
+            normalize_output: true,
+            max_log_line: logs::DEFAULT_MAX_LINE,
+            max_log_dir: logs::DEFAULT_MAX_LOG_DIR,
+            crash_lines: logs::DEFAULT_CRASH_LINES,
+            log_sink: LogSink::File,
+            log_mode: LogMode::Append,
+            log_prefix: None,
+            log_banner: None,
+            quiet: false,
+            backend_log_format: LogFormat::Text,
+            start_on_frontend_ready: false,
+            autostart: false,
+            detached: false,
+            script_path_mode: PathMode::Canonical,
+            ephemeral_port: false,
+            warm_ports: false,
+            host: net::loopback(false),
+            transport: Transport::Tcp,
+            ready_require_all: false,
+            ready_line: false,
+            ready_endpoints: Vec::new(),
+            force_port: None,
+            allow_privileged_ports: false,
+            warmup_path: None,
+            warmup_required: false,
+            post_start_hook: None,
+            post_start_hook_fatal: true,
+            migration_command: None,
+            task_timeout: backend_task::DEFAULT_TIMEOUT,
+            max_task_output: backend_task::DEFAULT_MAX_OUTPUT,
+            container: ContainerConfig {
+                image: None,
+                runtime: container::DEFAULT_RUNTIME.into(),
+                args: Vec::new(),
+            },
+            http: HttpConfig::default(),
+            shutdown: ShutdownTimeouts::default(),
+            shutdown_token_header: http::DEFAULT_SHUTDOWN_HEADER.into(),
+            restart_schedule_policy: SchedulePolicy::Replace,
+            hang_diagnostics: false,
+            hang_signal: None,
+            health_check: HealthCheck::default(),
+            settings: Settings::default(),
+            inspector: None,
+        }
+    }
+}
+
+impl LauncherConfig {
+    pub(crate) fn from_env() -> Self {
+        Self::from_vars(&Vars(&|name| env::var_os(name)))
+    }
+
+    /// [`Self::from_env`] with the variables looked up in `vars`.
+    fn from_vars(vars: &Vars) -> Self {
+        let min_bun_version = match vars.var("TOSHIK_MIN_BUN_VERSION") {
+            Ok(raw) => BunVersion::parse(&raw).unwrap_or_else(|| {
+                log::warn!(
+                    "Ignoring invalid TOSHIK_MIN_BUN_VERSION={raw:?}, using {DEFAULT_MIN_BUN_VERSION}"
+                );
+                DEFAULT_MIN_BUN_VERSION
+            }),
+            Err(_) => DEFAULT_MIN_BUN_VERSION,
+        };
+
+        let env_allowlist = match vars.var("TOSHIK_ENV_ALLOWLIST") {
+            Ok(raw) => raw
+                .split(',')
+                .map(str::trim)
+                .filter(|key| !key.is_empty())
+                .map(String::from)
+                .collect(),
+            Err(_) => DEFAULT_ENV_ALLOWLIST
+                .iter()
+                .map(|key| key.to_string())
+                .collect(),
+        };
+
+        let backend_log_format = match vars.var("TOSHIK_BACKEND_LOG_FORMAT") {
+            Ok(raw) => LogFormat::parse(&raw).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid TOSHIK_BACKEND_LOG_FORMAT={raw:?}, using text");
+                LogFormat::Text
+            }),
+            Err(_) => LogFormat::Text,
+        };
+
+        let script_path_mode = match vars.var("TOSHIK_SCRIPT_PATHS") {
+            Ok(raw) => PathMode::parse(&raw).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid TOSHIK_SCRIPT_PATHS={raw:?}, using canonical");
+                PathMode::Canonical
+            }),
+            Err(_) => PathMode::Canonical,
+        };
+
+        let bun_invocation = match vars.var("TOSHIK_BUN_INVOCATION") {
+            Ok(raw) => Invocation::parse(&raw).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid TOSHIK_BUN_INVOCATION={raw:?}, using run");
+                Invocation::Run
+            }),
+            Err(_) => Invocation::Run,
+        };
+
+        let log_sink = match vars.var("TOSHIK_LOG_SINK") {
+            Ok(raw) => LogSink::parse(&raw).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid TOSHIK_LOG_SINK={raw:?}, using file");
+                LogSink::File
+            }),
+            Err(_) => LogSink::File,
+        };
+        let log_sink = if log_sink.syslog() && !syslog::SUPPORTED {
+            log::info!("No system log on this platform, writing backend output to the file only");
+            LogSink::File
+        } else {
+            log_sink
+        };
+
+        let log_mode = match vars.var("TOSHIK_LOG_MODE") {
+            Ok(raw) => LogMode::parse(&raw).unwrap_or_else(|| {
+                log::warn!("Ignoring invalid TOSHIK_LOG_MODE={raw:?}, using append");
+                LogMode::Append
+            }),
+            Err(_) => LogMode::Append,
+        };
+
+        let ready_line = vars.flag("TOSHIK_READY_LINE");
+        // The ready line is read from stdout, which quiet mode would discard.
+        let quiet = vars.flag("TOSHIK_QUIET");
+        if quiet && ready_line {
+            log::warn!("Ignoring TOSHIK_QUIET: TOSHIK_READY_LINE needs the backend's stdout");
+        }
+        let quiet = quiet && !ready_line;
+        let log_prefix = vars
+            .var("TOSHIK_LOG_PREFIX")
+            .ok()
+            .and_then(|raw| LinePrefix::parse(&raw));
+        let log_banner = vars
+            .var("TOSHIK_LOG_BANNER")
+            .ok()
+            .and_then(|raw| SessionBanner::parse(&raw));
+
+        Self {
+            min_bun_version,
+            bun_path: vars
+                .var_os("TOSHIK_BUN_PATH")
+                .filter(|path| !path.is_empty())
+                .map(PathBuf::from),
+            bun_invocation,
+            use_login_shell: vars.flag("TOSHIK_LOGIN_SHELL"),
+            verify_integrity: integrity::EXPECTED_SHA256.is_some() && !skip_integrity_check(vars),
+            backend_entry: vars
+                .var_os("TOSHIK_BACKEND_ENTRY")
+                .filter(|entry| !entry.is_empty())
+                .map(PathBuf::from),
+            isolated_env: vars.flag("TOSHIK_ISOLATED_ENV"),
+            vault_secrets: vault_secrets(vars),
+            workers_env: vars
+                .var("TOSHIK_WORKERS_ENV")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| DEFAULT_WORKERS_ENV.into()),
+            auto_workers: vars.flag("TOSHIK_AUTO_WORKERS"),
+            uv_threadpool: vars.flag("TOSHIK_UV_THREADPOOL"),
+            env_allowlist,
+            stream_logs: vars.flag("TOSHIK_STREAM_LOGS")
+                || backend_log_format == LogFormat::Json
+                || log_sink.syslog()
+                || log_prefix.is_some()
+                || ready_line,
+            normalize_output: !vars.flag("TOSHIK_RAW_OUTPUT"),
+            max_log_line: vars.number("TOSHIK_MAX_LOG_LINE_BYTES", logs::DEFAULT_MAX_LINE),
+            max_log_dir: vars.number("TOSHIK_MAX_LOG_DIR_BYTES", logs::DEFAULT_MAX_LOG_DIR),
+            crash_lines: vars.number("TOSHIK_CRASH_LINES", logs::DEFAULT_CRASH_LINES),
+            log_sink,
+            log_mode,
+            log_prefix,
+            log_banner,
+            quiet,
+            backend_log_format,
+            start_on_frontend_ready: vars.flag("TOSHIK_START_ON_READY"),
+            autostart: vars.flag("TOSHIK_AUTOSTART"),
+            detached: vars.flag("TOSHIK_DETACHED"),
+            script_path_mode,
+            ephemeral_port: vars.flag("TOSHIK_EPHEMERAL_PORT"),
+            warm_ports: vars.flag("TOSHIK_WARM_PORTS"),
+            host: net::loopback(vars.flag("TOSHIK_IPV6")),
+            transport: transport(vars),
+            ready_require_all: vars.flag("TOSHIK_READY_REQUIRE_ALL"),
+            ready_line,
+            ready_endpoints: vars
+                .var("TOSHIK_READY_ENDPOINTS")
+                .map(|raw| ReadyEndpoint::parse_list(&raw))
+                .unwrap_or_default(),
+            force_port: force_port(vars),
+            allow_privileged_ports: vars.flag("TOSHIK_ALLOW_PRIVILEGED_PORTS"),
+            warmup_path: vars
+                .var("TOSHIK_WARMUP_PATH")
+                .ok()
+                .map(|path| path.trim().trim_start_matches('/').to_string())
+                .map(|path| format!("/{path}")),
+            warmup_required: vars.flag("TOSHIK_WARMUP_REQUIRED"),
+            post_start_hook: vars
+                .var("TOSHIK_POST_START_HOOK")
+                .ok()
+                .filter(|hook| !hook.trim().is_empty()),
+            post_start_hook_fatal: !vars.flag("TOSHIK_POST_START_HOOK_NONFATAL"),
+            migration_command: vars
+                .var("TOSHIK_MIGRATION_COMMAND")
+                .ok()
+                .filter(|command| !command.trim().is_empty()),
+            task_timeout: vars.millis("TOSHIK_TASK_TIMEOUT_MS", backend_task::DEFAULT_TIMEOUT),
+            max_task_output: vars.number(
+                "TOSHIK_MAX_TASK_OUTPUT_BYTES",
+                backend_task::DEFAULT_MAX_OUTPUT,
+            ),
+            container: ContainerConfig {
+                image: vars
+                    .var("TOSHIK_CONTAINER_IMAGE")
+                    .ok()
+                    .map(|image| image.trim().to_string())
+                    .filter(|image| !image.is_empty()),
+                runtime: vars
+                    .var("TOSHIK_CONTAINER_RUNTIME")
+                    .ok()
+                    .map(|runtime| runtime.trim().to_string())
+                    .filter(|runtime| !runtime.is_empty())
+                    .unwrap_or_else(|| container::DEFAULT_RUNTIME.into()),
+                args: vars
+                    .var("TOSHIK_CONTAINER_ARGS")
+                    .map(|args| args.split_whitespace().map(String::from).collect())
+                    .unwrap_or_default(),
+            },
+            http: HttpConfig {
+                connect_timeout: vars.millis(
+                    "TOSHIK_HTTP_CONNECT_TIMEOUT_MS",
+                    HttpConfig::default().connect_timeout,
+                ),
+                read_timeout: vars.millis(
+                    "TOSHIK_HTTP_READ_TIMEOUT_MS",
+                    HttpConfig::default().read_timeout,
+                ),
+                get_retries: vars.number("TOSHIK_HTTP_RETRIES", HttpConfig::default().get_retries),
+                proxy_concurrency: vars.number(
+                    "TOSHIK_PROXY_CONCURRENCY",
+                    HttpConfig::default().proxy_concurrency,
+                ),
+                proxy_queue_timeout: vars.millis(
+                    "TOSHIK_PROXY_QUEUE_TIMEOUT_MS",
+                    HttpConfig::default().proxy_queue_timeout,
+                ),
+                proxy_max_response: vars.number(
+                    "TOSHIK_PROXY_MAX_RESPONSE_BYTES",
+                    HttpConfig::default().proxy_max_response,
+                ),
+                proxy_read_timeout: vars.millis(
+                    "TOSHIK_PROXY_READ_TIMEOUT_MS",
+                    HttpConfig::default().proxy_read_timeout,
+                ),
+            },
+            shutdown: ShutdownTimeouts {
+                term_grace: vars.millis(
+                    "TOSHIK_TERM_GRACE_MS",
+                    ShutdownTimeouts::default().term_grace,
+                ),
+                kill_reap: vars
+                    .millis("TOSHIK_KILL_REAP_MS", ShutdownTimeouts::default().kill_reap),
+            },
+            restart_schedule_policy: restart_schedule_policy(vars),
+            hang_diagnostics: vars.flag("TOSHIK_HANG_DIAGNOSTICS"),
+            hang_signal: hang_signal(vars),
+            shutdown_token_header: vars
+                .var("TOSHIK_SHUTDOWN_TOKEN_HEADER")
+                .ok()
+                .map(|header| header.trim().to_string())
+                .filter(|header| !header.is_empty())
+                .unwrap_or_else(|| http::DEFAULT_SHUTDOWN_HEADER.into()),
+            health_check: health_check(vars),
+            settings: Settings::default(),
+            inspector: None,
+        }
+    }
+
+    /// A copy safe to show: values of secret-looking backend variables are masked, and so
+    /// are credentials passed on the hook, migration and container command lines.
+    pub(crate) fn redacted(&self) -> Self {
+        let mut config = self.clone();
+        for (key, value) in config.settings.backend_env.iter_mut() {
+            *value = backend_env::display_value(key, value).to_string();
+        }
+        for command in [&mut config.post_start_hook, &mut config.migration_command]
+            .into_iter()
+            .flatten()
+        {
+            *command = backend_env::redact_command(command);
+        }
+        config.container.args =
+            backend_env::redact_words(config.container.args.iter().map(String::as_str));
+        config
+    }
+
+    /// Origin of every field, keyed like the serialized config (nested fields as
+    /// `http.getRetries`, settings as `settings.logPath`). An environment variable counts
+    /// once it is set, even if its value was invalid and the default was kept.
+    pub(crate) fn sources(&self) -> BTreeMap<&'static str, ConfigSource> {
+        let mut sources: BTreeMap<_, _> = ENV_VARS
+            .iter()
+            .map(|&(field, var)| {
+                let source = if env::var_os(var).is_some() {
+                    ConfigSource::Env { var }
+                } else {
+                    ConfigSource::Default
+                };
+                (field, source)
+            })
+            .collect();
+        // JSON backend logs, syslog forwarding, prefixes and the ready line are only handled
+        // by the reader threads.
+        if env::var_os("TOSHIK_STREAM_LOGS").is_none() {
+            let implied_by = if self.backend_log_format == LogFormat::Json {
+                Some("TOSHIK_BACKEND_LOG_FORMAT")
+            } else if self.log_sink.syslog() {
+                Some("TOSHIK_LOG_SINK")
+            } else if self.log_prefix.is_some() {
+                Some("TOSHIK_LOG_PREFIX")
+            } else if self.ready_line {
+                Some("TOSHIK_READY_LINE")
+            } else {
+                None
+            };
+            if let Some(var) = implied_by {
+                sources.insert("streamLogs", ConfigSource::Env { var });
+            }
+        }
+        let from_settings = |set: bool| {
+            if set {
+                ConfigSource::Settings
+            } else {
+                ConfigSource::Default
+            }
+        };
+        sources.insert(
+            "settings.logPath",
+            from_settings(self.settings.log_path.is_some()),
+        );
+        sources.insert(
+            "settings.backendEnv",
+            from_settings(!self.settings.backend_env.is_empty()),
+        );
+        sources.insert(
+            "settings.launcherLogLevel",
+            from_settings(self.settings.launcher_log_level.is_some()),
+        );
+        sources
+    }
+
+    /// How the reader threads write backend lines to the file.
+    pub(crate) fn line_options(&self) -> LineOptions {
+        LineOptions {
+            format: self.backend_log_format,
+            max_line: self.max_log_line,
+            prefix: self.log_prefix.clone(),
+            normalize: self.normalize_output,
+        }
+    }
+
+    pub(crate) fn stream_taps(&self) -> StreamTaps {
+        StreamTaps {
+            log_sink: self.log_sink,
+            ready_line: self.ready_line,
+            recent_lines: self.crash_lines,
+        }
+    }
+
+    /// Where backend output goes: the `set_log_path` override, else the default log file.
+    pub(crate) fn log_path(&self, paths: &AppPaths) -> PathBuf {
+        self.settings
+            .log_path
+            .clone()
+            .unwrap_or_else(|| paths.log_file.clone())
+    }
+}
+
+/// Where [`LauncherConfig::from_env`] reads its variables: the process environment, or
+/// a fixed lookup in tests.
+struct Vars<'a>(&'a dyn Fn(&str) -> Option<OsString>);
+
+impl Vars<'_> {
+    /// Like `env::var`: unset and non-UTF-8 values are both errors.
+    fn var(&self, name: &str) -> Result<String, env::VarError> {
+        match (self.0)(name) {
+            Some(value) => value.into_string().map_err(env::VarError::NotUnicode),
+            None => Err(env::VarError::NotPresent),
+        }
+    }
+
+    fn var_os(&self, name: &str) -> Option<OsString> {
+        (self.0)(name)
+    }
+
+    /// `1`/`true`/`yes` (any case) count as set; anything else, or unset, as off.
+    fn flag(&self, name: &str) -> bool {
+        self.var(name)
+            .map(|v| matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes"))
+            .unwrap_or(false)
+    }
+
+    /// A non-negative integer variable, or `default` when unset or invalid.
+    fn number<T: std::str::FromStr + std::fmt::Display>(&self, name: &str, default: T) -> T {
+        match self.var(name) {
+            Ok(raw) => raw.trim().parse().unwrap_or_else(|_| {
+                log::warn!("Ignoring invalid {name}={raw:?}, using {default}");
+                default
+            }),
+            Err(_) => default,
+        }
+    }
+
+    /// A duration given in milliseconds, or `default` when unset or invalid.
+    fn millis(&self, name: &str, default: Duration) -> Duration {
+        Duration::from_millis(self.number(name, default.as_millis() as u64))
+    }
+}
+
+/// `TOSHIK_FORCE_PORT`, ignored (with a warning) outside debug builds.
+fn force_port(vars: &Vars) -> Option<u16> {
+    let raw = vars.var("TOSHIK_FORCE_PORT").ok()?;
+    if !cfg!(debug_assertions) {
+        log::warn!("Ignoring TOSHIK_FORCE_PORT in a release build");
+        return None;
+    }
+    let Ok(port) = raw.trim().parse() else {
+        log::warn!("Ignoring invalid TOSHIK_FORCE_PORT={raw:?}");
+        return None;
+    };
+    net::validate_port(port, vars.flag("TOSHIK_ALLOW_PRIVILEGED_PORTS"))
+        .inspect_err(|e| log::warn!("Ignoring TOSHIK_FORCE_PORT: {e}"))
+        .ok()
+}
+
+/// `TOSHIK_TRANSPORT`, falling back to TCP where Unix sockets aren't supported.
+fn transport(vars: &Vars) -> Transport {
+    let transport = match vars.var("TOSHIK_TRANSPORT") {
+        Ok(raw) => Transport::parse(&raw).unwrap_or_else(|| {
+            log::warn!("Ignoring invalid TOSHIK_TRANSPORT={raw:?}, using tcp");
+            Transport::Tcp
+        }),
+        Err(_) => Transport::Tcp,
+    };
+    if transport == Transport::Both && !cfg!(unix) {
+        log::info!("Unix sockets are not supported on this platform, using TCP only");
+        return Transport::Tcp;
+    }
+    transport
+}
+
+/// `TOSHIK_VAULT_SECRETS`, skipping (with a warning) entries that aren't `NAME=key` with a
+/// valid variable name.
+fn vault_secrets(vars: &Vars) -> BTreeMap<String, String> {
+    let Ok(raw) = vars.var("TOSHIK_VAULT_SECRETS") else {
+        return BTreeMap::new();
+    };
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = entry
+                .split_once('=')
+                .map(|(name, key)| (name.trim(), key.trim()))
+                .filter(|(_, key)| !key.is_empty())
+                .ok_or_else(|| "expected NAME=store-key".to_string())
+                .and_then(|(name, key)| {
+                    backend_env::validate_key(name)?;
+                    Ok((name.to_string(), key.to_string()))
+                });
+            parsed
+                .inspect_err(|e| log::warn!("Ignoring TOSHIK_VAULT_SECRETS entry {entry:?}: {e}"))
+                .ok()
+        })
+        .collect()
+}
+
+/// `TOSHIK_RESTART_SCHEDULE_POLICY`, replacing a pending restart unless told otherwise.
+fn restart_schedule_policy(vars: &Vars) -> SchedulePolicy {
+    match vars.var("TOSHIK_RESTART_SCHEDULE_POLICY") {
+        Ok(raw) => SchedulePolicy::parse(&raw).unwrap_or_else(|| {
+            log::warn!("Ignoring invalid TOSHIK_RESTART_SCHEDULE_POLICY={raw:?}, using replace");
+            SchedulePolicy::Replace
+        }),
+        Err(_) => SchedulePolicy::Replace,
+    }
+}
+
+/// `TOSHIK_HANG_SIGNAL`, if set to a signal a hang capture may send.
+fn hang_signal(vars: &Vars) -> Option<DumpSignal> {
+    let raw = vars.var("TOSHIK_HANG_SIGNAL").ok()?;
+    let signal = DumpSignal::parse(&raw);
+    if signal.is_none() {
+        log::warn!("Ignoring invalid TOSHIK_HANG_SIGNAL={raw:?}");
+    }
+    signal
+}
+
+/// `TOSHIK_SKIP_INTEGRITY_CHECK`, ignored (with a warning) outside debug builds.
+fn skip_integrity_check(vars: &Vars) -> bool {
+    if !vars.flag("TOSHIK_SKIP_INTEGRITY_CHECK") {
+        return false;
+    }
+    if !cfg!(debug_assertions) {
+        log::warn!("Ignoring TOSHIK_SKIP_INTEGRITY_CHECK in a release build");
+        return false;
+    }
+    true
+}
+
+/// `TOSHIK_READINESS_PROBE`, `TOSHIK_HEALTH_PATH` and `TOSHIK_HEALTH_MATCH` over the
+/// defaults.
+fn health_check(vars: &Vars) -> HealthCheck {
+    let mut check = HealthCheck::default();
+    if let Ok(raw) = vars.var("TOSHIK_READINESS_PROBE") {
+        match ProbeKind::parse(&raw) {
+            Some(probe) => check.probe = probe,
+            None => log::warn!("Ignoring invalid TOSHIK_READINESS_PROBE={raw:?}, using http"),
+        }
+    }
+    if let Ok(path) = vars.var("TOSHIK_HEALTH_PATH") {
+        check.path = format!("/{}", path.trim().trim_start_matches('/'));
+    }
+    if let Ok(raw) = vars.var("TOSHIK_HEALTH_MATCH") {
+        match BodyMatcher::parse(&raw) {
+            Ok(matcher) => check.matcher = matcher,
+            Err(e) => log::warn!("Ignoring TOSHIK_HEALTH_MATCH={raw:?}: {e}"),
+        }
+    }
+    check
+}
+
+/// Variable the backend reads its worker count from unless `TOSHIK_WORKERS_ENV` names another.
+const DEFAULT_WORKERS_ENV: &str = "BACKEND_WORKERS";
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    /// Dotted paths of the leaves of `value`, stopping at maps that are data, not structure.
+    fn fields(prefix: &str, value: &Value, out: &mut Vec<String>) {
+        match value {
+            Value::Object(map) if prefix.is_empty() || SECTIONS.contains(&prefix) => {
+                for (key, value) in map {
+                    let path = if prefix.is_empty() {
+                        key.clone()
+                    } else {
+                        format!("{prefix}.{key}")
+                    };
+                    fields(&path, value, out);
+                }
+            }
+            _ => out.push(prefix.to_string()),
+        }
+    }
+
+    #[test]
+    fn default_is_what_an_unset_environment_gives() {
+        assert_eq!(
+            serde_json::to_value(LauncherConfig::default()).unwrap(),
+            serde_json::to_value(LauncherConfig::from_vars(&Vars(&|_| None))).unwrap()
+        );
+    }
+
+    #[test]
+    fn quiet_mode_keeps_stdout_for_the_ready_line() {
+        let config = |vars: &[&str]| {
+            LauncherConfig::from_vars(&Vars(&|name| {
+                vars.contains(&name).then(|| OsString::from("1"))
+            }))
+        };
+        assert!(config(&["TOSHIK_QUIET"]).quiet);
+        let both = config(&["TOSHIK_QUIET", "TOSHIK_READY_LINE"]);
+        assert!(both.ready_line && !both.quiet);
+    }
+
+    #[test]
+    fn every_serialized_field_has_a_source() {
+        let mut config = LauncherConfig::default();
+        config
+            .settings
+            .backend_env
+            .insert("API_TOKEN".into(), "hunter2".into());
+
+        let json = serde_json::to_value(config.redacted()).unwrap();
+        assert_eq!(
+            json["settings"]["backendEnv"]["API_TOKEN"],
+            backend_env::REDACTED
+        );
+
+        let mut serialized = Vec::new();
+        fields("", &json, &mut serialized);
+        let mut sourced: Vec<String> = config.sources().keys().map(|k| k.to_string()).collect();
+        serialized.sort();
+        sourced.sort();
+        assert_eq!(serialized, sourced);
+    }
+}
@@ -0,0 +1,175 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Runtime used when `TOSHIK_CONTAINER_RUNTIME` is unset.
+pub(crate) const DEFAULT_RUNTIME: &str = "docker";
+
+/// Running the backend from a container image instead of bare bun.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ContainerConfig {
+    /// Image whose entrypoint is the backend (`TOSHIK_CONTAINER_IMAGE`); unset runs bun.
+    pub image: Option<String>,
+    /// `docker`, `podman` or a path to either (`TOSHIK_CONTAINER_RUNTIME`, default docker).
+    pub runtime: String,
+    /// Extra `run` arguments put before the image, e.g. `--env-file .env -v data:/data`
+    /// (`TOSHIK_CONTAINER_ARGS`, split on whitespace).
+    pub args: Vec<String>,
+}
+
+/// A backend running in a container, stopped through the runtime rather than by signalling
+/// its client process.
+#[derive(Debug)]
+pub(crate) struct Container {
+    runtime: PathBuf,
+    /// Given with `--name`; the runtime accepts it wherever it takes a container ID.
+    pub name: String,
+}
+
+/// `runtime` on `PATH`, or as given if it is a path to an existing file. `None` when the
+/// runtime isn't installed.
+pub(crate) fn find_runtime(runtime: &str) -> Option<PathBuf> {
+    let given = Path::new(runtime);
+    if given.components().count() > 1 {
+        return given.is_file().then(|| given.to_path_buf());
+    }
+    let file_name = if cfg!(windows) {
+        format!("{runtime}.exe")
+    } else {
+        runtime.to_string()
+    };
+    env::split_paths(&env::var_os("PATH")?)
+        .map(|dir| dir.join(&file_name))
+        .find(|path| path.is_file())
+}
+
+impl Container {
+    pub(crate) fn new(runtime: PathBuf, run_id: &str) -> Self {
+        Self {
+            runtime,
+            name: format!("toshik-backend-{run_id}"),
+        }
+    }
+
+    /// `<runtime> run` for `image`, publishing `port` on `host` only and passing the
+    /// launcher's arguments to the entrypoint. Variables are forwarded by name with `-e`,
+    /// their values taken from the client's environment, so secrets stay off the command
+    /// line. Inside the container the backend listens on all interfaces, which the
+    /// published port alone exposes.
+    pub(crate) fn run_command(
+        &self,
+        config: &ContainerConfig,
+        image: &str,
+        host: IpAddr,
+        port: u16,
+        run_id: &str,
+        env: &BTreeMap<OsString, OsString>,
+    ) -> Command {
+        let mut cmd = Command::new(&self.runtime);
+        let publish = match host {
+            IpAddr::V4(host) => format!("{host}:{port}:{port}"),
+            IpAddr::V6(host) => format!("[{host}]:{port}:{port}"),
+        };
+        cmd.args(["run", "--rm", "--name", &self.name, "-p", &publish]);
+        for key in env.keys() {
+            cmd.arg("-e").arg(key);
+        }
+        cmd.args(&config.args).arg(image);
+        cmd.args(["--port", &port.to_string(), "--host", "0.0.0.0"]);
+        cmd.args(["--run-id", run_id]);
+        cmd.envs(env);
+        cmd
+    }
+
+    /// `<runtime> stop`, which gives the backend `grace` after SIGTERM before killing it.
+    /// Returns whether the runtime reported success.
+    pub(crate) fn stop(&self, grace: Duration) -> bool {
+        log::info!("Stopping backend container {}", self.name);
+        let status = Command::new(&self.runtime)
+            .args([
+                "stop",
+                "-t",
+                &grace.as_secs().max(1).to_string(),
+                &self.name,
+            ])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+        match status {
+            Ok(status) if status.success() => true,
+            Ok(status) => {
+                log::warn!(
+                    "{} stop {} exited with {status}",
+                    self.runtime.display(),
+                    self.name
+                );
+                false
+            }
+            Err(e) => {
+                log::warn!("Failed to run {} stop: {e}", self.runtime.display());
+                false
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn run_command_publishes_on_loopback_and_keeps_secrets_off_the_command_line() {
+        let config = ContainerConfig {
+            image: Some("ghcr.io/toshik/backend:1".into()),
+            runtime: "podman".into(),
+            args: vec!["--env-file".into(), ".env".into()],
+        };
+        let env = BTreeMap::from([("SHUTDOWN_TOKEN".into(), "s3cret".into())]);
+        let container = Container::new(PathBuf::from("/usr/bin/podman"), "run-1");
+        let cmd = container.run_command(
+            &config,
+            "ghcr.io/toshik/backend:1",
+            "::1".parse().unwrap(),
+            3004,
+            "run-1",
+            &env,
+        );
+
+        let args: Vec<_> = cmd.get_args().map(|arg| arg.to_str().unwrap()).collect();
+        assert_eq!(
+            args,
+            [
+                "run",
+                "--rm",
+                "--name",
+                "toshik-backend-run-1",
+                "-p",
+                "[::1]:3004:3004",
+                "-e",
+                "SHUTDOWN_TOKEN",
+                "--env-file",
+                ".env",
+                "ghcr.io/toshik/backend:1",
+                "--port",
+                "3004",
+                "--host",
+                "0.0.0.0",
+                "--run-id",
+                "run-1",
+            ]
+        );
+        assert!(cmd
+            .get_envs()
+            .any(|(key, value)| key == "SHUTDOWN_TOKEN" && value == Some("s3cret".as_ref())));
+        assert_eq!(find_runtime("toshik-no-such-runtime"), None);
+        assert_eq!(find_runtime("/nonexistent/docker"), None);
+    }
+}
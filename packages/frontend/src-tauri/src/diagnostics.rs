@@ -0,0 +1,170 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Serialize;
+
+/// Time a backend gets to write its dump after [`DumpSignal`] before it is stopped.
+const DUMP_GRACE: Duration = Duration::from_secs(2);
+
+/// Most of `/proc/<pid>/maps` copied into a hang report.
+const MAPS_LIMIT: usize = 256 * 1024;
+
+/// Signal sent to a hung backend so its runtime writes a heap or stack dump, e.g. Node's
+/// `--heapsnapshot-signal=SIGUSR2` (`TOSHIK_HANG_SIGNAL=usr1|usr2|quit`). A runtime without
+/// a handler for it just exits, which is fine since it is about to be stopped anyway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub(crate) enum DumpSignal {
+    #[serde(rename = "SIGUSR1")]
+    Usr1,
+    #[serde(rename = "SIGUSR2")]
+    Usr2,
+    #[serde(rename = "SIGQUIT")]
+    Quit,
+}
+
+impl DumpSignal {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim().to_ascii_uppercase();
+        match raw.strip_prefix("SIG").unwrap_or(&raw) {
+            "USR1" => Some(Self::Usr1),
+            "USR2" => Some(Self::Usr2),
+            "QUIT" => Some(Self::Quit),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Self::Usr1 => "SIGUSR1",
+            Self::Usr2 => "SIGUSR2",
+            Self::Quit => "SIGQUIT",
+        }
+    }
+
+    #[cfg(unix)]
+    fn number(self) -> libc::c_int {
+        match self {
+            Self::Usr1 => libc::SIGUSR1,
+            Self::Usr2 => libc::SIGUSR2,
+            Self::Quit => libc::SIGQUIT,
+        }
+    }
+}
+
+/// Write what can be learned about hung process `pid` without its cooperation to
+/// `hang-<timestamp>.txt` in `dir`: its status, open file descriptors and memory map
+/// (Linux only), then send it `signal`, if any, and give it [`DUMP_GRACE`] to act on it.
+/// Returns the report's path.
+pub(crate) fn capture_hang(
+    pid: u32,
+    run_id: &str,
+    dir: &Path,
+    signal: Option<DumpSignal>,
+) -> io::Result<PathBuf> {
+    let now = chrono::Local::now();
+    let mut report = format!(
+        "Backend hang report\nrun_id: {run_id}\npid: {pid}\ncaptured: {}\n",
+        now.to_rfc3339()
+    );
+    snapshot(pid, &mut report);
+    if let Some(signal) = signal {
+        let _ = writeln!(report, "\n{} sent after this snapshot", signal.name());
+    }
+    fs::create_dir_all(dir)?;
+    let path = dir.join(format!("hang-{}.txt", now.format("%Y%m%d-%H%M%S%.3f")));
+    fs::write(&path, report)?;
+
+    #[cfg(unix)]
+    if let Some(signal) = signal {
+        // SAFETY: kill(2) on the pid of a child we spawned and have not reaped yet.
+        unsafe { libc::kill(pid as libc::pid_t, signal.number()) };
+        std::thread::sleep(DUMP_GRACE);
+    }
+    Ok(path)
+}
+
+#[cfg(target_os = "linux")]
+fn snapshot(pid: u32, report: &mut String) {
+    let proc_dir = PathBuf::from(format!("/proc/{pid}"));
+    let section = |report: &mut String, title: &str, body: io::Result<String>| {
+        let _ = write!(report, "\n== {title} ==\n");
+        match body {
+            Ok(body) => report.push_str(&body),
+            Err(e) => {
+                let _ = writeln!(report, "unavailable: {e}");
+            }
+        }
+    };
+    section(
+        report,
+        "status",
+        fs::read_to_string(proc_dir.join("status")),
+    );
+    section(report, "open file descriptors", open_fds(&proc_dir));
+    let maps = fs::read(proc_dir.join("maps")).map(|raw| {
+        let mut maps = String::from_utf8_lossy(&raw[..raw.len().min(MAPS_LIMIT)]).into_owned();
+        if raw.len() > MAPS_LIMIT {
+            maps.push_str("…[truncated]\n");
+        }
+        maps
+    });
+    section(report, "memory map", maps);
+}
+
+#[cfg(target_os = "linux")]
+fn open_fds(proc_dir: &Path) -> io::Result<String> {
+    let mut fds: Vec<(u32, String)> = fs::read_dir(proc_dir.join("fd"))?
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let fd = entry.file_name().to_str()?.parse().ok()?;
+            let target = fs::read_link(entry.path()).ok()?;
+            Some((fd, target.display().to_string()))
+        })
+        .collect();
+    fds.sort();
+    Ok(fds
+        .iter()
+        .map(|(fd, target)| format!("{fd} -> {target}\n"))
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn snapshot(_pid: u32, report: &mut String) {
+    report.push_str("\nProcess details are only collected on Linux.\n");
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn hang_report_lists_descriptors_and_memory_map() {
+        let dir = std::env::temp_dir().join(format!("toshik-hang-{}", uuid::Uuid::new_v4()));
+        let mut child = Command::new("sleep").arg("30").spawn().unwrap();
+
+        let path = capture_hang(child.id(), "run-1", &dir, None).unwrap();
+        let report = fs::read_to_string(&path).unwrap();
+        assert!(path
+            .file_name()
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .starts_with("hang-"));
+        assert!(report.contains("run_id: run-1"));
+        assert!(
+            report.contains("== open file descriptors ==\n0 -> "),
+            "{report}"
+        );
+        assert!(report.contains("== memory map ==\n"));
+        assert!(!report.contains("unavailable"), "{report}");
+
+        let _ = child.kill();
+        let _ = child.wait();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(DumpSignal::parse("sigusr2"), Some(DumpSignal::Usr2));
+    }
+}
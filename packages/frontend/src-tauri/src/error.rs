@@ -0,0 +1,142 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::bun::{BunVersion, Invocation};
+
+/// Failures that can occur while preparing or spawning the backend.
+///
+/// Commands still hand plain strings to the frontend; this converts via `Display`.
+#[derive(Debug)]
+pub(crate) enum BackendError {
+    /// The installed bun is older than the configured minimum.
+    BunTooOld {
+        found: BunVersion,
+        required: BunVersion,
+    },
+    /// The `bun` executable could not be found.
+    BunNotFound(io::Error),
+    /// `TOSHIK_BUN_PATH` does not lead to an executable file.
+    BunPathInvalid { path: PathBuf, reason: String },
+    /// `TOSHIK_BUN_INVOCATION` can't start the configured entry.
+    InvocationInvalid {
+        invocation: Invocation,
+        reason: String,
+    },
+    /// A `direct` backend executable does not have the SHA-256 embedded at build time.
+    IntegrityCheckFailed {
+        path: PathBuf,
+        expected: String,
+        actual: String,
+    },
+    /// `TOSHIK_BACKEND_ENTRY` is not covered by the allowlist embedded at build time.
+    ScriptNotAllowed { path: PathBuf },
+    /// Running bun for a version check failed or printed something unparsable.
+    BunCheckFailed(String),
+    /// A port from the config or a caller is zero, above 65535, or privileged without
+    /// `TOSHIK_ALLOW_PRIVILEGED_PORTS`.
+    InvalidPort { port: u32, reason: &'static str },
+    /// The backend exited during startup because another process bound its port between
+    /// the launcher's check and bun's own bind.
+    PortRaceLost(u16),
+    /// Spawning the backend process itself failed.
+    SpawnFailed(io::Error),
+    /// The configured post-start hook could not run or exited unsuccessfully.
+    PostStartHookFailed(String),
+    /// The migration command run by `upgrade_backend` failed; the backend was not started.
+    MigrationFailed {
+        reason: String,
+        /// Last lines of `migration.log`.
+        log_tail: Vec<String>,
+    },
+    /// A restarted backend came up but never passed the health check.
+    RestartUnhealthy(String),
+    /// The opener plugin is not registered, so nothing can be opened in the browser.
+    OpenerUnavailable,
+    /// A `proxy_backend` call waited this long for a free slot without getting one.
+    ProxyThrottled(Duration),
+    /// A proxied response body grew past this many bytes and was dropped.
+    ProxyResponseTooLarge(usize),
+    /// A proxied response body was not fully read within this long.
+    ProxyReadTimeout(Duration),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BunTooOld { found, required } => {
+                write!(f, "bun {found} is too old, {required} or newer is required")
+            }
+            Self::BunNotFound(e) => write!(f, "bun was not found on PATH: {e}"),
+            Self::BunPathInvalid { path, reason } => {
+                write!(
+                    f,
+                    "TOSHIK_BUN_PATH {} is not usable: {reason}",
+                    path.display()
+                )
+            }
+            Self::InvocationInvalid { invocation, reason } => {
+                write!(
+                    f,
+                    "TOSHIK_BUN_INVOCATION={invocation} can't be used: {reason}"
+                )
+            }
+            Self::IntegrityCheckFailed {
+                path,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "Refusing to start {}: sha256 is {actual}, expected {expected}",
+                path.display()
+            ),
+            Self::BunCheckFailed(reason) => write!(f, "bun check failed: {reason}"),
+            Self::ScriptNotAllowed { path } => write!(
+                f,
+                "Refusing to start {}: TOSHIK_BACKEND_ENTRY is not in this build's allowlist",
+                path.display()
+            ),
+            Self::PortRaceLost(port) => write!(
+                f,
+                "Port {port} was taken by another process before the backend could bind it"
+            ),
+            Self::OpenerUnavailable => {
+                f.write_str("Opening URLs is not available: the opener plugin is not loaded")
+            }
+            Self::InvalidPort { port, reason } => write!(f, "Invalid port {port}: {reason}"),
+            Self::SpawnFailed(e) => write!(f, "Failed to spawn bun backend: {e}"),
+            Self::PostStartHookFailed(reason) => write!(f, "Post-start hook failed: {reason}"),
+            Self::MigrationFailed { reason, log_tail } => {
+                write!(f, "Migration failed: {reason}")?;
+                for line in log_tail {
+                    write!(f, "\n{line}")?;
+                }
+                Ok(())
+            }
+            Self::RestartUnhealthy(reason) => {
+                write!(f, "Restarted backend is not healthy: {reason}")
+            }
+            Self::ProxyThrottled(waited) => write!(
+                f,
+                "Too many proxy requests in flight, gave up after waiting {waited:?}"
+            ),
+            Self::ProxyResponseTooLarge(limit) => write!(
+                f,
+                "Backend response exceeds the proxy limit of {limit} bytes \
+                 (TOSHIK_PROXY_MAX_RESPONSE_BYTES)"
+            ),
+            Self::ProxyReadTimeout(limit) => {
+                write!(f, "Backend response was not read within {limit:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl From<BackendError> for String {
+    fn from(err: BackendError) -> Self {
+        err.to_string()
+    }
+}
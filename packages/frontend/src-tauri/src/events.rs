@@ -0,0 +1,352 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::logs;
+
+/// How many emitted events `recent_events` can replay.
+const HISTORY_CAPACITY: usize = 100;
+
+/// Emitted after the backend process has been spawned.
+pub(crate) const STARTED: &str = "backend://started";
+/// Emitted after the launcher has stopped the backend process.
+pub(crate) const STOPPED: &str = "backend://stopped";
+/// Emitted when a launch picks a different port than the previous one, before the backend
+/// is reported as started, so subscribers can rebuild their base URL.
+pub(crate) const PORT_CHANGED: &str = "backend://port-changed";
+/// Emitted once a spawned backend accepts connections on its port.
+pub(crate) const READY: &str = "backend://ready";
+/// Emitted when a launcher-initiated start (autostart) fails.
+pub(crate) const START_FAILED: &str = "backend://start-failed";
+/// Emitted when a spawned backend doesn't accept connections within the readiness timeout,
+/// or doesn't pass one of `TOSHIK_READY_ENDPOINTS` in time.
+pub(crate) const NOT_READY: &str = "backend://not-ready";
+/// Emitted when a spawned backend exits during startup because its port was taken after
+/// the launcher checked it; a new launch on another port follows if `retrying`.
+pub(crate) const PORT_RACE_LOST: &str = "backend://port-race-lost";
+/// Emitted when the warmup request fails.
+pub(crate) const WARMUP_FAILED: &str = "backend://warmup-failed";
+/// Emitted when `restart_backend` or `drain_backend` has a new backend up that passed the
+/// health check.
+pub(crate) const RESTARTED: &str = "backend://restarted";
+/// Emitted when `schedule_restart` has set a restart up.
+pub(crate) const RESTART_SCHEDULED: &str = "backend://restart-scheduled";
+/// Emitted when a pending restart is called off, by `cancel_scheduled_restart` or because a
+/// newer schedule replaced it.
+pub(crate) const RESTART_CANCELLED: &str = "backend://restart-cancelled";
+/// Emitted when a hang report was written for a backend about to be restarted.
+pub(crate) const HANG_CAPTURED: &str = "backend://hang-captured";
+/// Emitted when the post-start hook fails.
+pub(crate) const HOOK_FAILED: &str = "backend://hook-failed";
+/// Emitted when a backend is started in verbose diagnostics mode, as a reminder that it
+/// runs slower.
+pub(crate) const VERBOSE_MODE: &str = "backend://verbose-mode";
+/// Emitted with batches of `bun install` output while `install_backend_deps` runs.
+pub(crate) const INSTALL_LOG: &str = "backend://install-log";
+/// Emitted when `bun install` succeeded.
+pub(crate) const INSTALL_DONE: &str = "backend://install-done";
+/// Emitted when `bun install` could not run or exited unsuccessfully.
+pub(crate) const INSTALL_FAILED: &str = "backend://install-failed";
+/// Emitted when the secure storage self-test passes.
+pub(crate) const STRONGHOLD_READY: &str = "stronghold://ready";
+/// Emitted when the secure storage self-test fails.
+pub(crate) const STRONGHOLD_ERROR: &str = "stronghold://error";
+
+/// How an event should be surfaced: `error` ones are worth a toast, `info` ones are
+/// bookkeeping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Info,
+    Warn,
+    Error,
+}
+
+/// The envelope every event is sent in: the payload's own fields plus `severity`.
+#[derive(Clone, Serialize)]
+pub(crate) struct LifecycleEvent<P> {
+    pub severity: Severity,
+    #[serde(flatten)]
+    pub payload: P,
+}
+
+/// The usual severity of `event`; `emit_as` overrides it where the payload decides.
+pub(crate) fn severity(event: &str) -> Severity {
+    match event {
+        START_FAILED | NOT_READY | HOOK_FAILED | INSTALL_FAILED | STRONGHOLD_ERROR => {
+            Severity::Error
+        }
+        WARMUP_FAILED
+        | PORT_RACE_LOST
+        | HANG_CAPTURED
+        | VERBOSE_MODE
+        | logs::LOG_TRUNCATED_EVENT => Severity::Warn,
+        _ => Severity::Info,
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartedPayload {
+    pub run_id: String,
+    pub port: u16,
+    /// `None` for a backend attached with `attach_backend`.
+    pub pid: Option<u32>,
+}
+
+/// Why the backend stopped, so the UI can tell "stopped by you" from "crashed".
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum StopReason {
+    /// `stop_backend` was called.
+    UserRequested,
+    /// `restart_backend` stopped it to start a fresh one.
+    Restart,
+    /// `upgrade_backend` stopped it to run the migration.
+    Upgrade,
+    /// The app is quitting (`shutdown_all` or the exit event).
+    AppExit,
+    /// The process exited on its own with status 0.
+    Exited,
+    /// The process exited on its own with a failure status or signal.
+    Crashed,
+    /// Its status could no longer be read, so the launcher killed it.
+    Lost,
+    /// The post-start hook failed and was configured to be fatal.
+    PostStartHookFailed,
+}
+
+impl StopReason {
+    /// Severity of a `stopped` event with this reason.
+    pub(crate) fn severity(self) -> Severity {
+        match self {
+            Self::Crashed => Severity::Error,
+            Self::PostStartHookFailed | Self::Lost => Severity::Warn,
+            _ => Severity::Info,
+        }
+    }
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StoppedPayload {
+    pub run_id: Option<String>,
+    pub reason: StopReason,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PortChangedPayload {
+    /// `None` for the first launch of this app session.
+    pub old_port: Option<u16>,
+    pub new_port: u16,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ReadyPayload {
+    pub run_id: String,
+    pub port: u16,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartFailedPayload {
+    pub error: String,
+}
+
+/// Payload of the per-launch failure events (`not-ready`, `warmup-failed`, `hook-failed`).
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RunErrorPayload {
+    pub run_id: String,
+    pub error: String,
+    /// For `not-ready`, the `TOSHIK_READY_ENDPOINTS` path the backend didn't pass.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HangCapturedPayload {
+    pub run_id: String,
+    /// The `hang-<timestamp>.txt` report.
+    pub path: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PortRaceLostPayload {
+    pub run_id: String,
+    pub port: u16,
+    /// Whether the launcher is starting the backend again on another port.
+    pub retrying: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RestartedPayload {
+    pub run_id: String,
+    pub previous_run_id: Option<String>,
+    pub port: u16,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RestartScheduledPayload {
+    /// Milliseconds since the Unix epoch.
+    pub at: u64,
+    pub drain: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RestartCancelledPayload {
+    /// When the cancelled restart was due.
+    pub at: u64,
+    /// A newer `schedule_restart` took its place.
+    pub replaced: bool,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VerboseModePayload {
+    pub run_id: String,
+    pub inspector_url: String,
+    pub warning: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InstallDonePayload {
+    pub duration_ms: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct InstallFailedPayload {
+    pub error: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StrongholdErrorPayload {
+    pub error: String,
+}
+
+/// An emitted event as recorded in [`EventHistory`].
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LauncherEvent {
+    pub event: String,
+    pub payload: Value,
+    /// Milliseconds since the Unix epoch.
+    pub timestamp: u64,
+}
+
+/// The last [`HISTORY_CAPACITY`] lifecycle events, so a webview that subscribes late can
+/// backfill what it missed.
+#[derive(Default)]
+pub(crate) struct EventHistory(Mutex<VecDeque<LauncherEvent>>);
+
+impl EventHistory {
+    fn record(&self, event: LauncherEvent) {
+        if let Ok(mut events) = self.0.lock() {
+            if events.len() == HISTORY_CAPACITY {
+                events.pop_front();
+            }
+            events.push_back(event);
+        }
+    }
+
+    /// Recorded events, oldest first.
+    pub(crate) fn snapshot(&self) -> Vec<LauncherEvent> {
+        self.0
+            .lock()
+            .map(|events| events.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Emit a lifecycle event to every webview and record it in the managed [`EventHistory`].
+/// Failures are only logged: during exit there may be no window left to receive them.
+pub(crate) fn emit<R: Runtime, P: Serialize + Clone>(app: &AppHandle<R>, event: &str, payload: P) {
+    emit_as(app, event, severity(event), payload);
+}
+
+/// [`emit`] with an explicit severity, for events whose payload decides it.
+pub(crate) fn emit_as<R: Runtime, P: Serialize + Clone>(
+    app: &AppHandle<R>,
+    event: &str,
+    severity: Severity,
+    payload: P,
+) {
+    let payload = LifecycleEvent { severity, payload };
+    if let Some(history) = app.try_state::<EventHistory>() {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        history.record(LauncherEvent {
+            event: event.to_string(),
+            payload: serde_json::to_value(&payload).unwrap_or(Value::Null),
+            timestamp,
+        });
+    }
+    send(app, event, payload);
+}
+
+/// Emit without recording: for high-volume streams such as `backend://log` that would
+/// flush lifecycle events out of the history.
+pub(crate) fn broadcast<R: Runtime, P: Serialize + Clone>(
+    app: &AppHandle<R>,
+    event: &str,
+    payload: P,
+) {
+    let severity = severity(event);
+    send(app, event, LifecycleEvent { severity, payload });
+}
+
+fn send<R: Runtime, P: Serialize + Clone>(
+    app: &AppHandle<R>,
+    event: &str,
+    payload: LifecycleEvent<P>,
+) {
+    if let Err(e) = app.emit(event, payload) {
+        log::debug!("Failed to emit {event}: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn severity_is_flattened_into_the_payload() {
+        let stopped = LifecycleEvent {
+            severity: StopReason::Crashed.severity(),
+            payload: StoppedPayload {
+                run_id: Some("run".into()),
+                reason: StopReason::Crashed,
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(stopped).unwrap(),
+            serde_json::json!({ "severity": "error", "runId": "run", "reason": "crashed" })
+        );
+        let ready = LifecycleEvent {
+            severity: severity(STRONGHOLD_READY),
+            payload: (),
+        };
+        assert_eq!(
+            serde_json::to_value(ready).unwrap(),
+            serde_json::json!({ "severity": "info" })
+        );
+        assert_eq!(severity(NOT_READY), Severity::Error);
+        assert_eq!(severity(logs::LOG_BATCH_EVENT), Severity::Info);
+    }
+}
@@ -0,0 +1,268 @@
+use std::fs::{File, Metadata};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tauri::{AppHandle, Runtime};
+
+use crate::events;
+use crate::logs::{self, LogLinePayload, LOG_EVENT};
+
+/// How often the followed file is checked for new bytes or rotation.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Chunk size used when scanning backwards for the last lines.
+const TAIL_CHUNK: u64 = 8 * 1024;
+
+/// Stream name given to lines read back from the log file.
+const FILE_STREAM: &str = "file";
+
+/// The `follow_backend_log` thread, if one is running.
+#[derive(Default)]
+pub(crate) struct LogFollower(Mutex<Option<Following>>);
+
+struct Following {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+impl LogFollower {
+    /// Emit the last `from_end_lines` lines of `path` as `backend://log` events, then keep
+    /// emitting lines as they are appended. Lines over `max_line` bytes are truncated.
+    /// Replaces any follower already running.
+    pub(crate) fn start<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        path: PathBuf,
+        from_end_lines: usize,
+        max_line: usize,
+    ) -> Result<(), String> {
+        let mut slot = self.0.lock().map_err(|e| e.to_string())?;
+        if let Some(previous) = slot.take() {
+            previous.stop();
+        }
+        let stop = Arc::new(AtomicBool::new(false));
+        let app = app.clone();
+        let flag = Arc::clone(&stop);
+        let thread = thread::Builder::new()
+            .name("backend-log-follow".into())
+            .spawn(move || follow(&app, &path, from_end_lines, max_line, &flag))
+            .map_err(|e| format!("Failed to start log follower: {e}"))?;
+        *slot = Some(Following { stop, thread });
+        Ok(())
+    }
+
+    /// Stop the follower; returns whether one was running.
+    pub(crate) fn stop(&self) -> Result<bool, String> {
+        let following = self.0.lock().map_err(|e| e.to_string())?.take();
+        let running = following.is_some();
+        if let Some(following) = following {
+            following.stop();
+        }
+        Ok(running)
+    }
+}
+
+impl Following {
+    fn stop(self) {
+        self.stop.store(true, Ordering::SeqCst);
+        let _ = self.thread.join();
+    }
+}
+
+/// The followed file and how far into it we have read.
+struct Position {
+    reader: BufReader<File>,
+    metadata: Metadata,
+    offset: u64,
+}
+
+impl Position {
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = File::open(path)?;
+        Ok(Self {
+            metadata: file.metadata()?,
+            reader: BufReader::new(file),
+            offset: 0,
+        })
+    }
+}
+
+fn follow<R: Runtime>(
+    app: &AppHandle<R>,
+    path: &Path,
+    from_end_lines: usize,
+    max_line: usize,
+    stop: &AtomicBool,
+) {
+    let emit = |line: &[u8]| {
+        events::broadcast(
+            app,
+            LOG_EVENT,
+            LogLinePayload {
+                run_id: None,
+                stream: FILE_STREAM,
+                line: String::from_utf8_lossy(line).trim_end().to_string(),
+            },
+        );
+    };
+
+    let mut position = match Position::open(path) {
+        Ok(mut position) => match tail_lines(position.reader.get_mut(), from_end_lines, max_line) {
+            Ok((lines, end)) => {
+                lines.iter().for_each(|line| emit(line));
+                position.offset = end;
+                Some(position)
+            }
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                None
+            }
+        },
+        // Not written yet; picked up from the start once it appears.
+        Err(_) => None,
+    };
+    // A line the writer has not finished yet, carried over to the next poll.
+    let mut pending = Vec::new();
+    let mut truncated = false;
+
+    while !stop.load(Ordering::SeqCst) {
+        thread::sleep(POLL_INTERVAL);
+        let current = std::fs::metadata(path).ok();
+        let rotated = match (&position, &current) {
+            (Some(position), Some(current)) => {
+                !same_file(&position.metadata, current) || current.len() < position.offset
+            }
+            (None, Some(_)) => true,
+            (_, None) => false,
+        };
+        if rotated {
+            log::info!("Following {} from the start", path.display());
+            pending.clear();
+            truncated = false;
+            position = Position::open(path).ok();
+        }
+        let Some(ref mut position) = position else {
+            continue;
+        };
+        loop {
+            match logs::read_line_capped(&mut position.reader, &mut pending, max_line) {
+                Ok((0, _)) => break,
+                Ok((read, dropped)) => {
+                    position.offset += read as u64;
+                    truncated |= dropped;
+                }
+                Err(e) => {
+                    log::warn!("Failed to read {}: {e}", path.display());
+                    break;
+                }
+            }
+            if !pending.ends_with(b"\n") {
+                continue;
+            }
+            if truncated {
+                log::warn!("Followed log line exceeded {max_line} bytes, truncated");
+                logs::mark_truncated(&mut pending);
+            }
+            emit(&pending);
+            pending.clear();
+            truncated = false;
+        }
+    }
+}
+
+/// Whether two metadata snapshots describe the same file rather than a replacement created
+/// at the same path by rotation. Only detectable on Unix; elsewhere a shrinking file is the
+/// only sign of rotation.
+fn same_file(a: &Metadata, b: &Metadata) -> bool {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        a.dev() == b.dev() && a.ino() == b.ino()
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (a, b);
+        true
+    }
+}
+
+/// The last `count` lines of `file` and its end offset, where following continues. Reads
+/// backwards from the end in chunks, so only the tail of a large log is loaded, and no more
+/// than `count` lines of `max_line` bytes even if they are longer; those are truncated.
+pub(crate) fn tail_lines(
+    file: &mut File,
+    count: usize,
+    max_line: usize,
+) -> io::Result<(Vec<Vec<u8>>, u64)> {
+    let max_scan = (count as u64).saturating_mul(max_line as u64 + 1);
+    let end = file.seek(SeekFrom::End(0))?;
+    let mut start = end;
+    let mut tail = Vec::new();
+    // Done once a newline precedes the first wanted line (one ending the file doesn't count).
+    let separators = |tail: &[u8]| {
+        let body = tail.strip_suffix(b"\n").unwrap_or(tail);
+        body.iter().filter(|&&b| b == b'\n').count()
+    };
+    while count > 0 && start > 0 && end - start < max_scan && separators(&tail) < count {
+        let chunk = TAIL_CHUNK.min(start);
+        start -= chunk;
+        file.seek(SeekFrom::Start(start))?;
+        let mut buf = vec![0; chunk as usize];
+        file.read_exact(&mut buf)?;
+        buf.extend_from_slice(&tail);
+        tail = buf;
+    }
+    file.seek(SeekFrom::Start(end))?;
+
+    let mut lines: Vec<Vec<u8>> = tail
+        .split_inclusive(|&b| b == b'\n')
+        .map(<[u8]>::to_vec)
+        .collect();
+    // Unless the scan reached the start of the file, the first piece is cut off.
+    if start > 0 && !lines.is_empty() {
+        lines.remove(0);
+    }
+    let skip = lines.len().saturating_sub(count);
+    let mut lines = lines.split_off(skip);
+    for line in &mut lines {
+        if line.len() > max_line {
+            line.truncate(max_line);
+            logs::mark_truncated(line);
+        }
+    }
+    Ok((lines, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn tail_returns_the_last_lines_across_chunks() {
+        let path = std::env::temp_dir().join(format!("toshik-tail-{}.log", uuid::Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        let lines: Vec<String> = (0..2000).map(|i| format!("line {i:04}\n")).collect();
+        file.write_all(lines.concat().as_bytes()).unwrap();
+        file.write_all(b"partial").unwrap();
+
+        let mut file = File::open(&path).unwrap();
+        let (tail, end) = tail_lines(&mut file, 3, logs::DEFAULT_MAX_LINE).unwrap();
+        assert_eq!(tail, [&b"line 1998\n"[..], b"line 1999\n", b"partial"]);
+        assert_eq!(end, file.metadata().unwrap().len());
+
+        let (all, _) = tail_lines(&mut file, 5000, logs::DEFAULT_MAX_LINE).unwrap();
+        assert_eq!(all.len(), 2001);
+        assert_eq!(all[0], b"line 0000\n");
+        assert!(tail_lines(&mut file, 0, logs::DEFAULT_MAX_LINE)
+            .unwrap()
+            .0
+            .is_empty());
+        std::fs::remove_file(&path).unwrap();
+    }
+}
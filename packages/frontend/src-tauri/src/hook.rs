@@ -0,0 +1,115 @@
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::error::BackendError;
+use crate::follow;
+
+/// Lines of `migration.log` included in a `MigrationFailed` error.
+const MIGRATION_LOG_TAIL: usize = 20;
+
+/// Run the configured post-start hook through the platform shell, appending its output to
+/// `log_path`. The hook sees the backend's port and run ID as `TOSHIK_BACKEND_PORT` and
+/// `TOSHIK_RUN_ID`.
+pub(crate) fn run(
+    command: &str,
+    port: u16,
+    run_id: &str,
+    log_path: &Path,
+) -> Result<(), BackendError> {
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(|e| format!("failed to open {}: {e}", log_path.display()))
+        .map_err(BackendError::PostStartHookFailed)?;
+    let mut cmd = shell(command);
+    cmd.env("TOSHIK_BACKEND_PORT", port.to_string())
+        .env("TOSHIK_RUN_ID", run_id);
+    log::info!("Running post-start hook: {command}");
+    run_logged(cmd, command, log_file).map_err(BackendError::PostStartHookFailed)
+}
+
+/// Run the migration command (`TOSHIK_MIGRATION_COMMAND`) through the platform shell with
+/// `env` added, while no backend is running. `log_path` is truncated first so it holds
+/// this run only; on failure its last lines are part of the error.
+pub(crate) fn migrate(
+    command: &str,
+    env: &BTreeMap<String, String>,
+    log_path: &Path,
+) -> Result<(), BackendError> {
+    let log_file = File::create(log_path).map_err(|e| BackendError::MigrationFailed {
+        reason: format!("failed to open {}: {e}", log_path.display()),
+        log_tail: Vec::new(),
+    })?;
+    let mut cmd = shell(command);
+    cmd.envs(env);
+    log::info!("Running migration: {command}");
+    run_logged(cmd, command, log_file).map_err(|reason| {
+        let log_tail = File::open(log_path)
+            .and_then(|mut file| follow::tail_lines(&mut file, MIGRATION_LOG_TAIL, 4096))
+            .map(|(lines, _)| {
+                lines
+                    .iter()
+                    .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+                    .collect()
+            })
+            .unwrap_or_default();
+        BackendError::MigrationFailed { reason, log_tail }
+    })
+}
+
+/// `sh -c <command>`, or `cmd /C <command>` on Windows.
+fn shell(command: &str) -> Command {
+    let mut cmd = if cfg!(windows) {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C");
+        cmd
+    } else {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c");
+        cmd
+    };
+    cmd.arg(command);
+    cmd
+}
+
+/// Run `cmd` to completion with stdout and stderr going to `log_file`.
+fn run_logged(mut cmd: Command, command: &str, log_file: File) -> Result<(), String> {
+    let stderr = log_file
+        .try_clone()
+        .map_err(|e| format!("failed to clone log file handle: {e}"))?;
+    let status = cmd
+        .stdin(Stdio::null())
+        .stdout(log_file)
+        .stderr(stderr)
+        .status()
+        .map_err(|e| format!("failed to run `{command}`: {e}"))?;
+    if !status.success() {
+        return Err(format!("`{command}` exited with {status}"));
+    }
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_migration_reports_the_tail_of_its_log() {
+        let log =
+            std::env::temp_dir().join(format!("toshik-migration-{}.log", uuid::Uuid::new_v4()));
+        let env = BTreeMap::from([("STEP".to_string(), "2".to_string())]);
+
+        migrate("echo first run", &env, &log).unwrap();
+        let err = migrate("echo applying $STEP; echo broken >&2; exit 3", &env, &log).unwrap_err();
+
+        let BackendError::MigrationFailed { reason, log_tail } = err else {
+            panic!("unexpected error: {err}");
+        };
+        assert!(reason.contains("exited with"), "{reason}");
+        assert_eq!(log_tail, ["applying 2", "broken"]);
+        std::fs::remove_file(log).unwrap();
+    }
+}
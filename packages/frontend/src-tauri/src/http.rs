@@ -0,0 +1,193 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use reqwest::{Client, Method, Request, RequestBuilder, Response, Url};
+use serde::{Serialize, Serializer};
+
+/// Connection and retry behaviour shared by every request the launcher makes to the backend.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HttpConfig {
+    /// Time allowed to establish the TCP connection (`TOSHIK_HTTP_CONNECT_TIMEOUT_MS`).
+    #[serde(rename = "connectTimeoutMs", serialize_with = "millis")]
+    pub connect_timeout: Duration,
+    /// Time allowed between reads of the response (`TOSHIK_HTTP_READ_TIMEOUT_MS`).
+    #[serde(rename = "readTimeoutMs", serialize_with = "millis")]
+    pub read_timeout: Duration,
+    /// Extra attempts for idempotent GETs that fail to connect or time out
+    /// (`TOSHIK_HTTP_RETRIES`).
+    pub get_retries: u32,
+    /// `proxy_backend` calls allowed in flight at once; the rest queue
+    /// (`TOSHIK_PROXY_CONCURRENCY`).
+    pub proxy_concurrency: usize,
+    /// How long a queued `proxy_backend` call waits for a slot before failing
+    /// (`TOSHIK_PROXY_QUEUE_TIMEOUT_MS`).
+    #[serde(rename = "proxyQueueTimeoutMs", serialize_with = "millis")]
+    pub proxy_queue_timeout: Duration,
+    /// Largest response body `proxy_backend` relays, in bytes
+    /// (`TOSHIK_PROXY_MAX_RESPONSE_BYTES`).
+    pub proxy_max_response: usize,
+    /// Time allowed to read a proxied response body once its headers arrived
+    /// (`TOSHIK_PROXY_READ_TIMEOUT_MS`); connecting is bounded by `connect_timeout` alone.
+    #[serde(rename = "proxyReadTimeoutMs", serialize_with = "millis")]
+    pub proxy_read_timeout: Duration,
+}
+
+/// Serialize a duration as whole milliseconds.
+pub(crate) fn millis<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Duration::from_secs(2),
+            read_timeout: Duration::from_secs(30),
+            get_retries: 2,
+            proxy_concurrency: 16,
+            proxy_queue_timeout: Duration::from_secs(10),
+            proxy_max_response: 10 * 1024 * 1024,
+            proxy_read_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Time allowed to connect to the backend for `POST /shutdown` and to get its answer.
+const SHUTDOWN_REQUEST_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Header carrying the shutdown token unless `TOSHIK_SHUTDOWN_TOKEN_HEADER` names another.
+pub(crate) const DEFAULT_SHUTDOWN_HEADER: &str = "X-Shutdown-Token";
+
+/// Secret generated for each launch and passed to the backend as `SHUTDOWN_TOKEN` (with the
+/// header name as `SHUTDOWN_TOKEN_HEADER`), so only the launcher can make it exit through
+/// `POST /shutdown`.
+#[derive(Debug, Clone)]
+pub(crate) struct ShutdownToken {
+    pub header: String,
+    pub secret: String,
+}
+
+impl ShutdownToken {
+    pub(crate) fn generate(header: &str) -> Self {
+        Self {
+            header: header.to_string(),
+            secret: uuid::Uuid::new_v4().simple().to_string(),
+        }
+    }
+
+    /// Ask the backend on `addr` to exit. `Ok` once it answered 202; the process may still
+    /// be finishing up. This runs from synchronous stop paths, so it doesn't use the
+    /// async client.
+    pub(crate) fn request_shutdown(&self, addr: SocketAddr) -> Result<(), String> {
+        let mut stream = TcpStream::connect_timeout(&addr, SHUTDOWN_REQUEST_TIMEOUT)
+            .map_err(|e| format!("TCP connect to port {} failed: {e}", addr.port()))?;
+        stream
+            .set_read_timeout(Some(SHUTDOWN_REQUEST_TIMEOUT))
+            .and_then(|()| stream.set_write_timeout(Some(SHUTDOWN_REQUEST_TIMEOUT)))
+            .map_err(|e| e.to_string())?;
+        // One write: a backend that answers after the first packet must see the whole head.
+        let request = format!(
+            "POST /shutdown HTTP/1.1\r\nHost: {addr}\r\n{}: {}\r\nContent-Length: 0\r\n\
+             Connection: close\r\n\r\n",
+            self.header, self.secret
+        );
+        stream
+            .write_all(request.as_bytes())
+            .map_err(|e| format!("POST /shutdown failed: {e}"))?;
+        let mut status = String::new();
+        BufReader::new(&stream)
+            .read_line(&mut status)
+            .map_err(|e| format!("No response to POST /shutdown: {e}"))?;
+        match status.split_whitespace().nth(1) {
+            Some("202") => Ok(()),
+            _ => Err(format!(
+                "POST /shutdown answered {:?}, expected 202",
+                status.trim_end()
+            )),
+        }
+    }
+}
+
+/// Base delay between GET retries; grows linearly with the attempt number.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The one HTTP client used for version, proxy and health requests.
+pub(crate) struct BackendClient {
+    client: Client,
+    get_retries: u32,
+}
+
+impl BackendClient {
+    pub(crate) fn new(config: &HttpConfig) -> Result<Self, String> {
+        let client = Client::builder()
+            .connect_timeout(config.connect_timeout)
+            .read_timeout(config.read_timeout)
+            .build()
+            .map_err(|e| format!("Failed to build HTTP client: {e}"))?;
+        Ok(Self {
+            client,
+            get_retries: config.get_retries,
+        })
+    }
+
+    /// Start a request to `url`; build it and pass it to [`Self::send`].
+    pub(crate) fn request(&self, method: Method, url: Url) -> RequestBuilder {
+        self.client.request(method, url)
+    }
+
+    /// Execute `request`. GETs (and only GETs) are retried on connect errors and timeouts,
+    /// which is when a slow or just-restarted backend is most likely to recover.
+    pub(crate) async fn send(&self, request: Request) -> reqwest::Result<Response> {
+        let retries = if request.method() == Method::GET {
+            self.get_retries
+        } else {
+            0
+        };
+        let mut attempt = 0;
+        loop {
+            let Some(next) = request.try_clone().filter(|_| attempt < retries) else {
+                return self.client.execute(request).await;
+            };
+            match self.client.execute(next).await {
+                Err(e) if e.is_connect() || e.is_timeout() => {
+                    attempt += 1;
+                    log::debug!("Backend GET failed ({e}), retrying ({attempt}/{retries})");
+                    tokio::time::sleep(RETRY_DELAY * attempt).await;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    #[test]
+    fn shutdown_request_carries_the_token_header() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = [0; 1024];
+            let read = stream.read(&mut request).unwrap();
+            stream.write_all(b"HTTP/1.1 202 Accepted\r\n\r\n").unwrap();
+            String::from_utf8_lossy(&request[..read]).into_owned()
+        });
+
+        let token = ShutdownToken::generate("X-Test-Token");
+        token.request_shutdown(addr).unwrap();
+        let request = server.join().unwrap();
+        assert!(
+            request.starts_with("POST /shutdown HTTP/1.1\r\n"),
+            "{request}"
+        );
+        let header = format!("X-Test-Token: {}\r\n", token.secret);
+        assert!(request.contains(&header), "{request}");
+    }
+}
@@ -0,0 +1,177 @@
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::events;
+use crate::logs::{self, LineOptions, LogLine};
+use crate::tasks::TaskRegistry;
+
+/// Set while a `bun install` runs, so two can't race on `node_modules`.
+static RUNNING: AtomicBool = AtomicBool::new(false);
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct InstallLogPayload<'a> {
+    lines: &'a [LogLine],
+    /// Latest progress bun reported, in percent, if it reported any so far.
+    progress: Option<u8>,
+}
+
+/// Run `bun install` in `workspace` on a background thread. Output is appended to
+/// `log_path` and emitted in batches as `backend://install-log`; the outcome is announced
+/// with `backend://install-done` or `backend://install-failed`.
+pub(crate) fn spawn<R: Runtime>(
+    app: &AppHandle<R>,
+    bun: PathBuf,
+    workspace: PathBuf,
+    log_path: PathBuf,
+    max_line: usize,
+) -> Result<(), String> {
+    if RUNNING.swap(true, Ordering::SeqCst) {
+        return Err("bun install is already running".into());
+    }
+    let tasks = app.state::<TaskRegistry>();
+    let app = app.clone();
+    let spawned = tasks.spawn("bun-install", None, move |_| {
+        let started = Instant::now();
+        let result = run(&app, &bun, &workspace, &log_path, max_line);
+        RUNNING.store(false, Ordering::SeqCst);
+        match result {
+            Ok(()) => {
+                log::info!("bun install finished in {:?}", started.elapsed());
+                let duration_ms = started.elapsed().as_millis() as u64;
+                let payload = events::InstallDonePayload { duration_ms };
+                events::emit(&app, events::INSTALL_DONE, payload);
+            }
+            Err(error) => {
+                log::warn!("bun install failed: {error}");
+                let payload = events::InstallFailedPayload { error };
+                events::emit(&app, events::INSTALL_FAILED, payload);
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        RUNNING.store(false, Ordering::SeqCst);
+        return Err(format!("Failed to start bun install: {e}"));
+    }
+    Ok(())
+}
+
+fn run<R: Runtime>(
+    app: &AppHandle<R>,
+    bun: &Path,
+    workspace: &Path,
+    log_path: &Path,
+    max_line: usize,
+) -> Result<(), String> {
+    let log_file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .map_err(|e| format!("Failed to open {}: {e}", log_path.display()))?;
+    let sink = Mutex::new(log_file);
+
+    log::info!("Running bun install in {}", workspace.display());
+    let mut child = Command::new(bun)
+        .arg("install")
+        .current_dir(workspace)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("Failed to run {} install: {e}", bun.display()))?;
+
+    let mut pipes: Vec<(&'static str, Box<dyn Read + Send>)> = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        pipes.push(("stdout", Box::new(stdout)));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pipes.push(("stderr", Box::new(stderr)));
+    }
+    let (lines, queue) = mpsc::sync_channel(logs::QUEUE_CAPACITY);
+    thread::scope(|scope| {
+        for (stream, pipe) in pipes {
+            let lines = lines.clone();
+            let sink = &sink;
+            scope.spawn(move || {
+                logs::copy_lines(
+                    "",
+                    stream,
+                    &LineOptions::raw(max_line),
+                    pipe,
+                    Some(sink),
+                    |line| {
+                        let line = String::from_utf8_lossy(line).trim_end().to_string();
+                        // Blocking is fine here: install output is modest and slowing bun
+                        // down beats dropping its progress.
+                        let _ = lines.send(LogLine { stream, line });
+                    },
+                );
+            });
+        }
+        drop(lines);
+
+        let mut progress = None;
+        logs::batch_lines(&queue, &logs::Dropped::default(), |lines, _| {
+            if let Some(latest) = lines.iter().rev().find_map(|l| parse_progress(&l.line)) {
+                progress = Some(latest);
+            }
+            let payload = InstallLogPayload { lines, progress };
+            events::broadcast(app, events::INSTALL_LOG, payload);
+        });
+    });
+
+    let status = child
+        .wait()
+        .map_err(|e| format!("Failed to wait for bun install: {e}"))?;
+    if !status.success() {
+        return Err(format!(
+            "bun install exited with {status}, see {}",
+            log_path.display()
+        ));
+    }
+    Ok(())
+}
+
+/// A progress percentage from a line of bun output: an explicit `42%`, or a `[3/4]`-style
+/// step counter.
+fn parse_progress(line: &str) -> Option<u8> {
+    if let Some(end) = line.find('%') {
+        let digits = line[..end]
+            .rsplit(|c: char| !c.is_ascii_digit())
+            .next()
+            .filter(|digits| !digits.is_empty());
+        if let Some(percent) = digits.and_then(|d| d.parse::<u8>().ok()) {
+            return Some(percent.min(100));
+        }
+    }
+    let start = line.find('[')?;
+    let (done, total) = line[start + 1..].split_once(']')?.0.split_once('/')?;
+    let (done, total): (u64, u64) = (done.trim().parse().ok()?, total.trim().parse().ok()?);
+    (total > 0 && done <= total).then(|| (done * 100 / total) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn progress_is_read_from_percentages_and_step_counters() {
+        assert_eq!(parse_progress("Downloading 42% (12/30)"), Some(42));
+        assert_eq!(parse_progress("[3/4] Linking dependencies"), Some(75));
+        assert_eq!(parse_progress("+ react@19.0.0"), None);
+        assert_eq!(
+            parse_progress("Resolved, downloaded and extracted [120]"),
+            None
+        );
+        assert_eq!(parse_progress("100%"), Some(100));
+    }
+}
@@ -0,0 +1,120 @@
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use sha2::{Digest, Sha256};
+
+use crate::error::BackendError;
+
+/// SHA-256 (hex) a `direct` backend executable must have, embedded at build time with
+/// `TOSHIK_BACKEND_SHA256`. Builds without it skip the check.
+pub(crate) const EXPECTED_SHA256: Option<&str> = option_env!("TOSHIK_BACKEND_SHA256");
+
+/// What `TOSHIK_BACKEND_ENTRY` may name in release builds, embedded at build time with
+/// `TOSHIK_ALLOWED_ENTRIES` (separated like `PATH`). Each item is an allowed entry or a
+/// directory everything under which is allowed. A release build without it refuses any
+/// override; debug builds allow all of them.
+pub(crate) const ALLOWED_ENTRIES: Option<&str> = option_env!("TOSHIK_ALLOWED_ENTRIES");
+
+/// Refuse `entry` unless `allowlist` covers it. Existing paths are compared after resolving
+/// symlinks and `..`, so an allowed directory can't be escaped; anything else, like a
+/// package for `bun x`, has to be listed as is.
+pub(crate) fn check_entry_allowed(
+    entry: &Path,
+    allowlist: Option<&str>,
+    enforce: bool,
+) -> Result<(), BackendError> {
+    if !enforce {
+        return Ok(());
+    }
+    let allowed: Vec<PathBuf> = allowlist
+        .map(|raw| std::env::split_paths(raw).filter(|item| !item.as_os_str().is_empty()))
+        .into_iter()
+        .flatten()
+        .map(|item| fs::canonicalize(&item).unwrap_or(item))
+        .collect();
+    let covered = match fs::canonicalize(entry) {
+        Ok(resolved) => allowed.iter().any(|item| resolved.starts_with(item)),
+        Err(_) => allowed.iter().any(|item| item == entry),
+    };
+    if covered {
+        return Ok(());
+    }
+    log::error!("Refusing backend entry {}", entry.display());
+    Err(BackendError::ScriptNotAllowed {
+        path: entry.to_path_buf(),
+    })
+}
+
+/// Lower-case hex SHA-256 of the file at `path`, read in chunks.
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut hasher = Sha256::new();
+    io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect())
+}
+
+/// Refuse `path` unless it hashes to `expected` (hex, any case).
+pub(crate) fn verify(path: &Path, expected: &str) -> Result<(), BackendError> {
+    let actual = sha256_file(path).unwrap_or_else(|e| format!("unreadable ({e})"));
+    if actual.eq_ignore_ascii_case(expected.trim()) {
+        return Ok(());
+    }
+    log::error!(
+        "Integrity check of {} failed: expected sha256 {expected}, computed {actual}",
+        path.display()
+    );
+    Err(BackendError::IntegrityCheckFailed {
+        path: path.to_path_buf(),
+        expected: expected.to_string(),
+        actual,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify_compares_the_file_hash() {
+        let path = std::env::temp_dir().join(format!("toshik-sha-{}", uuid::Uuid::new_v4()));
+        std::fs::write(&path, "abc").unwrap();
+        let abc = "BA7816BF8F01CFEA414140DE5DAE2223B00361A396177A9CB410FF61F20015AD";
+
+        assert!(verify(&path, abc).is_ok());
+        assert!(matches!(
+            verify(&path, &"0".repeat(64)),
+            Err(BackendError::IntegrityCheckFailed { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+        assert!(verify(&path, abc).is_err(), "missing file");
+    }
+
+    #[test]
+    fn entries_outside_the_allowlist_are_refused() {
+        let dir = std::env::temp_dir().join(format!("toshik-allow-{}", uuid::Uuid::new_v4()));
+        let allowed = dir.join("allowed");
+        fs::create_dir_all(&allowed).unwrap();
+        let script = allowed.join("index.ts");
+        fs::write(&script, "").unwrap();
+        let allowlist = std::env::join_paths([&allowed, Path::new("@toshik/backend")]).unwrap();
+        let allowlist = allowlist.to_str();
+        let refused = |entry: &Path| {
+            matches!(
+                check_entry_allowed(entry, allowlist, true),
+                Err(BackendError::ScriptNotAllowed { .. })
+            )
+        };
+
+        assert!(check_entry_allowed(&script, allowlist, true).is_ok());
+        assert!(check_entry_allowed(Path::new("@toshik/backend"), allowlist, true).is_ok());
+        assert!(refused(&allowed.join("..").join("other.ts")));
+        assert!(refused(Path::new("@toshik/other")));
+        assert!(check_entry_allowed(&script, None, true).is_err());
+        assert!(check_entry_allowed(Path::new("/tmp/anything"), None, false).is_ok());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,195 +1,3715 @@
+mod audit;
+mod backend_env;
+mod backend_task;
+mod bun;
+mod command;
+mod config;
+mod container;
+mod diagnostics;
+mod error;
+mod events;
+mod follow;
+mod hook;
+mod http;
+mod install;
+mod integrity;
+mod logger;
+mod logs;
+mod net;
+mod paths;
+mod pidfile;
+mod proxy;
+mod ready;
+mod resolve;
+mod schedule;
+mod secure_storage;
+mod session;
+mod settings;
+mod shell;
+mod sockets;
+mod syslog;
+mod tasks;
+mod template;
+mod timings;
+mod version;
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use std::fs::{self, OpenOptions};
-use std::net::TcpListener;
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
 use std::process::{Child, Command, Stdio};
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tauri::{plugin::Builder as PluginBuilder, AppHandle, Manager, RunEvent, Runtime, State};
+
+use audit::AuditLog;
+use config::{LauncherConfig, ShutdownTimeouts};
+use error::BackendError;
+use events::{EventHistory, StopReason};
+use follow::LogFollower;
+use http::{BackendClient, ShutdownToken};
+use logs::{LogMode, LogStreams};
+use net::Transport;
+use paths::AppPaths;
+use schedule::{PendingRestart, ScheduledRestart};
+use secure_storage::{StorageHealth, StorageStatus};
+use session::SessionState;
+use settings::Settings;
+use tasks::{TaskInfo, TaskRegistry};
+
+/// Spawn attempts made when `spawn` fails with a transient error (e.g. EAGAIN).
+const SPAWN_ATTEMPTS: u32 = 3;
+
+/// Pause between spawn attempts.
+const SPAWN_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// How long `attach_backend` waits for the given port to accept a connection.
+const ATTACH_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Crash reports kept for `crash_history`, oldest dropped first.
+const CRASH_HISTORY_CAPACITY: usize = 20;
+
+/// How long a crash report waits for the reader threads to copy a crashed backend's last
+/// output out of its pipes.
+const CRASH_OUTPUT_DRAIN: Duration = Duration::from_millis(200);
+
+/// How long log reader threads get to drain the closed pipes after the backend exits.
+const LOG_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long `flush_logs` waits for the disk before giving up.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Times a start that lost the race for its port is retried on another one.
+const PORT_RACE_RETRIES: u32 = 2;
+
+/// How often the readiness watcher checks whether a backend that isn't listening yet has
+/// exited because its port was taken.
+const PORT_RACE_CHECK_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Most of a failed launch's log output searched for a port-in-use error.
+const STARTUP_OUTPUT_LIMIT: u64 = 64 * 1024;
+
+/// Holds the backend child process so we can kill it on app exit.
+#[derive(Default)]
+struct BackendProcess {
+    inner: Mutex<BackendState>,
+    /// Set once `shutdown_all`, `relaunch_app` or the exit event has begun shutdown:
+    /// repeated calls are no-ops and a backend spawned meanwhile is killed rather than stored.
+    shutting_down: AtomicBool,
+}
+
+#[derive(Default)]
+struct BackendState {
+    /// The current launch; `None` when no backend is running.
+    launch: Option<Launch>,
+    /// New instance started by `drain_backend` that takes over from `launch` once it is
+    /// ready. Kept here so shutdown can stop it too.
+    standby: Option<Launch>,
+    /// Exit code of the last backend that exited on its own (`None` if killed by a signal).
+    last_exit_code: Option<i32>,
+    /// Why the last backend stopped.
+    last_stop_reason: Option<StopReason>,
+    /// Port of the most recent launch, kept after it stops to detect port changes.
+    last_port: Option<u16>,
+    /// A setting that only applies at launch changed while the backend was running.
+    /// Cleared by the next launch.
+    config_dirty: bool,
+    /// Backends that exited unsuccessfully on their own this session, oldest first.
+    crashes: VecDeque<CrashReport>,
+    /// Why the last attempt to start a backend failed; cleared by the next successful start.
+    last_error: Option<ErrorReport>,
+    /// Backends started after the first one this session, including drains.
+    restart_count: u32,
+}
+
+/// A failed start, as returned by `last_error`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorReport {
+    message: String,
+    /// Milliseconds since the Unix epoch.
+    timestamp: u64,
+}
+
+/// A backend that crashed, as returned by `crash_history`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CrashReport {
+    run_id: String,
+    port: u16,
+    /// `None` when it was killed by a signal.
+    exit_code: Option<i32>,
+    /// Milliseconds since the Unix epoch at which the crash was noticed.
+    timestamp: u64,
+    /// The environment it was spawned with, secret-looking values redacted.
+    env: BTreeMap<String, String>,
+    /// Its last lines of output, oldest first; empty unless output was streamed.
+    last_output: Vec<logs::LogLine>,
+}
+
+/// What is left of reaping once the state lock is released. A crash's last output waits
+/// for the readers to drain its pipes, which commands shouldn't queue behind.
+enum Reaped {
+    Crash {
+        report: CrashReport,
+        streams: Option<LogStreams>,
+    },
+    /// Its status could no longer be read, so it is killed rather than left untracked.
+    Lost(Box<Launch>),
+}
+
+impl Reaped {
+    /// Finish up without the lock; returns the crash report to record.
+    fn finish(self) -> Option<CrashReport> {
+        match self {
+            Self::Crash {
+                mut report,
+                streams,
+            } => {
+                if let Some(streams) = streams {
+                    report.last_output = streams.recent_lines(CRASH_OUTPUT_DRAIN);
+                }
+                Some(report)
+            }
+            Self::Lost(mut launch) => {
+                launch.abandon();
+                None
+            }
+        }
+    }
+}
+
+/// A spawned (or attached) backend. Dropped as a whole when the backend stops or is reaped.
+struct Launch {
+    /// `None` for a backend started outside the app and attached with `attach_backend`;
+    /// the launcher never signals those.
+    child: Option<Child>,
+    /// Unique per `start_backend` call; passed to bun and included in lifecycle events so
+    /// frontend sessions can be matched with backend logs.
+    run_id: String,
+    host: IpAddr,
+    port: u16,
+    /// Reported by `/version`; cached until the next launch.
+    version: Option<String>,
+    /// Present when the backend was started with log streaming enabled.
+    log_streams: Option<LogStreams>,
+    /// Set once the backend accepts connections; until then the process may be up but not
+    /// listening yet.
+    ready: bool,
+    /// Started by `start_backend_verbose`.
+    verbose: bool,
+    /// Taken from the config at launch, so every way of stopping it uses the same timeline.
+    shutdown: ShutdownTimeouts,
+    /// Redacted environment it was spawned with (empty when attached), for crash reports.
+    env: BTreeMap<String, String>,
+    /// How it was started; `None` when attached.
+    invocation: Option<bun::Invocation>,
+    /// `backend.pid`, written once this becomes the current backend and removed when it
+    /// stops. `None` when attached.
+    pid_file: Option<PathBuf>,
+    /// Unix socket it also listens on (`TOSHIK_TRANSPORT=both`), removed when it stops.
+    socket: Option<PathBuf>,
+    /// Milliseconds since the Unix epoch at which it was spawned (or attached).
+    started_at: u64,
+    /// Spawned with `TOSHIK_DETACHED`: left running when the app exits.
+    detached: bool,
+    /// Required by the backend's `POST /shutdown`; `None` when attached.
+    shutdown_token: Option<ShutdownToken>,
+    /// Set when it runs in a container; `child` is then the runtime's client process.
+    container: Option<container::Container>,
+}
+
+impl BackendState {
+    /// Port of the backend this launcher spawned or attached to, if one is running.
+    fn running_port(&self) -> Option<u16> {
+        self.launch.as_ref().map(|launch| launch.port)
+    }
+
+    /// The running backend as the heartbeat records it, if any.
+    fn session_state(&self) -> Option<SessionState> {
+        let launch = self.launch.as_ref()?;
+        Some(SessionState {
+            run_id: launch.run_id.clone(),
+            host: launch.host,
+            port: launch.port,
+            pid: launch.child.as_ref().map(Child::id),
+            started_at: launch.started_at,
+            restart_count: self.restart_count,
+            detached: launch.detached,
+            heartbeat_at: now_millis(),
+        })
+    }
+
+    /// Socket address of the running backend, if any.
+    fn running_addr(&self) -> Option<SocketAddr> {
+        self.launch
+            .as_ref()
+            .map(|launch| SocketAddr::new(launch.host, launch.port))
+    }
+
+    /// If the stored child has exited, clear the launch and record its exit code. A crash
+    /// is returned to be finished with [`Reaped::finish`] and recorded with
+    /// [`Self::record_crash`] once the lock is dropped.
+    #[must_use]
+    fn reap_if_exited(&mut self) -> Option<Reaped> {
+        let launch = self.launch.as_mut()?;
+        let child = launch.child.as_mut()?;
+        match child.try_wait() {
+            Ok(None) => None,
+            Ok(Some(status)) => {
+                log::info!("Backend (run_id={}) exited with {status}", launch.run_id);
+                launch.remove_run_files();
+                self.last_exit_code = status.code();
+                self.last_stop_reason = Some(if status.success() {
+                    StopReason::Exited
+                } else {
+                    StopReason::Crashed
+                });
+                let launch = self.launch.take().filter(|_| !status.success())?;
+                Some(Reaped::Crash {
+                    report: CrashReport {
+                        run_id: launch.run_id,
+                        port: launch.port,
+                        exit_code: status.code(),
+                        timestamp: now_millis(),
+                        last_output: Vec::new(),
+                        env: launch.env,
+                    },
+                    streams: launch.log_streams,
+                })
+            }
+            Err(e) => {
+                log::warn!(
+                    "Failed to check backend process status (run_id={}), killing it: {e}",
+                    launch.run_id
+                );
+                self.last_stop_reason = Some(StopReason::Lost);
+                self.launch
+                    .take()
+                    .map(|launch| Reaped::Lost(Box::new(launch)))
+            }
+        }
+    }
+
+    fn record_crash(&mut self, report: CrashReport) {
+        if self.crashes.len() == CRASH_HISTORY_CAPACITY {
+            self.crashes.pop_front();
+        }
+        self.crashes.push_back(report);
+    }
+}
+
+impl BackendProcess {
+    /// Take the current launch out of the slot and terminate it; returns its run ID, or
+    /// `None` if nothing was running.
+    fn terminate(&self, reason: StopReason) -> Option<String> {
+        let mut launch = {
+            let mut guard = self.lock_reaped().ok()?;
+            let launch = guard.launch.take()?;
+            guard.last_stop_reason = Some(reason);
+            launch
+        };
+        launch.terminate();
+        Some(launch.run_id)
+    }
+
+    /// Put a freshly spawned `launch` in the slot as early as possible, so the exit handler
+    /// can always find it. If shutdown began meanwhile (or the state is unusable) the
+    /// process is terminated instead of leaking. Returns the previous launch's port.
+    fn store_launch(&self, mut launch: Launch) -> Result<Option<u16>, String> {
+        let mut guard = match self.lock_reaped() {
+            Ok(guard) => guard,
+            Err(e) => {
+                launch.terminate();
+                return Err(e);
+            }
+        };
+        if self.shutting_down.load(Ordering::SeqCst) {
+            drop(guard);
+            launch.terminate();
+            return Err("Application is shutting down".into());
+        }
+        let port = launch.port;
+        launch.write_pid_file();
+        guard.launch = Some(launch);
+        guard.config_dirty = false;
+        guard.last_error = None;
+        if guard.last_port.is_some() {
+            guard.restart_count += 1;
+        }
+        Ok(guard.last_port.replace(port))
+    }
+
+    /// Remember `message` as the reason the backend is not running, for `last_error`.
+    fn record_error(&self, message: &str) {
+        if let Ok(mut guard) = self.inner.lock() {
+            guard.last_error = Some(ErrorReport {
+                message: message.to_string(),
+                timestamp: now_millis(),
+            });
+        }
+    }
+
+    /// Hold the instance a drain is waiting on. Terminated instead if shutdown began or
+    /// another drain already holds the slot.
+    fn store_standby(&self, mut launch: Launch) -> Result<(), String> {
+        let mut guard = match self.lock_reaped() {
+            Ok(guard) => guard,
+            Err(e) => {
+                launch.terminate();
+                return Err(e);
+            }
+        };
+        let error = if self.shutting_down.load(Ordering::SeqCst) {
+            "Application is shutting down"
+        } else if guard.standby.is_some() {
+            "A drain is already in progress"
+        } else {
+            guard.standby = Some(launch);
+            return Ok(());
+        };
+        drop(guard);
+        launch.terminate();
+        Err(error.into())
+    }
+
+    /// Swap the standby in for the launch `replaces`, returning that launch (still running,
+    /// for the caller to stop outside the lock) and the previous port. If the backend
+    /// changed in the meantime, or shutdown began, the standby is terminated instead.
+    fn promote_standby(&self, replaces: &str) -> Result<(Launch, Option<u16>), String> {
+        let mut guard = self.lock_reaped()?;
+        let mut standby = guard.standby.take().ok_or("Drain was cancelled")?;
+        let error = if self.shutting_down.load(Ordering::SeqCst) {
+            "Application is shutting down"
+        } else if guard.launch.as_ref().map(|launch| launch.run_id.as_str()) != Some(replaces) {
+            "Backend was stopped or replaced during the drain"
+        } else {
+            let port = standby.port;
+            standby.write_pid_file();
+            let old = guard.launch.replace(standby).expect("checked above");
+            guard.last_stop_reason = Some(StopReason::Restart);
+            guard.config_dirty = false;
+            guard.restart_count += 1;
+            return Ok((old, guard.last_port.replace(port)));
+        };
+        drop(guard);
+        standby.terminate();
+        Err(error.into())
+    }
+
+    /// Terminate the drain standby, if any.
+    fn discard_standby(&self) {
+        let standby = self
+            .lock_reaped()
+            .ok()
+            .and_then(|mut guard| guard.standby.take());
+        if let Some(mut standby) = standby {
+            standby.terminate();
+        }
+    }
+
+    /// Run ID of the current launch, if any (for audit entries).
+    fn current_run_id(&self) -> Option<String> {
+        let guard = self.lock_reaped().ok()?;
+        guard.launch.as_ref().map(|launch| launch.run_id.clone())
+    }
+
+    /// Apply `f` to the current launch if it is still `run_id`; returns whether it was.
+    fn update_launch(&self, run_id: &str, f: impl FnOnce(&mut Launch)) -> bool {
+        let Ok(mut guard) = self.lock_reaped() else {
+            return false;
+        };
+        match guard.launch.as_mut() {
+            Some(launch) if launch.run_id == run_id => {
+                f(launch);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// When launch `run_id` was spawned, and whether it is the first of this app session
+    /// (a standby never is).
+    fn launch_start(&self, run_id: &str) -> Option<(u64, bool)> {
+        let guard = self.lock_reaped().ok()?;
+        match (guard.launch.as_ref(), guard.standby.as_ref()) {
+            (Some(launch), _) if launch.run_id == run_id => {
+                Some((launch.started_at, guard.restart_count == 0))
+            }
+            (_, Some(standby)) if standby.run_id == run_id => Some((standby.started_at, false)),
+            _ => None,
+        }
+    }
+
+    /// Where the port from launch `run_id`'s ready line turns up, whether it is current or
+    /// the standby of a drain.
+    fn announced_port(&self, run_id: &str) -> Option<Arc<ready::AnnouncedPort>> {
+        let guard = self.lock_reaped().ok()?;
+        let launch = [guard.launch.as_ref(), guard.standby.as_ref()]
+            .into_iter()
+            .flatten()
+            .find(|launch| launch.run_id == run_id)?;
+        launch.log_streams.as_ref()?.announced_port()
+    }
+
+    /// The log files the current launch and a drain standby are streamed into.
+    fn log_files(&self) -> Vec<Arc<Mutex<fs::File>>> {
+        let Ok(guard) = self.lock_reaped() else {
+            return Vec::new();
+        };
+        [guard.launch.as_ref(), guard.standby.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|launch| launch.log_streams.as_ref())
+            .map(LogStreams::log_file)
+            .collect()
+    }
+
+    /// Store `port`, which launch `run_id` reported listening on, in place of the one it
+    /// was spawned with. Returns the old port if the current launch moved.
+    fn correct_port(&self, run_id: &str, port: u16) -> Option<u16> {
+        let mut guard = self.lock_reaped().ok()?;
+        let guard = &mut *guard;
+        let current = guard.launch.as_ref().is_some_and(|l| l.run_id == run_id);
+        let launch = [guard.launch.as_mut(), guard.standby.as_mut()]
+            .into_iter()
+            .flatten()
+            .find(|launch| launch.run_id == run_id)?;
+        let stored = std::mem::replace(&mut launch.port, port);
+        if stored == port || !current {
+            return None;
+        }
+        guard.last_port = Some(port);
+        Some(stored)
+    }
+
+    /// Record that a launch-time setting changed; returns whether a backend is running (and
+    /// so needs a restart to pick it up).
+    fn mark_config_dirty(&self) -> Result<bool, String> {
+        let mut guard = self.lock_reaped()?;
+        let running = guard.launch.is_some();
+        guard.config_dirty |= running;
+        Ok(running)
+    }
+
+    /// Fail if the current backend was attached rather than spawned by the launcher.
+    fn ensure_not_attached(&self) -> Result<(), String> {
+        match self.lock_reaped()?.launch {
+            Some(Launch { child: None, .. }) => {
+                Err("Backend was attached, not started by the app; not stopping it".into())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Lock the state after reaping a backend that exited on its own, so commands never act
+    /// on a dead child. Every command goes through this.
+    /// A crash is finished with the lock released, then recorded before returning.
+    fn lock_reaped(&self) -> Result<MutexGuard<'_, BackendState>, String> {
+        let mut guard = self.inner.lock().map_err(|e| e.to_string())?;
+        let Some(reaped) = guard.reap_if_exited() else {
+            return Ok(guard);
+        };
+        drop(guard);
+        let report = reaped.finish();
+        let mut guard = self.inner.lock().map_err(|e| e.to_string())?;
+        if let Some(report) = report {
+            guard.record_crash(report);
+        }
+        Ok(guard)
+    }
+}
+
+/// Plugin that stops the backend process on app exit (Tauri 2 has no Builder::on_event, only in plugins).
+fn backend_cleanup_plugin<R: Runtime>() -> tauri::plugin::TauriPlugin<R> {
+    PluginBuilder::new("backend-cleanup")
+        .on_event(|app, event| {
+            if let RunEvent::Exit = event {
+                if let Some(state) = app.try_state::<BackendProcess>() {
+                    state.shutting_down.store(true, Ordering::SeqCst);
+                    release_detached(app, &state);
+                    state.discard_standby();
+                    stop_backend_process(app, &state, StopReason::AppExit);
+                }
+                // A clean exit leaves nothing to recover, unless a detached backend lives on.
+                if let Some(paths) = app.try_state::<AppPaths>() {
+                    let detached = session::read(&paths.session_file)
+                        .is_some_and(|previous| previous.detached);
+                    if !detached {
+                        session::remove(&paths.session_file);
+                    }
+                }
+            }
+        })
+        .build()
+}
+
+/// Ask the child to exit (SIGTERM on Unix), kill it if it is still alive after
+/// `term_grace`, then wait up to `kill_reap` for it to be reaped. A process that outlives
+/// that (e.g. stuck in uninterruptible I/O) is abandoned rather than blocking the caller.
+fn terminate_gracefully(child: &mut Child, timeouts: ShutdownTimeouts) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) on the pid of a child we spawned and have not reaped yet.
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+        if wait_for_exit(child, timeouts.term_grace) {
+            return;
+        }
+        log::warn!(
+            "Backend did not exit within {:?} of SIGTERM, killing it",
+            timeouts.term_grace
+        );
+    }
+    let _ = child.kill();
+    if !wait_for_exit(child, timeouts.kill_reap) {
+        log::warn!(
+            "Backend (pid={}) was not reaped within {:?} of SIGKILL, abandoning it",
+            child.id(),
+            timeouts.kill_reap
+        );
+    }
+}
+
+/// Poll until `child` has exited; `false` if it is still running after `timeout`.
+fn wait_for_exit(child: &mut Child, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        match child.try_wait() {
+            Ok(Some(_)) => return true,
+            Ok(None) if Instant::now() < deadline => std::thread::sleep(Duration::from_millis(50)),
+            Ok(None) | Err(_) => return false,
+        }
+    }
+}
+
+impl Launch {
+    /// Terminate the process (attached backends are only forgotten).
+    ///
+    /// Log readers are told to stop first and joined once the process is gone, so the final
+    /// lines the backend printed are flushed to `backend.log` before we return.
+    fn terminate(&mut self) {
+        if let Some(ref streams) = self.log_streams {
+            streams.request_stop();
+        }
+        match self.child {
+            Some(ref mut child) => {
+                log::info!(
+                    "Stopping backend process (pid={}, run_id={})",
+                    child.id(),
+                    self.run_id
+                );
+                let addr = SocketAddr::new(self.host, self.port);
+                let asked = self.shutdown_token.as_ref().is_some_and(|token| {
+                    token
+                        .request_shutdown(addr)
+                        .inspect_err(|e| log::debug!("Graceful shutdown request failed: {e}"))
+                        .is_ok()
+                });
+                if asked && wait_for_exit(child, self.shutdown.term_grace) {
+                    log::info!("Backend exited after POST /shutdown");
+                } else if self.container.as_ref().is_some_and(|container| {
+                    container.stop(self.shutdown.term_grace)
+                        && wait_for_exit(child, self.shutdown.kill_reap)
+                }) {
+                    log::info!("Backend container stopped");
+                } else {
+                    terminate_gracefully(child, self.shutdown);
+                }
+            }
+            None => log::info!("Detaching from external backend on port {}", self.port),
+        }
+        if let Some(streams) = self.log_streams.take() {
+            streams.join(LOG_DRAIN_TIMEOUT);
+        }
+        self.remove_run_files();
+    }
+
+    /// Kill a child that can no longer be waited on normally, then clean up as
+    /// [`Self::terminate`] does. Everything is best-effort.
+    fn abandon(&mut self) {
+        if let Some(ref streams) = self.log_streams {
+            streams.request_stop();
+        }
+        if let Some(ref mut child) = self.child {
+            if let Err(e) = child.kill() {
+                log::warn!("Failed to kill backend (pid={}): {e}", child.id());
+            }
+            wait_for_exit(child, self.shutdown.kill_reap);
+        }
+        if let Some(ref container) = self.container {
+            container.stop(Duration::ZERO);
+        }
+        if let Some(streams) = self.log_streams.take() {
+            streams.join(LOG_DRAIN_TIMEOUT);
+        }
+        self.remove_run_files();
+    }
+
+    fn write_pid_file(&self) {
+        let (Some(path), Some(child)) = (&self.pid_file, &self.child) else {
+            return;
+        };
+        if let Err(e) = pidfile::write(path, child.id(), self.port) {
+            log::warn!("Failed to write {}: {e}", path.display());
+        }
+    }
+
+    /// Remove `backend.pid` (unless a newer backend took it over) and the socket file.
+    fn remove_run_files(&self) {
+        if let (Some(path), Some(child)) = (&self.pid_file, &self.child) {
+            pidfile::remove_if_owned(path, child.id());
+        }
+        if let Some(ref socket) = self.socket {
+            match fs::remove_file(socket) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    log::warn!("Failed to remove {}: {e}", socket.display());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// On shutdown, forget a detached backend instead of stopping it, recording it in
+/// `session.json` for the next launch. `backend.pid` is left in place as well.
+fn release_detached<R: Runtime>(app: &AppHandle<R>, state: &BackendProcess) {
+    let released = state.lock_reaped().ok().and_then(|mut guard| {
+        if !guard.launch.as_ref().is_some_and(|launch| launch.detached) {
+            return None;
+        }
+        let current = guard.session_state();
+        // Dropping the `Child` neither kills nor waits for it.
+        guard.launch = None;
+        current
+    });
+    let Some(current) = released else {
+        return;
+    };
+    log::info!(
+        "Leaving detached backend running (pid={:?}, run_id={}, port {})",
+        current.pid,
+        current.run_id,
+        current.port
+    );
+    if let Some(paths) = app.try_state::<AppPaths>() {
+        if let Err(e) = session::write(&paths.session_file, &current) {
+            log::warn!("Failed to write {}: {e}", paths.session_file.display());
+        }
+    }
+}
+
+/// Stop the managed backend, if any, clear the slot and announce it.
+fn stop_backend_process<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    reason: StopReason,
+) {
+    if let Some(run_id) = state.terminate(reason) {
+        app.state::<TaskRegistry>().cancel_run(&run_id);
+        events::emit_as(
+            app,
+            events::STOPPED,
+            reason.severity(),
+            events::StoppedPayload {
+                run_id: Some(run_id),
+                reason,
+            },
+        );
+    }
+}
+
+/// [`stop_backend_process`] on a blocking thread, since a graceful stop waits out the grace
+/// period and the reaping.
+async fn stop_blocking<R: Runtime>(app: &AppHandle<R>, reason: StopReason) -> Result<(), String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        stop_backend_process(&app, &app.state::<BackendProcess>(), reason)
+    })
+    .await
+    .map_err(|e| format!("Stop task failed: {e}"))
+}
+
+/// The `vault_secrets` values, read from the webview's vault. A vault that can't be read
+/// only costs the backend those variables, so it is logged rather than failing the start.
+fn vault_secrets<R: Runtime>(
+    app: &AppHandle<R>,
+    config: &LauncherConfig,
+) -> BTreeMap<String, String> {
+    if config.vault_secrets.is_empty() {
+        return BTreeMap::new();
+    }
+    let paths = app.state::<AppPaths>();
+    match secure_storage::read_secrets(&paths.vault_file, &paths.salt_file, &config.vault_secrets) {
+        Ok(secrets) => {
+            log::info!(
+                "Passing {} secrets from the vault to the backend",
+                secrets.len()
+            );
+            secrets
+        }
+        Err(e) => {
+            log::warn!("Not passing vault secrets to the backend: {e}");
+            BTreeMap::new()
+        }
+    }
+}
+
+/// Ports scanned for the backend unless `TOSHIK_FORCE_PORT` or `TOSHIK_EPHEMERAL_PORT` is set.
+const PORT_RANGE: RangeInclusive<u16> = 3001..=3010;
+
+/// Scan `PORT_RANGE` and return the first available port.
+fn find_available_port(host: IpAddr) -> Option<u16> {
+    let mut ports = PORT_RANGE;
+    ports.find(|&port| {
+        net::validate_port(port.into(), false).is_ok() && TcpListener::bind((host, port)).is_ok()
+    })
+}
+
+/// Free port found during setup with `TOSHIK_WARM_PORTS`, used (once) by the first start.
+#[derive(Default)]
+struct WarmPort(Mutex<Option<u16>>);
+
+/// Find the first free port in the background and remember it in [`WarmPort`].
+fn warm_port<R: Runtime>(app: &AppHandle<R>, host: IpAddr) {
+    let tasks = app.state::<TaskRegistry>();
+    let app = app.clone();
+    let spawned = tasks.spawn("port-warmup", None, move |_| {
+        let Some(port) = find_available_port(host) else {
+            return;
+        };
+        log::debug!("Port {port} is free, keeping it for the first start");
+        if let Ok(mut warm) = app.state::<WarmPort>().0.lock() {
+            *warm = Some(port);
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start port warmup: {e}");
+    }
+}
+
+/// The port remembered by [`warm_port`], if there is one and it is still free.
+fn take_warm_port<R: Runtime>(app: &AppHandle<R>, host: IpAddr) -> Option<u16> {
+    let port = app.try_state::<WarmPort>()?.0.lock().ok()?.take()?;
+    if TcpListener::bind((host, port)).is_ok() {
+        return Some(port);
+    }
+    log::info!("Warmed-up port {port} was taken meanwhile, scanning again");
+    None
+}
+
+/// Ask the OS for a free port by binding port 0. The listener is dropped before bun binds
+/// it, so another process could in principle grab it first.
+fn ephemeral_port(host: IpAddr) -> Option<u16> {
+    let listener = TcpListener::bind((host, 0)).ok()?;
+    Some(listener.local_addr().ok()?.port())
+}
+
+/// Tauri command: find a free port, spawn `bun run packages/backend/src/index.ts --port <PORT>`,
+/// redirect stdout/stderr to `backend.log`, and return the chosen port.
+///
+/// `env` is added to the backend's environment for this launch only. `workers` is passed in
+/// `TOSHIK_WORKERS_ENV` (`BACKEND_WORKERS` by default) and overrides `env`.
+#[tauri::command]
+async fn start_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    audit: State<'_, AuditLog>,
+    env: Option<HashMap<String, String>>,
+    workers: Option<usize>,
+) -> Result<u16, String> {
+    // Only the names of the overrides are audited, never their values.
+    let mut env_keys: Vec<String> = env.iter().flat_map(|env| env.keys().cloned()).collect();
+    env_keys.sort();
+    let args = json!({ "envKeys": env_keys, "workers": workers });
+
+    let prepared = config
+        .lock()
+        .map_err(|e| e.to_string())
+        .map(|config| config.clone())
+        .and_then(|config| Ok((with_workers(&config, workers, env)?, config)));
+    let result = match prepared {
+        Ok((env, config)) => launch_blocking(&app, config, env).await,
+        Err(e) => Err(e),
+    };
+    audit.record(
+        "start_backend",
+        args,
+        &result,
+        state.current_run_id().as_deref(),
+    );
+    result
+}
+
+/// Add the worker count, given or (with `TOSHIK_AUTO_WORKERS`) one per logical CPU, to
+/// the per-launch `env`.
+fn with_workers(
+    config: &LauncherConfig,
+    workers: Option<usize>,
+    env: Option<HashMap<String, String>>,
+) -> Result<Option<HashMap<String, String>>, String> {
+    let workers = match workers {
+        Some(0) => return Err("workers must be at least 1".into()),
+        Some(workers) => workers,
+        None if config.auto_workers => {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        }
+        None => return Ok(env),
+    };
+    log::info!("Starting the backend with {workers} workers");
+    let mut env = env.unwrap_or_default();
+    env.insert(config.workers_env.clone(), workers.to_string());
+    if config.uv_threadpool {
+        env.insert("UV_THREADPOOL_SIZE".into(), workers.to_string());
+    }
+    Ok(Some(env))
+}
+
+/// Result of `start_backend_verbose`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct VerboseStart {
+    port: u16,
+    run_id: String,
+    inspector_url: String,
+}
+
+/// Tauri command: start the backend with every debugging aid on: bun's inspector, trace
+/// logging (`LOG_LEVEL=trace`), stdout kept and streamed as `backend://log-batch` events. Only
+/// this launch is affected; the next `start_backend` uses the configured settings again.
+#[tauri::command]
+async fn start_backend_verbose(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    audit: State<'_, AuditLog>,
+) -> Result<VerboseStart, String> {
+    let result = start_verbose(&app, &state, &config).await;
+    audit.record(
+        "start_backend_verbose",
+        Value::Null,
+        &result,
+        state.current_run_id().as_deref(),
+    );
+    result
+}
+
+async fn start_verbose<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &Mutex<LauncherConfig>,
+) -> Result<VerboseStart, String> {
+    let mut config = config.lock().map_err(|e| e.to_string())?.clone();
+    let inspector = bun::Inspector {
+        addr: SocketAddr::new(
+            config.host,
+            ephemeral_port(config.host).ok_or("Failed to get an inspector port")?,
+        ),
+        path: uuid::Uuid::new_v4().simple().to_string(),
+    };
+    config.quiet = false;
+    config.stream_logs = true;
+    config.inspector = Some(inspector.clone());
+    let env = HashMap::from([("LOG_LEVEL".to_string(), "trace".to_string())]);
+    let port = launch_blocking(app, config, Some(env)).await?;
+    let run_id = state
+        .current_run_id()
+        .ok_or("Backend exited right after start")?;
+
+    let warning = "Verbose mode is on: tracing and the inspector slow the backend down";
+    log::warn!("{warning} (run_id={run_id})");
+    events::emit(
+        app,
+        events::VERBOSE_MODE,
+        events::VerboseModePayload {
+            run_id: run_id.clone(),
+            inspector_url: inspector.url(),
+            warning: warning.into(),
+        },
+    );
+    Ok(VerboseStart {
+        port,
+        run_id,
+        inspector_url: inspector.url(),
+    })
+}
+
+/// Spawn the backend unless one is already running; shared by `start_backend` and the
+/// launcher-initiated start in `frontend_ready`.
+fn launch_backend<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &LauncherConfig,
+    env: Option<HashMap<String, String>>,
+) -> Result<u16, String> {
+    launch_with_retries(app, state, config, env, PORT_RACE_RETRIES)
+}
+
+/// [`launch_backend`] on a blocking thread, off the main thread and the async workers:
+/// reading vault secrets, hashing the executable and the bun checks all take a while.
+async fn launch_blocking<R: Runtime>(
+    app: &AppHandle<R>,
+    config: LauncherConfig,
+    env: Option<HashMap<String, String>>,
+) -> Result<u16, String> {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        launch_backend(&app, &app.state::<BackendProcess>(), &config, env)
+    })
+    .await
+    .map_err(|e| format!("Start task failed: {e}"))?
+}
+
+/// A launch's way back to its startup output, and what relaunching it takes, should it
+/// lose the race for its port.
+struct PortRace {
+    log_path: PathBuf,
+    /// Length of the log before the launch, where its own output starts.
+    log_offset: u64,
+    env: Option<HashMap<String, String>>,
+    retries_left: u32,
+}
+
+/// [`launch_backend`], relaunching on another port up to `retries_left` times if bun
+/// reports the port taken during startup.
+fn launch_with_retries<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &LauncherConfig,
+    env: Option<HashMap<String, String>>,
+    retries_left: u32,
+) -> Result<u16, String> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err("Application is shutting down".into());
+    }
+
+    // If backend is already running, don't spawn another one.
+    if state.lock_reaped()?.launch.is_some() {
+        return Err("Backend is already running".into());
+    }
+
+    prune_logs(
+        &app.state::<TaskRegistry>(),
+        config.log_path(&app.state::<AppPaths>()),
+        config.max_log_dir,
+    );
+    let log_path = config.log_path(&app.state::<AppPaths>());
+    let log_offset = match config.log_mode {
+        LogMode::Append => fs::metadata(&log_path).map_or(0, |meta| meta.len()),
+        LogMode::Truncate => 0,
+    };
+    let (launch, hook_log) =
+        spawn_backend(app, config, env.clone()).inspect_err(|e| state.record_error(e))?;
+    let run_id = launch.run_id.clone();
+    let port = launch.port;
+    let socket = launch.socket.clone();
+    record_launch(app, state, launch).inspect_err(|e| state.record_error(e))?;
+    let race = PortRace {
+        log_path,
+        log_offset,
+        env,
+        retries_left,
+    };
+    watch_readiness(app, run_id, port, socket, config, hook_log, Some(race));
+    Ok(port)
+}
+
+/// Milliseconds since the Unix epoch, for report timestamps.
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Enforce the log directory size cap in the background, so starting isn't delayed by it.
+fn prune_logs(tasks: &TaskRegistry, active_log: PathBuf, cap: u64) {
+    let spawned = tasks.spawn("log-prune", None, move |_| {
+        if let Err(e) = logs::prune(&active_log, cap) {
+            log::warn!("Failed to prune old logs: {e}");
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start log pruning: {e}");
+    }
+}
+
+/// Pick a port and spawn bun on it, without storing the result anywhere. Returns the new
+/// launch and the path for its post-start hook output.
+fn spawn_backend<R: Runtime>(
+    app: &AppHandle<R>,
+    config: &LauncherConfig,
+    env: Option<HashMap<String, String>>,
+) -> Result<(Launch, PathBuf), String> {
+    let runtime = container_runtime(config);
+    let invocation = config.bun_invocation;
+    invocation.validate(config.backend_entry.as_deref(), config.inspector.is_some())?;
+    if let Some(ref entry) = config.backend_entry {
+        integrity::check_entry_allowed(entry, integrity::ALLOWED_ENTRIES, !cfg!(debug_assertions))?;
+    }
+    // Older bun releases fail with cryptic flag-parsing errors, so check up front.
+    let bun = match invocation.subcommand() {
+        Some(subcommand) if runtime.is_none() => {
+            let bun = bun_executable(config)?;
+            let version = bun::ensure_compatible(&bun, config.min_bun_version)?;
+            Some((bun, subcommand, version))
+        }
+        _ => None,
+    };
+
+    let port = if let Some(port) = config.force_port {
+        TcpListener::bind((config.host, port))
+            .map_err(|e| format!("TOSHIK_FORCE_PORT={port} is not available: {e}"))?;
+        port
+    } else if config.ephemeral_port {
+        ephemeral_port(config.host).ok_or("Failed to get a port from the OS")?
+    } else if let Some(port) = take_warm_port(app, config.host) {
+        port
+    } else {
+        find_available_port(config.host).ok_or_else(|| {
+            format!(
+                "No available port in range {}-{}",
+                PORT_RANGE.start(),
+                PORT_RANGE.end()
+            )
+        })?
+    };
+
+    let log_path = config.log_path(&app.state::<AppPaths>());
+    let mut log_file = open_log_file(&log_path, config.log_mode)?;
+
+    // A configured entry doesn't need the workspace, only its `.env` if it is there.
+    let resolved = resolve::resolve_backend_script(config.script_path_mode);
+    let env_file = resolved
+        .as_ref()
+        .ok()
+        .and_then(|resolved| resolved.env_file.clone());
+    let backend_script = match (&runtime, &config.backend_entry) {
+        (Some((_, image)), _) => PathBuf::from(image),
+        (None, Some(entry)) => entry.clone(),
+        (None, None) => resolved?.script,
+    };
+    if let (None, bun::Invocation::Direct, Some(expected)) =
+        (&runtime, invocation, integrity::EXPECTED_SHA256)
+    {
+        if config.verify_integrity {
+            integrity::verify(&backend_script, expected)?;
+        } else {
+            log::warn!("Skipping integrity check of {}", backend_script.display());
+        }
+    }
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+
+    match runtime {
+        Some((ref runtime, ref image)) => log::info!(
+            "Starting backend (run_id={run_id}) on port {port} in container {image} ({}), log: {}",
+            runtime.display(),
+            log_path.display()
+        ),
+        None => log::info!(
+            "Starting backend (run_id={run_id}) on port {port}, {invocation}: {}, log: {}",
+            backend_script.display(),
+            log_path.display()
+        ),
+    }
+    if let Some(ref banner) = config.log_banner {
+        if let Err(e) = banner.write(&mut log_file, config.backend_log_format, &run_id, port) {
+            log::warn!("Failed to write to {}: {e}", log_path.display());
+        }
+    }
+    if let Err(e) = logs::write_launcher_line(
+        &mut log_file,
+        config.backend_log_format,
+        &run_id,
+        &format!("starting backend on port {port}"),
+    ) {
+        log::warn!("Failed to write to {}: {e}", log_path.display());
+    }
+
+    // Named per run, so a drain standby doesn't collide with the backend it replaces.
+    let socket = match config.transport {
+        Transport::Tcp => None,
+        Transport::Both if runtime.is_some() => None,
+        Transport::Both => {
+            let dir = &app.state::<AppPaths>().socket_dir;
+            fs::create_dir_all(dir)
+                .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+            let socket = dir.join(format!("{}.sock", &run_id[..8]));
+            let _ = fs::remove_file(&socket);
+            Some(socket)
+        }
+    };
+
+    let shutdown_token = ShutdownToken::generate(&config.shutdown_token_header);
+    let secrets = vault_secrets(app, config);
+    let spec = command::build_command_spec(
+        config,
+        &command::LaunchInputs {
+            bun: bun
+                .as_ref()
+                .map(|(bun, subcommand, version)| (bun.as_path(), *subcommand, *version)),
+            script: &backend_script,
+            env_file: env_file.as_deref(),
+            port,
+            run_id: &run_id,
+            socket: socket.as_deref(),
+            shutdown_token: &shutdown_token,
+            secrets: &secrets,
+            env: env.as_ref(),
+            parent_env: &|key| std::env::var_os(key),
+        },
+    );
+    let container =
+        runtime.map(|(runtime, image)| (container::Container::new(runtime, &run_id), image));
+    let mut cmd = match (&container, login_shell(config)) {
+        (Some((container, image)), _) => container.run_command(
+            &config.container,
+            image,
+            config.host,
+            port,
+            &run_id,
+            &spec.env,
+        ),
+        (None, Some(shell)) => spec.login_shell_command(&shell),
+        (None, None) => spec.command(),
+    };
+
+    if config.detached {
+        cmd.stdin(Stdio::null());
+        detach(&mut cmd);
+    }
+    // Reader threads end with the app, and a detached backend writing to their pipes would
+    // then fail, so it always writes to the file directly.
+    let stream_logs = config.stream_logs && !config.detached;
+    if config.stream_logs && config.detached {
+        log::info!("Backend is detached, writing its output to the log file without streaming");
+    }
+
+    let clone_log = || {
+        log_file
+            .try_clone()
+            .map_err(|e| format!("Failed to clone log file handle: {e}"))
+    };
+    if stream_logs {
+        cmd.stderr(Stdio::piped());
+    } else {
+        cmd.stderr(Stdio::from(clone_log()?));
+    }
+    if config.quiet {
+        cmd.stdout(Stdio::null());
+    } else if stream_logs {
+        cmd.stdout(Stdio::piped());
+    } else {
+        cmd.stdout(Stdio::from(clone_log()?));
+    }
+
+    let mut spawn_env = backend_env::snapshot(&cmd, !config.isolated_env);
+    for name in secrets.keys() {
+        if let Some(value) = spawn_env.get_mut(name) {
+            *value = backend_env::REDACTED.to_string();
+        }
+    }
+    let mut child = spawn_with_retry(&mut cmd).map_err(BackendError::SpawnFailed)?;
+
+    let mut pipes: Vec<(&'static str, Box<dyn std::io::Read + Send>)> = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        pipes.push(("stdout", Box::new(stdout)));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        pipes.push(("stderr", Box::new(stderr)));
+    }
+    let log_streams = if pipes.is_empty() {
+        None
+    } else {
+        match LogStreams::spawn(
+            app,
+            &run_id,
+            &log_file,
+            config.line_options(),
+            config.stream_taps(),
+            pipes,
+        ) {
+            Ok(streams) => Some(streams),
+            Err(e) => {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(format!("Failed to start log reader threads: {e}"));
+            }
+        }
+    };
+
+    let launch = Launch {
+        child: Some(child),
+        run_id,
+        host: config.host,
+        port,
+        version: None,
+        log_streams,
+        ready: false,
+        verbose: config.inspector.is_some(),
+        shutdown: config.shutdown,
+        env: spawn_env,
+        invocation: Some(invocation),
+        pid_file: Some(app.state::<AppPaths>().pid_file.clone()),
+        socket,
+        started_at: now_millis(),
+        detached: config.detached,
+        shutdown_token: Some(shutdown_token),
+        container: container.map(|(container, _)| container),
+    };
+    Ok((launch, log_path.with_file_name("hook.log")))
+}
+
+/// The container runtime and image to start the backend with, if an image is configured and
+/// the runtime is installed; bun is used otherwise.
+fn container_runtime(config: &LauncherConfig) -> Option<(PathBuf, String)> {
+    let image = config.container.image.clone()?;
+    let Some(runtime) = container::find_runtime(&config.container.runtime) else {
+        log::warn!(
+            "Container runtime {} not found, starting the backend with bun instead of {image}",
+            config.container.runtime
+        );
+        return None;
+    };
+    if config.transport == Transport::Both {
+        log::warn!("A containerized backend only listens on TCP; ignoring TOSHIK_TRANSPORT");
+    }
+    Some((runtime, image))
+}
+
+/// The shell to start the backend through with `TOSHIK_LOGIN_SHELL`, if it applies here.
+fn login_shell(config: &LauncherConfig) -> Option<PathBuf> {
+    if !config.use_login_shell {
+        return None;
+    }
+    let shell = shell::login_shell();
+    if shell.is_none() {
+        log::warn!("TOSHIK_LOGIN_SHELL needs $SHELL on Unix, starting the backend directly");
+    }
+    shell
+}
+
+/// [`bun::executable`], except that an unconfigured bun is looked up in the login shell
+/// with `TOSHIK_LOGIN_SHELL`, falling back to `PATH` if it isn't found there.
+fn bun_executable(config: &LauncherConfig) -> Result<PathBuf, BackendError> {
+    if config.bun_path.is_none() {
+        if let Some(shell) = login_shell(config) {
+            match shell::which_cached(&shell, "bun") {
+                Some(bun) => return Ok(bun),
+                None => log::warn!("bun is not on the PATH of {}", shell.display()),
+            }
+        }
+    }
+    bun::executable(config.bun_path.as_deref())
+}
+
+/// Start `cmd` in a new session (a new process group without a console on Windows), so it
+/// doesn't get the signals meant for the app's terminal or process group.
+fn detach(cmd: &mut Command) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        // SAFETY: setsid(2) is async-signal-safe and touches no memory of the parent.
+        unsafe {
+            cmd.pre_exec(|| {
+                if libc::setsid() == -1 {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        const DETACHED_PROCESS: u32 = 0x0000_0008;
+        const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+        cmd.creation_flags(DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP);
+    }
+}
+
+/// In the background, wait for the backend to accept connections, send the warmup request,
+/// announce `ready` and run the post-start hook. A fatal hook failure stops the backend, if
+/// it is still this launch.
+///
+/// With `race`, a backend that crashes before listening and whose output says its port is
+/// in use is reported as `PortRaceLost` and, retries permitting, launched again.
+fn watch_readiness<R: Runtime>(
+    app: &AppHandle<R>,
+    run_id: String,
+    port: u16,
+    socket: Option<PathBuf>,
+    config: &LauncherConfig,
+    hook_log: PathBuf,
+    race: Option<PortRace>,
+) {
+    let relaunch_config = config.clone();
+    let hook = config.post_start_hook.clone();
+    let hook_fatal = config.post_start_hook_fatal;
+    let warmup_path = config.warmup_path.clone();
+    let warmup_required = config.warmup_required;
+    let require_all = config.ready_require_all;
+    let ready_endpoints = config.ready_endpoints.clone();
+    let health_check = config.health_check.clone();
+    let addr = SocketAddr::new(config.host, port);
+    let tasks = app.state::<TaskRegistry>();
+    let app = app.clone();
+    let task_run_id = run_id.clone();
+    let spawned = tasks.spawn("backend-ready", Some(&task_run_id), move |cancel| {
+        let fail_at = |event: &str, error: String, endpoint: Option<String>| {
+            log::warn!("Backend (run_id={run_id}): {error}");
+            events::emit(
+                &app,
+                event,
+                events::RunErrorPayload {
+                    run_id: run_id.clone(),
+                    error,
+                    endpoint,
+                },
+            );
+        };
+        let fail = |event: &str, error: String| fail_at(event, error, None);
+        let deadline = Instant::now() + ready::READY_TIMEOUT;
+        let state = app.state::<BackendProcess>();
+        let start = state.launch_start(&run_id);
+        let since_spawn = || start.map(|(started_at, _)| now_millis().saturating_sub(started_at));
+        let (port, addr) = match state.announced_port(&run_id) {
+            Some(announced) => match announced.wait(ready::READY_LINE_TIMEOUT) {
+                Some(reported) => {
+                    if let Some(stored) = state.correct_port(&run_id, reported) {
+                        log::warn!(
+                            "Backend (run_id={run_id}) reported port {reported}, not {stored}"
+                        );
+                        events::emit(
+                            &app,
+                            events::PORT_CHANGED,
+                            events::PortChangedPayload {
+                                old_port: Some(stored),
+                                new_port: reported,
+                            },
+                        );
+                    }
+                    (reported, SocketAddr::new(addr.ip(), reported))
+                }
+                None => {
+                    log::info!(
+                        "Backend (run_id={run_id}) printed no ready line, probing port {port}"
+                    );
+                    (port, addr)
+                }
+            },
+            None => (port, addr),
+        };
+        let listening = loop {
+            let slice = deadline
+                .saturating_duration_since(Instant::now())
+                .min(PORT_RACE_CHECK_INTERVAL);
+            if ready::wait_for_endpoints(addr, socket.as_deref(), require_all, slice) {
+                break true;
+            }
+            if let Some(ref race) = race {
+                if lost_port_race(&app.state::<BackendProcess>(), &run_id, port, race) {
+                    relaunch_after_port_race(&app, &relaunch_config, &run_id, port, race);
+                    return;
+                }
+            }
+            if Instant::now() >= deadline {
+                break false;
+            }
+        };
+        if !listening {
+            let endpoints = match socket {
+                Some(ref socket) if require_all => {
+                    format!("port {port} and {}", socket.display())
+                }
+                Some(ref socket) => format!("port {port} or {}", socket.display()),
+                None => format!("port {port}"),
+            };
+            fail(
+                events::NOT_READY,
+                format!(
+                    "did not accept connections on {endpoints} within {:?}",
+                    ready::READY_TIMEOUT
+                ),
+            );
+            return;
+        }
+        let listening_ms = since_spawn();
+        if cancel.load(Ordering::SeqCst) {
+            return;
+        }
+        if !ready_endpoints.is_empty() {
+            let client = app.state::<BackendClient>();
+            let passed = tauri::async_runtime::block_on(ready::wait_for_ready_endpoints(
+                &client,
+                addr,
+                &ready_endpoints,
+            ));
+            if let Err(failure) = passed {
+                fail_at(events::NOT_READY, failure.error, Some(failure.path));
+                return;
+            }
+            if cancel.load(Ordering::SeqCst) {
+                return;
+            }
+        }
+        let mut warmed_ms = None;
+        if let Some(ref path) = warmup_path {
+            let client = app.state::<BackendClient>();
+            let warmed = tauri::async_runtime::block_on(ready::warmup(&client, addr, path));
+            match warmed {
+                Ok(()) => warmed_ms = since_spawn(),
+                Err(error) => {
+                    fail(events::WARMUP_FAILED, error);
+                    if warmup_required {
+                        return;
+                    }
+                }
+            }
+        }
+        if cancel.load(Ordering::SeqCst)
+            || !state.update_launch(&run_id, |launch| launch.ready = true)
+        {
+            return;
+        }
+        events::emit(
+            &app,
+            events::READY,
+            events::ReadyPayload {
+                run_id: run_id.clone(),
+                port,
+            },
+        );
+        if let (Some((started_at, cold)), Some(listening_ms)) = (start, listening_ms) {
+            let timing = timings::StartupTiming {
+                run_id: run_id.clone(),
+                started_at,
+                cold,
+                listening_ms,
+                healthy_ms: None,
+                warmed_ms,
+            };
+            record_startup_timing(&app, timing, addr, health_check, deadline);
+        }
+        let Some(hook) = hook.filter(|_| !cancel.load(Ordering::SeqCst)) else {
+            return;
+        };
+        let Err(e) = hook::run(&hook, port, &run_id, &hook_log) else {
+            return;
+        };
+        fail(events::HOOK_FAILED, e.to_string());
+        if hook_fatal && state.update_launch(&run_id, |_| {}) {
+            state.record_error(&e.to_string());
+            stop_backend_process(&app, &state, StopReason::PostStartHookFailed);
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start readiness watcher: {e}");
+    }
+}
+
+/// In the background, fill in when the launch behind `timing` first passes `check`, polling
+/// until `deadline`, and add it to the `startup_timings` history.
+fn record_startup_timing<R: Runtime>(
+    app: &AppHandle<R>,
+    mut timing: timings::StartupTiming,
+    addr: SocketAddr,
+    check: ready::HealthCheck,
+    deadline: Instant,
+) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let client = app.state::<BackendClient>();
+        let budget = deadline.saturating_duration_since(Instant::now());
+        if ready::wait_healthy(&client, addr, &check, budget)
+            .await
+            .is_ok()
+        {
+            timing.healthy_ms = Some(now_millis().saturating_sub(timing.started_at));
+        }
+        if let Some(timings) = app.try_state::<timings::StartupTimings>() {
+            timings.record(timing);
+        }
+    });
+}
+
+/// Whether launch `run_id` crashed before listening because `port` was taken: it is the
+/// latest crash on record and its output since `race.log_offset` says so. Output reaches
+/// the file through the reader threads when streaming, so a miss is retried on the next
+/// check.
+fn lost_port_race(state: &BackendProcess, run_id: &str, port: u16, race: &PortRace) -> bool {
+    let crashed = state.lock_reaped().is_ok_and(|guard| {
+        guard
+            .crashes
+            .back()
+            .is_some_and(|crash| crash.run_id == run_id)
+    });
+    crashed
+        && startup_output(&race.log_path, race.log_offset)
+            .is_some_and(|output| bun::reports_port_in_use(&output, port))
+}
+
+/// What was appended to `path` after `offset`, at most [`STARTUP_OUTPUT_LIMIT`] bytes of it.
+fn startup_output(path: &Path, offset: u64) -> Option<String> {
+    use std::io::{Read, Seek, SeekFrom};
+    let mut file = fs::File::open(path).ok()?;
+    file.seek(SeekFrom::Start(offset)).ok()?;
+    let mut output = Vec::new();
+    file.take(STARTUP_OUTPUT_LIMIT)
+        .read_to_end(&mut output)
+        .ok()?;
+    Some(String::from_utf8_lossy(&output).into_owned())
+}
+
+/// Report a lost port race and, unless the port is forced or retries ran out, start the
+/// backend again; the next scan skips the port now taken.
+fn relaunch_after_port_race<R: Runtime>(
+    app: &AppHandle<R>,
+    config: &LauncherConfig,
+    run_id: &str,
+    port: u16,
+    race: &PortRace,
+) {
+    let state = app.state::<BackendProcess>();
+    let error = BackendError::PortRaceLost(port).to_string();
+    let retrying = race.retries_left > 0 && config.force_port.is_none();
+    log::warn!("Backend (run_id={run_id}): {error}");
+    state.record_error(&error);
+    events::emit(
+        app,
+        events::PORT_RACE_LOST,
+        events::PortRaceLostPayload {
+            run_id: run_id.to_string(),
+            port,
+            retrying,
+        },
+    );
+    if !retrying {
+        return;
+    }
+    // Same session: the failed attempt's output stays in the log.
+    let mut config = config.clone();
+    config.log_mode = LogMode::Append;
+    let relaunched = launch_with_retries(
+        app,
+        &state,
+        &config,
+        race.env.clone(),
+        race.retries_left - 1,
+    );
+    if let Err(error) = relaunched {
+        log::error!("Failed to start the backend again after losing port {port}: {error}");
+        events::emit(
+            app,
+            events::START_FAILED,
+            events::StartFailedPayload { error },
+        );
+    }
+}
+
+/// Store `launch` as the current backend and announce it (`port-changed`, then `started`).
+fn record_launch<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    launch: Launch,
+) -> Result<(), String> {
+    let started = events::StartedPayload {
+        run_id: launch.run_id.clone(),
+        port: launch.port,
+        pid: launch.child.as_ref().map(Child::id),
+    };
+    let old_port = state.store_launch(launch)?;
+    announce_launch(app, old_port, started);
+    Ok(())
+}
+
+/// Emit `port-changed` (if the port moved away from `old_port`), then `started`.
+fn announce_launch<R: Runtime>(
+    app: &AppHandle<R>,
+    old_port: Option<u16>,
+    started: events::StartedPayload,
+) {
+    if old_port != Some(started.port) {
+        events::emit(
+            app,
+            events::PORT_CHANGED,
+            events::PortChangedPayload {
+                old_port,
+                new_port: started.port,
+            },
+        );
+    }
+    events::emit(app, events::STARTED, started);
+}
+
+/// `cmd.spawn()`, retried a few times when it fails with an error that may clear up on its
+/// own (EAGAIN under memory pressure, EINTR). Anything else, like bun missing, fails at once.
+fn spawn_with_retry(cmd: &mut Command) -> std::io::Result<Child> {
+    let mut attempt = 1;
+    loop {
+        match cmd.spawn() {
+            Err(e)
+                if attempt < SPAWN_ATTEMPTS
+                    && matches!(
+                        e.kind(),
+                        std::io::ErrorKind::WouldBlock | std::io::ErrorKind::Interrupted
+                    ) =>
+            {
+                log::warn!(
+                    "Spawning bun failed ({e}), retrying (attempt {}/{SPAWN_ATTEMPTS})",
+                    attempt + 1
+                );
+                std::thread::sleep(SPAWN_RETRY_DELAY);
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Open `path` for appending, creating it and its directory if needed. With
+/// [`LogMode::Truncate`] the previous contents are archived and the file starts empty.
+fn open_log_file(path: &Path, mode: LogMode) -> Result<fs::File, String> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    }
+    let mut options = OpenOptions::new();
+    options.create(true);
+    match mode {
+        LogMode::Append => options.append(true),
+        LogMode::Truncate => {
+            if let Err(e) = logs::archive_previous(path) {
+                log::warn!("Failed to archive {}: {e}", path.display());
+            }
+            options.write(true).truncate(true)
+        }
+    };
+    options
+        .open(path)
+        .map_err(|e| format!("Failed to open {}: {e}", path.display()))
+}
+
+/// Apply `change` to the persisted settings: saved to `settings.json` first, and only
+/// adopted by the running config once that succeeded.
+fn update_settings(
+    config: &Mutex<LauncherConfig>,
+    paths: &AppPaths,
+    change: impl FnOnce(&mut Settings),
+) -> Result<(), String> {
+    let mut config = config.lock().map_err(|e| e.to_string())?;
+    let mut settings = config.settings.clone();
+    change(&mut settings);
+    settings
+        .save(&paths.settings_file)
+        .map_err(|e| format!("Failed to save settings: {e}"))?;
+    config.settings = settings;
+    Ok(())
+}
+
+/// A persisted backend variable as shown to the webview.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendEnvVar {
+    key: String,
+    /// Redacted for secret-looking keys.
+    value: String,
+    secret: bool,
+}
+
+/// Tauri command: persist `key=value` for every future backend spawn. Takes effect on the
+/// next start; returns `true` when a running backend needs a restart to see it.
+#[tauri::command]
+fn set_backend_env(
+    paths: State<'_, AppPaths>,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    audit: State<'_, AuditLog>,
+    key: String,
+    value: String,
+) -> Result<bool, String> {
+    let args = json!({ "key": key, "value": backend_env::display_value(&key, &value) });
+    let result = backend_env::validate_key(&key).and_then(|()| {
+        log::info!(
+            "Setting persistent backend env {key}={}",
+            backend_env::display_value(&key, &value)
+        );
+        update_settings(&config, &paths, |settings| {
+            settings.backend_env.insert(key, value);
+        })?;
+        state.mark_config_dirty()
+    });
+    audit.record("set_backend_env", args, &result, None);
+    result
+}
+
+/// Tauri command: remove a persisted backend variable. Returns `true` when a running
+/// backend needs a restart to drop it.
+#[tauri::command]
+fn unset_backend_env(
+    paths: State<'_, AppPaths>,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    audit: State<'_, AuditLog>,
+    key: String,
+) -> Result<bool, String> {
+    let args = json!({ "key": key });
+    let result = update_settings(&config, &paths, |settings| {
+        settings.backend_env.remove(&key);
+    })
+    .and_then(|()| state.mark_config_dirty());
+    audit.record("unset_backend_env", args, &result, None);
+    result
+}
+
+/// Tauri command: the persisted backend variables, with secret-looking values redacted.
+#[tauri::command]
+fn get_backend_env(config: State<'_, Mutex<LauncherConfig>>) -> Result<Vec<BackendEnvVar>, String> {
+    let config = config.lock().map_err(|e| e.to_string())?;
+    Ok(config
+        .settings
+        .backend_env
+        .iter()
+        .map(|(key, value)| BackendEnvVar {
+            key: key.clone(),
+            value: backend_env::display_value(key, value).to_string(),
+            secret: backend_env::is_secret(key),
+        })
+        .collect())
+}
+
+/// Tauri command: write backend output to `path` instead of `<app data>/backend.log`;
+/// `None` restores the default.
+///
+/// The file must be writable (it is created if missing). The setting is persisted and used
+/// by later launches; returns `true` when a backend is running and keeps logging to the old
+/// file until it is restarted.
+#[tauri::command]
+fn set_log_path(
+    paths: State<'_, AppPaths>,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    path: Option<String>,
+) -> Result<bool, String> {
+    let log_path = match path {
+        Some(path) => {
+            let path = PathBuf::from(path);
+            if !path.is_absolute() {
+                return Err(format!("Log path must be absolute, got {}", path.display()));
+            }
+            if path.is_dir() {
+                return Err(format!("Log path {} is a directory", path.display()));
+            }
+            open_log_file(&path, LogMode::Append)?;
+            Some(path)
+        }
+        None => None,
+    };
+
+    update_settings(&config, &paths, |settings| settings.log_path = log_path)?;
+    let running = state.mark_config_dirty()?;
+    if running {
+        log::info!("New log path takes effect when the backend is next started");
+    }
+    Ok(running)
+}
+
+/// Tauri command: change how much the launcher itself logs (`off`, `error`, `warn`, `info`,
+/// `debug` or `trace`), effective immediately. The level is persisted and applied on the
+/// next app start too.
+#[tauri::command]
+fn set_launcher_log_level(
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    level: String,
+) -> Result<(), String> {
+    let level = logger::parse_level(&level)?;
+    let name = level.to_string().to_ascii_lowercase();
+    update_settings(&config, &paths, |settings| {
+        settings.launcher_log_level = Some(name)
+    })?;
+    logger::set_level(level);
+    log::info!("Launcher log level set to {level}");
+    Ok(())
+}
+
+/// Tauri command: the file the next backend launch will write its output to.
+#[tauri::command]
+fn get_log_path(
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<String, String> {
+    let config = config.lock().map_err(|e| e.to_string())?;
+    Ok(config.log_path(&paths).display().to_string())
+}
+
+/// Tauri command: push the backend log and the launcher's own output to disk, e.g. before
+/// collecting them for a bug report. Streamed lines are synced once their newline has been
+/// read; a backend writing straight to the file is synced through a fresh handle. Fails
+/// if the disk doesn't finish within two seconds.
+#[tauri::command]
+async fn flush_logs(
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    state: State<'_, BackendProcess>,
+) -> Result<(), String> {
+    let log_path = config.lock().map_err(|e| e.to_string())?.log_path(&paths);
+    let streamed = state.log_files();
+    let flushed = tauri::async_runtime::spawn_blocking(move || {
+        log::logger().flush();
+        for file in streamed {
+            let file = file.lock().unwrap_or_else(PoisonError::into_inner);
+            file.sync_all()
+                .map_err(|e| format!("Failed to sync {}: {e}", log_path.display()))?;
+        }
+        match OpenOptions::new().append(true).open(&log_path) {
+            Ok(file) => file
+                .sync_all()
+                .map_err(|e| format!("Failed to sync {}: {e}", log_path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(format!("Failed to open {}: {e}", log_path.display())),
+        }
+    });
+    match tokio::time::timeout(FLUSH_TIMEOUT, flushed).await {
+        Ok(flushed) => flushed.map_err(|e| format!("Log flush task failed: {e}"))?,
+        Err(_) => Err(format!(
+            "Logs were not flushed within {}s",
+            FLUSH_TIMEOUT.as_secs()
+        )),
+    }
+}
+
+/// Tauri command: open the running backend's base URL in the default browser; returns the
+/// URL. Fails with `OpenerUnavailable` in a build without the opener plugin.
+#[tauri::command]
+fn open_backend_in_browser<R: Runtime>(
+    app: AppHandle<R>,
+    state: State<'_, BackendProcess>,
+) -> Result<String, String> {
+    let opener = app
+        .try_state::<tauri_plugin_opener::Opener<R>>()
+        .ok_or(BackendError::OpenerUnavailable)?;
+    let addr = state
+        .lock_reaped()?
+        .running_addr()
+        .ok_or("Backend is not running")?;
+    let url = net::base_url(addr);
+    opener
+        .open_url(&url, None::<&str>)
+        .map_err(|e| format!("Failed to open {url}: {e}"))?;
+    Ok(url)
+}
+
+/// Tauri command: `tail -f` for the backend log. Emits its last `from_end_lines` lines
+/// (default 100) as `backend://log` events with stream `file`, then every line appended
+/// afterwards, re-opening the file when it is rotated or truncated. Works whatever wrote
+/// the file, including attached backends; a later `set_log_path` needs a new call.
+#[tauri::command]
+fn follow_backend_log(
+    app: AppHandle,
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    follower: State<'_, LogFollower>,
+    from_end_lines: Option<usize>,
+) -> Result<(), String> {
+    let (log_path, max_line) = {
+        let config = config.lock().map_err(|e| e.to_string())?;
+        (config.log_path(&paths), config.max_log_line)
+    };
+    follower.start(&app, log_path, from_end_lines.unwrap_or(100), max_line)
+}
+
+/// Tauri command: stop `follow_backend_log`. Returns whether it was running.
+#[tauri::command]
+fn unfollow_backend_log(follower: State<'_, LogFollower>) -> Result<bool, String> {
+    follower.stop()
+}
+
+/// Tauri command: size of the backend log and its rotated files, for storage management.
+#[tauri::command]
+fn log_stats(
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<logs::LogStats, String> {
+    let log_path = config.lock().map_err(|e| e.to_string())?.log_path(&paths);
+    logs::stats(&log_path).map_err(|e| format!("Failed to read log stats: {e}"))
+}
+
+/// Tauri command: called by the webview once it has mounted.
+///
+/// With `start_on_frontend_ready` enabled the launcher starts the backend here, after first
+/// paint, and returns its port (also when it is already running). Otherwise returns `None`
+/// and the frontend is expected to call `start_backend` itself.
+#[tauri::command]
+async fn frontend_ready(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<Option<u16>, String> {
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    if !config.start_on_frontend_ready {
+        return Ok(None);
+    }
+    if let Some(port) = state.lock_reaped()?.running_port() {
+        return Ok(Some(port));
+    }
+    log::info!("Frontend is ready, starting backend");
+    launch_blocking(&app, config, None).await.map(Some)
+}
+
+/// Tauri command: gracefully stop the backend if it is running. Does nothing otherwise.
+///
+/// Refuses to touch a backend attached with `attach_backend`, since the app didn't start it.
+#[tauri::command]
+async fn stop_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    audit: State<'_, AuditLog>,
+) -> Result<(), String> {
+    let run_id = state.current_run_id();
+    let result = match state.ensure_not_attached() {
+        Ok(()) => stop_blocking(&app, StopReason::UserRequested).await,
+        Err(e) => Err(e),
+    };
+    audit.record("stop_backend", Value::Null, &result, run_id.as_deref());
+    result
+}
+
+/// Tauri command: stop the backend (if running) and start a fresh one with the current
+/// settings. Returns the new port once it passes the health check (`backend://restarted`);
+/// a new backend that never does is left running and reported as `RestartUnhealthy`.
+/// With `TOSHIK_HANG_DIAGNOSTICS`, an old backend failing its health check gets a hang
+/// report written first (`backend://hang-captured`).
+#[tauri::command]
+async fn restart_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    client: State<'_, BackendClient>,
+    audit: State<'_, AuditLog>,
+) -> Result<u16, String> {
+    let previous_run_id = state.current_run_id();
+    let result = restart(&app, &state, &config, &client, previous_run_id.clone()).await;
+    audit.record(
+        "restart_backend",
+        json!({ "previousRunId": previous_run_id }),
+        &result,
+        state.current_run_id().as_deref(),
+    );
+    result
+}
+
+/// How often a scheduled restart's timer checks whether it was cancelled.
+const SCHEDULE_TICK: Duration = Duration::from_millis(250);
+
+/// Tauri command: restart the backend in `delay_secs`, e.g. to apply an update at a quiet
+/// moment; with `drain` the new backend takes over blue-green, as in `drain_backend`.
+/// Only one restart can be pending: another call replaces it (`backend://restart-cancelled`)
+/// or, with `TOSHIK_RESTART_SCHEDULE_POLICY=reject`, fails.
+#[tauri::command]
+fn schedule_restart(
+    app: AppHandle,
+    schedule: State<'_, ScheduledRestart>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    tasks: State<'_, TaskRegistry>,
+    delay_secs: u64,
+    drain: Option<bool>,
+) -> Result<PendingRestart, String> {
+    let policy = config
+        .lock()
+        .map_err(|e| e.to_string())?
+        .restart_schedule_policy;
+    let delay = Duration::from_secs(delay_secs);
+    let drain = drain.unwrap_or(false);
+    let (pending, replaced) =
+        schedule.schedule(now_millis() + delay.as_millis() as u64, drain, policy)?;
+    if let Some(replaced) = replaced {
+        tasks.cancel(schedule::TASK_NAME);
+        restart_cancelled(&app, replaced, true);
+    }
+
+    let timer_app = app.clone();
+    let spawned = tasks.spawn(schedule::TASK_NAME, None, move |cancel| {
+        let due = Instant::now() + delay;
+        loop {
+            if cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            let left = due.saturating_duration_since(Instant::now());
+            if left.is_zero() {
+                break;
+            }
+            std::thread::sleep(left.min(SCHEDULE_TICK));
+        }
+        run_scheduled_restart(&timer_app, pending.id);
+    });
+    if let Err(e) = spawned {
+        schedule.take_if(pending.id);
+        return Err(format!("Failed to start the restart timer: {e}"));
+    }
+    log::info!(
+        "Scheduled a {} in {delay_secs}s",
+        if drain { "drain" } else { "restart" }
+    );
+    events::emit(
+        &app,
+        events::RESTART_SCHEDULED,
+        events::RestartScheduledPayload {
+            at: pending.at,
+            drain,
+        },
+    );
+    Ok(pending)
+}
+
+/// Tauri command: call off the pending scheduled restart; returns whether there was one.
+#[tauri::command]
+fn cancel_scheduled_restart(
+    app: AppHandle,
+    schedule: State<'_, ScheduledRestart>,
+    tasks: State<'_, TaskRegistry>,
+) -> bool {
+    let Some(pending) = schedule.cancel() else {
+        return false;
+    };
+    tasks.cancel(schedule::TASK_NAME);
+    log::info!("Cancelled the scheduled restart");
+    restart_cancelled(&app, pending, false);
+    true
+}
+
+fn restart_cancelled<R: Runtime>(app: &AppHandle<R>, pending: PendingRestart, replaced: bool) {
+    events::emit(
+        app,
+        events::RESTART_CANCELLED,
+        events::RestartCancelledPayload {
+            at: pending.at,
+            replaced,
+        },
+    );
+}
+
+/// Fire the scheduled restart `id` from its timer, unless it was cancelled or replaced
+/// meanwhile.
+fn run_scheduled_restart<R: Runtime>(app: &AppHandle<R>, id: u64) {
+    let Some(pending) = app.state::<ScheduledRestart>().take_if(id) else {
+        return;
+    };
+    let state = app.state::<BackendProcess>();
+    let config = app.state::<Mutex<LauncherConfig>>();
+    let client = app.state::<BackendClient>();
+    let previous_run_id = state.current_run_id();
+    let result = tauri::async_runtime::block_on(async {
+        if pending.drain {
+            drain(app, &state, &config, &client).await
+        } else {
+            restart(app, &state, &config, &client, previous_run_id.clone()).await
+        }
+    });
+    if let Err(ref e) = result {
+        log::warn!("Scheduled restart failed: {e}");
+    }
+    if let Some(audit) = app.try_state::<AuditLog>() {
+        audit.record(
+            "schedule_restart",
+            json!({ "previousRunId": previous_run_id, "drain": pending.drain }),
+            &result,
+            state.current_run_id().as_deref(),
+        );
+    }
+}
+
+/// Tauri command: blue-green restart. Start a second backend on another port, wait until it
+/// is ready, switch over to it (`port-changed`, `started`) and only then stop the old one.
+/// If the new backend never becomes ready or healthy it is stopped and the old one keeps
+/// serving.
+/// Returns the new port.
+#[tauri::command]
+async fn drain_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    client: State<'_, BackendClient>,
+    audit: State<'_, AuditLog>,
+) -> Result<u16, String> {
+    let previous_run_id = state.current_run_id();
+    let result = drain(&app, &state, &config, &client).await;
+    audit.record(
+        "drain_backend",
+        json!({ "previousRunId": previous_run_id }),
+        &result,
+        state.current_run_id().as_deref(),
+    );
+    result
+}
+
+async fn drain<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &Mutex<LauncherConfig>,
+    client: &BackendClient,
+) -> Result<u16, String> {
+    let mut config = config.lock().map_err(|e| e.to_string())?.clone();
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err("Application is shutting down".into());
+    }
+    if config.force_port.is_some() {
+        return Err(
+            "Cannot drain with TOSHIK_FORCE_PORT set: the new backend needs another port".into(),
+        );
+    }
+    state.ensure_not_attached()?;
+    let old_run_id = state.current_run_id().ok_or("Backend is not running")?;
+    if state.lock_reaped()?.standby.is_some() {
+        return Err("A drain is already in progress".into());
+    }
+
+    // The old backend keeps writing to the log until the standby takes over.
+    config.log_mode = LogMode::Append;
+    let spawned = {
+        let (app, config) = (app.clone(), config.clone());
+        tauri::async_runtime::spawn_blocking(move || spawn_backend(&app, &config, None))
+    };
+    let (launch, hook_log) = spawned
+        .await
+        .map_err(|e| format!("Start task failed: {e}"))??;
+    let run_id = launch.run_id.clone();
+    let addr = SocketAddr::new(launch.host, launch.port);
+    let socket = launch.socket.clone();
+    let started = events::StartedPayload {
+        run_id: run_id.clone(),
+        port: launch.port,
+        pid: launch.child.as_ref().map(Child::id),
+    };
+    log::info!("Draining backend (run_id={old_run_id}) to run_id={run_id} on {addr}");
+    state.store_standby(launch)?;
+
+    let addr = announced_addr(state, &run_id, addr).await;
+    if let Err(e) = standby_ready(client, addr, &config).await {
+        log::warn!("Drain aborted, new backend (run_id={run_id}) not ready: {e}");
+        state.discard_standby();
+        return Err(format!(
+            "New backend did not become ready, keeping the old one: {e}"
+        ));
+    }
+
+    let (mut old, old_port) = state.promote_standby(&old_run_id)?;
+    announce_launch(app, old_port, started);
+    events::emit(
+        app,
+        events::RESTARTED,
+        events::RestartedPayload {
+            run_id: run_id.clone(),
+            previous_run_id: Some(old_run_id),
+            port: addr.port(),
+        },
+    );
+    let old_run_id = old.run_id.clone();
+    let stopped = tauri::async_runtime::spawn_blocking(move || old.terminate()).await;
+    if let Err(e) = stopped {
+        log::warn!("Stopping the drained backend (run_id={old_run_id}) failed: {e}");
+    }
+    app.state::<TaskRegistry>().cancel_run(&old_run_id);
+    events::emit(
+        app,
+        events::STOPPED,
+        events::StoppedPayload {
+            run_id: Some(old_run_id),
+            reason: StopReason::Restart,
+        },
+    );
+
+    // Already warmed up above; the watcher only has to mark it ready and run the hook.
+    config.warmup_path = None;
+    watch_readiness(app, run_id, addr.port(), socket, &config, hook_log, None);
+    Ok(addr.port())
+}
+
+/// Wait for a drain standby to accept connections, answer the warmup request and pass the
+/// health check. A failed warmup only counts when `TOSHIK_WARMUP_REQUIRED` is set, as for
+/// a normal start.
+async fn standby_ready(
+    client: &BackendClient,
+    addr: SocketAddr,
+    config: &LauncherConfig,
+) -> Result<(), String> {
+    ready::wait_listening(addr, ready::READY_TIMEOUT).await?;
+    if let Some(ref path) = config.warmup_path {
+        if let Err(e) = ready::warmup(client, addr, path).await {
+            if config.warmup_required {
+                return Err(e);
+            }
+            log::warn!("Drain standby on {addr}: {e}");
+        }
+    }
+    ready::wait_healthy(client, addr, &config.health_check, ready::READY_TIMEOUT).await
+}
+
+/// Tauri command: the configuration in effect, with defaults, `TOSHIK_*` variables and
+/// persisted settings merged, and secret-looking values redacted.
+#[tauri::command]
+fn effective_config(config: State<'_, Mutex<LauncherConfig>>) -> Result<LauncherConfig, String> {
+    Ok(config.lock().map_err(|e| e.to_string())?.redacted())
+}
+
+/// Tauri command: the effective configuration as commented TOML, each field documented with
+/// the variable that sets it. Only a reference: there is no config file the launcher reads.
+#[tauri::command]
+fn generate_config_template(config: State<'_, Mutex<LauncherConfig>>) -> Result<String, String> {
+    let config = config.lock().map_err(|e| e.to_string())?;
+    Ok(template::render(&config))
+}
+
+/// Tauri command: where each field of `effective_config` came from (default, environment
+/// variable or settings file).
+#[tauri::command]
+fn config_sources(
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<BTreeMap<&'static str, config::ConfigSource>, String> {
+    Ok(config.lock().map_err(|e| e.to_string())?.sources())
+}
+
+async fn restart<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &Mutex<LauncherConfig>,
+    client: &BackendClient,
+    previous_run_id: Option<String>,
+) -> Result<u16, String> {
+    state.ensure_not_attached()?;
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    if config.hang_diagnostics {
+        capture_if_hung(app, state, &config, client).await;
+    }
+    stop_blocking(app, StopReason::Restart).await?;
+    start_healthy(app, state, &config, client, previous_run_id).await
+}
 
-use tauri::{plugin::Builder as PluginBuilder, AppHandle, Manager, RunEvent, Runtime, State};
+/// Write a hang report (`TOSHIK_HANG_DIAGNOSTICS`) for the spawned backend if it doesn't
+/// pass its health check, so the evidence survives the restart.
+async fn capture_if_hung<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &LauncherConfig,
+    client: &BackendClient,
+) {
+    let Some((run_id, pid, addr)) = state.lock_reaped().ok().and_then(|guard| {
+        let launch = guard.launch.as_ref()?;
+        let pid = launch.child.as_ref()?.id();
+        Some((launch.run_id.clone(), pid, guard.running_addr()?))
+    }) else {
+        return;
+    };
+    if ready::wait_healthy(client, addr, &config.health_check, Duration::ZERO)
+        .await
+        .is_ok()
+    {
+        return;
+    }
+    let log_path = config.log_path(&app.state::<AppPaths>());
+    let dir = log_path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let signal = config.hang_signal;
+    let capture_run_id = run_id.clone();
+    let captured = tauri::async_runtime::spawn_blocking(move || {
+        diagnostics::capture_hang(pid, &capture_run_id, &dir, signal)
+    })
+    .await;
+    match captured {
+        Ok(Ok(path)) => {
+            log::warn!(
+                "Backend (run_id={run_id}) is unresponsive, hang report written to {}",
+                path.display()
+            );
+            events::emit(
+                app,
+                events::HANG_CAPTURED,
+                events::HangCapturedPayload {
+                    run_id,
+                    path: path.display().to_string(),
+                },
+            );
+        }
+        Ok(Err(e)) => log::warn!("Failed to write a hang report for run_id={run_id}: {e}"),
+        Err(e) => log::warn!("Hang capture for run_id={run_id} failed: {e}"),
+    }
+}
 
-/// Holds the backend child process so we can kill it on app exit.
-struct BackendProcess(Mutex<Option<Child>>);
+/// Where launch `run_id` listens: on the port its ready line announces, which the
+/// readiness watcher stores in place of the one in `spawned`, or on `spawned` without one.
+async fn announced_addr(state: &BackendProcess, run_id: &str, spawned: SocketAddr) -> SocketAddr {
+    let Some(announced) = state.announced_port(run_id) else {
+        return spawned;
+    };
+    let port =
+        tauri::async_runtime::spawn_blocking(move || announced.wait(ready::READY_LINE_TIMEOUT))
+            .await;
+    match port {
+        Ok(Some(port)) => SocketAddr::new(spawned.ip(), port),
+        _ => spawned,
+    }
+}
 
-/// Plugin that kills the backend process on app exit (Tauri 2 has no Builder::on_event, only in plugins).
-fn backend_cleanup_plugin<R: Runtime>() -> tauri::plugin::TauriPlugin<R> {
-    PluginBuilder::new("backend-cleanup").on_event(|app, event| {
-        if let RunEvent::Exit = event {
-            if let Some(state) = app.try_state::<BackendProcess>() {
-                if let Ok(mut guard) = state.0.lock() {
-                    if let Some(ref mut child) = *guard {
-                        let pid = child.id();
-                        log::info!("Killing backend process (pid={})", pid);
-                        let _ = child.kill();
-                        let _ = child.wait();
-                    }
-                    *guard = None;
-                }
-            }
+/// Launch a backend, wait until it passes the health check and announce it as
+/// `backend://restarted`. Returns its port.
+async fn start_healthy<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &LauncherConfig,
+    client: &BackendClient,
+    previous_run_id: Option<String>,
+) -> Result<u16, String> {
+    let port = launch_blocking(app, config.clone(), None).await?;
+    let run_id = state
+        .current_run_id()
+        .ok_or("Backend exited right after restart")?;
+
+    let addr = announced_addr(state, &run_id, SocketAddr::new(config.host, port)).await;
+    let port = addr.port();
+    let healthy = match ready::wait_listening(addr, ready::READY_TIMEOUT).await {
+        Ok(()) => {
+            ready::wait_healthy(client, addr, &config.health_check, ready::READY_TIMEOUT).await
         }
-    }).build()
+        Err(e) => Err(e),
+    };
+    if let Err(e) = healthy {
+        log::warn!("Restarted backend (run_id={run_id}) is unhealthy: {e}");
+        return Err(BackendError::RestartUnhealthy(e).into());
+    }
+    events::emit(
+        app,
+        events::RESTARTED,
+        events::RestartedPayload {
+            run_id,
+            previous_run_id,
+            port,
+        },
+    );
+    Ok(port)
 }
 
-/// Scan ports 3001–3010 and return the first available one.
-fn find_available_port() -> Option<u16> {
-    for port in 3001..=3010 {
-        if TcpListener::bind(("127.0.0.1", port)).is_ok() {
-            return Some(port);
-        }
+/// Tauri command: upgrade in place. Stop the backend gracefully, run
+/// `TOSHIK_MIGRATION_COMMAND` (output in `migration.log`) and, only if it exits 0, start
+/// the backend again and wait for it to pass the health check, like `restart_backend`.
+/// A failed migration leaves the backend stopped and returns `MigrationFailed` with the end
+/// of the log. Returns the new port.
+#[tauri::command]
+async fn upgrade_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    client: State<'_, BackendClient>,
+    audit: State<'_, AuditLog>,
+) -> Result<u16, String> {
+    let previous_run_id = state.current_run_id();
+    let result = upgrade(&app, &state, &config, &client, previous_run_id.clone()).await;
+    audit.record(
+        "upgrade_backend",
+        json!({ "previousRunId": previous_run_id }),
+        &result,
+        state.current_run_id().as_deref(),
+    );
+    result
+}
+
+async fn upgrade<R: Runtime>(
+    app: &AppHandle<R>,
+    state: &BackendProcess,
+    config: &Mutex<LauncherConfig>,
+    client: &BackendClient,
+    previous_run_id: Option<String>,
+) -> Result<u16, String> {
+    state.ensure_not_attached()?;
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    let command = config
+        .migration_command
+        .clone()
+        .ok_or("TOSHIK_MIGRATION_COMMAND is not set")?;
+    let log_path = config
+        .log_path(&app.state::<AppPaths>())
+        .with_file_name("migration.log");
+    stop_blocking(app, StopReason::Upgrade).await?;
+
+    let env = config.settings.backend_env.clone();
+    let migrated =
+        tauri::async_runtime::spawn_blocking(move || hook::migrate(&command, &env, &log_path))
+            .await
+            .map_err(|e| format!("Migration task failed: {e}"))?;
+    if let Err(e) = migrated {
+        let message = e.to_string();
+        log::warn!("{message}");
+        state.record_error(&message);
+        return Err(message);
     }
-    None
+    start_healthy(app, state, &config, client, previous_run_id).await
 }
 
-/// Tauri command: find a free port, spawn `bun run packages/backend/src/index.ts --port <PORT>`,
-/// redirect stdout/stderr to `backend.log`, and return the chosen port.
+/// Tauri command: whether settings changed since the running backend was started and only
+/// take effect once it is restarted.
 #[tauri::command]
-fn start_backend(app: AppHandle, state: State<'_, BackendProcess>) -> Result<u16, String> {
-    // If backend is already running, don't spawn another one.
-    {
-        let guard = state.0.lock().map_err(|e| e.to_string())?;
-        if let Some(ref child) = *guard {
-            // Check if still alive by trying to get its id (non-zero means alive).
-            let _pid = child.id();
-            // Already running — we can't easily check exit status without `try_wait`
-            // but we'll handle it below after dropping the guard.
+fn restart_required(state: State<'_, BackendProcess>) -> Result<bool, String> {
+    let guard = state.lock_reaped()?;
+    Ok(guard.config_dirty && guard.launch.is_some())
+}
+
+/// Tauri command: use a backend that is already listening on `port` (e.g. one started by
+/// hand during development) instead of spawning one.
+///
+/// The port must accept a TCP connection. The launcher only records it: it won't be
+/// monitored for exit or stopped by `stop_backend`.
+#[tauri::command]
+fn attach_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    port: u16,
+) -> Result<(), String> {
+    if state.shutting_down.load(Ordering::SeqCst) {
+        return Err("Application is shutting down".into());
+    }
+    if state.lock_reaped()?.launch.is_some() {
+        return Err("Backend is already running".into());
+    }
+    let (host, allow_privileged) = {
+        let config = config.lock().map_err(|e| e.to_string())?;
+        (config.host, config.allow_privileged_ports)
+    };
+    let port = net::validate_port(port.into(), allow_privileged)?;
+    TcpStream::connect_timeout(&SocketAddr::new(host, port), ATTACH_TIMEOUT)
+        .map_err(|e| format!("No backend reachable on port {port}: {e}"))?;
+
+    let run_id = uuid::Uuid::new_v4().to_string();
+    log::info!("Attaching to external backend on port {port} (run_id={run_id})");
+    record_launch(
+        &app,
+        &state,
+        attached_launch(run_id, host, port, now_millis()),
+    )
+}
+
+/// A backend the launcher did not spawn and only records; the caller checked that it is
+/// reachable.
+fn attached_launch(run_id: String, host: IpAddr, port: u16, started_at: u64) -> Launch {
+    Launch {
+        child: None,
+        run_id,
+        host,
+        port,
+        version: None,
+        log_streams: None,
+        ready: true,
+        verbose: false,
+        shutdown: ShutdownTimeouts::default(),
+        env: BTreeMap::new(),
+        invocation: None,
+        pid_file: None,
+        socket: None,
+        started_at,
+        detached: false,
+        shutdown_token: None,
+        container: None,
+    }
+}
+
+/// `session.json` as a previous app instance left it, read before this one overwrites it.
+#[derive(Default)]
+struct PreviousSession(Mutex<Option<SessionState>>);
+
+/// A backend from a previous app session that is still reachable, as returned by
+/// `recover_previous_session`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RecoveredBackend {
+    run_id: String,
+    port: u16,
+    pid: Option<u32>,
+    /// Milliseconds since the Unix epoch.
+    started_at: u64,
+    restart_count: u32,
+    /// Milliseconds since the Unix epoch of the previous session's last heartbeat.
+    heartbeat_at: u64,
+    /// Started with `TOSHIK_DETACHED` and left running when that session exited.
+    detached: bool,
+    /// Whether it was attached as the current backend.
+    attached: bool,
+}
+
+/// Tauri command: the backend a previous app session recorded in `session.json`, if its
+/// process is still alive and its port accepts connections, e.g. after the app crashed.
+///
+/// With `attach`, it also becomes the current backend under its old run ID, with the same
+/// limits as `attach_backend`: it is not monitored for exit and `stop_backend` won't stop it.
+#[tauri::command]
+fn recover_previous_session(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    previous: State<'_, PreviousSession>,
+    attach: Option<bool>,
+) -> Result<Option<RecoveredBackend>, String> {
+    let Some(previous) = previous.0.lock().map_err(|e| e.to_string())?.clone() else {
+        return Ok(None);
+    };
+    // Liveness can only be checked on Unix; elsewhere the port check has to do.
+    if let Some(pid) = previous.pid.filter(|_| cfg!(unix)) {
+        if !pidfile::process_alive(pid) {
+            log::info!("Previous backend (pid={pid}) is gone, nothing to recover");
+            return Ok(None);
         }
     }
-    // Re-check with try_wait to see if it actually exited.
-    {
-        let mut guard = state.0.lock().map_err(|e| e.to_string())?;
-        if let Some(ref mut child) = *guard {
-            match child.try_wait() {
-                Ok(Some(_exited)) => {
-                    // Process exited, we can spawn a new one.
-                    *guard = None;
-                }
-                Ok(None) => {
-                    // Still running — return error.
-                    return Err("Backend is already running".into());
-                }
-                Err(e) => {
-                    log::warn!("Failed to check backend process status: {e}");
-                    *guard = None;
+    let addr = SocketAddr::new(previous.host, previous.port);
+    if let Err(e) = TcpStream::connect_timeout(&addr, ATTACH_TIMEOUT) {
+        log::info!("Previous backend on {addr} is not reachable: {e}");
+        return Ok(None);
+    }
+
+    let attach = attach.unwrap_or(false);
+    if attach {
+        if state.shutting_down.load(Ordering::SeqCst) {
+            return Err("Application is shutting down".into());
+        }
+        if state.lock_reaped()?.launch.is_some() {
+            return Err("Backend is already running".into());
+        }
+        log::info!(
+            "Reattaching to backend from a previous session on {addr} (run_id={})",
+            previous.run_id
+        );
+        let launch = attached_launch(
+            previous.run_id.clone(),
+            previous.host,
+            previous.port,
+            previous.started_at,
+        );
+        record_launch(&app, &state, launch)?;
+    }
+    Ok(Some(RecoveredBackend {
+        run_id: previous.run_id,
+        port: previous.port,
+        pid: previous.pid,
+        started_at: previous.started_at,
+        restart_count: previous.restart_count,
+        heartbeat_at: previous.heartbeat_at,
+        detached: previous.detached,
+        attached: attach,
+    }))
+}
+
+/// Every [`session::HEARTBEAT_INTERVAL`], write the running backend to `session.json`, and
+/// remove the file once it stops. Ends when the app shuts down.
+fn spawn_heartbeat<R: Runtime>(app: &AppHandle<R>, path: PathBuf) {
+    let tasks = app.state::<TaskRegistry>();
+    let app = app.clone();
+    let spawned = tasks.spawn("session-heartbeat", None, move |cancel| {
+        let state = app.state::<BackendProcess>();
+        let mut written = false;
+        loop {
+            std::thread::sleep(session::HEARTBEAT_INTERVAL);
+            if state.shutting_down.load(Ordering::SeqCst) || cancel.load(Ordering::SeqCst) {
+                return;
+            }
+            let Ok(guard) = state.lock_reaped() else {
+                return;
+            };
+            let current = guard.session_state();
+            drop(guard);
+            match current {
+                Some(current) => match session::write(&path, &current) {
+                    Ok(()) => written = true,
+                    Err(e) => log::warn!("Failed to write {}: {e}", path.display()),
+                },
+                // Only our own file: one left by a crashed session stays until replaced.
+                None if written => {
+                    session::remove(&path);
+                    written = false;
                 }
+                None => {}
             }
         }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start session heartbeat: {e}");
     }
+}
 
-    let port = find_available_port().ok_or("No available port in range 3001-3010")?;
+/// Tauri command: port of the running backend, e.g. one started by `TOSHIK_AUTOSTART`.
+#[tauri::command]
+fn backend_port(state: State<'_, BackendProcess>) -> Result<Option<u16>, String> {
+    Ok(state.lock_reaped()?.running_port())
+}
 
-    // Resolve log file path inside Tauri's app data directory.
-    let app_data_dir = app
-        .path()
-        .app_data_dir()
-        .map_err(|e| format!("Failed to resolve app data dir: {e}"))?;
-    fs::create_dir_all(&app_data_dir)
-        .map_err(|e| format!("Failed to create app data dir: {e}"))?;
+/// Tauri command: base URL of the running backend, e.g. `http://127.0.0.1:3001` (or
+/// `http://[::1]:3001` in IPv6 mode).
+#[tauri::command]
+fn backend_url(state: State<'_, BackendProcess>) -> Result<String, String> {
+    let addr = state
+        .lock_reaped()?
+        .running_addr()
+        .ok_or("Backend is not running")?;
+    Ok(net::base_url(addr))
+}
 
-    let log_path = app_data_dir.join("backend.log");
-    let log_file = OpenOptions::new()
-        .create(true)
-        .append(true)
-        .open(&log_path)
-        .map_err(|e| format!("Failed to open backend.log: {e}"))?;
+/// Tauri command: the version reported by the running backend's `/version` endpoint.
+#[tauri::command]
+async fn backend_version(
+    state: State<'_, BackendProcess>,
+    client: State<'_, BackendClient>,
+) -> Result<String, String> {
+    fetch_backend_version(&state, &client).await
+}
 
-    let log_file_err = log_file
-        .try_clone()
-        .map_err(|e| format!("Failed to clone log file handle: {e}"))?;
+/// Tauri command: compare the running backend's version with `expected`, to spot a stale
+/// backend left over from a partial update. `mode` is `exact` (default) or `compatible`
+/// for a semver caret match.
+#[tauri::command]
+async fn verify_backend_version(
+    state: State<'_, BackendProcess>,
+    client: State<'_, BackendClient>,
+    expected: String,
+    mode: Option<String>,
+) -> Result<version::VersionCheck, String> {
+    let mode = match mode {
+        Some(raw) => version::VersionMatch::parse(&raw)
+            .ok_or_else(|| format!("Unknown version match mode {raw:?}"))?,
+        None => version::VersionMatch::Exact,
+    };
+    let running = fetch_backend_version(&state, &client).await?;
+    let check = version::check(running, expected, mode);
+    if !check.matches {
+        log::warn!(
+            "Running backend version {} does not match expected {} ({mode:?})",
+            check.running,
+            check.expected
+        );
+    }
+    Ok(check)
+}
 
-    // Resolve the backend entry point relative to the resource directory.
-    // In dev mode, the workspace root is two levels up from src-tauri.
-    // We'll look for "bun" in PATH and pass the script path.
-    let backend_script = {
-        // Try to resolve relative to the current executable's grandparent (workspace root).
-        let exe_dir = std::env::current_exe()
-            .ok()
-            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
-
-        // In development, Cargo builds into src-tauri/target/debug, so workspace root is ../../../../
-        // We'll try multiple candidate paths.
-        let candidates: Vec<std::path::PathBuf> = if let Some(ref dir) = exe_dir {
-            vec![
-                // dev build: target/debug/toshik-babe-engine -> ../../packages/backend/src/index.ts
-                dir.join("../../../packages/backend/src/index.ts"),
-                dir.join("../../../../packages/backend/src/index.ts"),
-                dir.join("../../../../../packages/backend/src/index.ts"),
-            ]
-        } else {
-            vec![]
-        };
+/// `/version` of the running backend, cached on its launch.
+async fn fetch_backend_version(
+    state: &BackendProcess,
+    client: &BackendClient,
+) -> Result<String, String> {
+    #[derive(Deserialize)]
+    struct VersionResponse {
+        version: String,
+    }
 
-        let mut found: Option<std::path::PathBuf> = None;
-        for candidate in &candidates {
-            if let Ok(canonical) = candidate.canonicalize() {
-                found = Some(canonical);
-                break;
-            }
+    let (addr, run_id) = {
+        let guard = state.lock_reaped()?;
+        let launch = guard.launch.as_ref().ok_or("Backend is not running")?;
+        if let Some(ref version) = launch.version {
+            return Ok(version.clone());
         }
+        (
+            SocketAddr::new(launch.host, launch.port),
+            launch.run_id.clone(),
+        )
+    };
+
+    let request = client
+        .request(
+            reqwest::Method::GET,
+            reqwest::Url::parse(&format!("{}/version", net::base_url(addr)))
+                .map_err(|e| e.to_string())?,
+        )
+        .build()
+        .map_err(|e| e.to_string())?;
+    let response: VersionResponse = client
+        .send(request)
+        .await
+        .and_then(|r| r.error_for_status())
+        .map_err(|e| format!("Failed to query backend version: {e}"))?
+        .json()
+        .await
+        .map_err(|e| format!("Invalid /version response: {e}"))?;
+
+    // Only cache against the launch we asked; the backend may have restarted meanwhile.
+    state.update_launch(&run_id, |launch| {
+        launch.version = Some(response.version.clone())
+    });
+    Ok(response.version)
+}
+
+/// Tauri command: perform an HTTP request against the running backend from Rust.
+///
+/// Lets the webview reach the backend without tripping CORS/mixed-content rules in packaged
+/// builds. Only paths on the local backend are reachable. At most `TOSHIK_PROXY_CONCURRENCY`
+/// (default 16) calls run at once; the rest wait up to `TOSHIK_PROXY_QUEUE_TIMEOUT_MS`.
+/// Bodies over `TOSHIK_PROXY_MAX_RESPONSE_BYTES` (default 10 MiB) fail with
+/// `ProxyResponseTooLarge` rather than being sent over IPC.
+#[tauri::command]
+async fn proxy_backend(
+    state: State<'_, BackendProcess>,
+    client: State<'_, BackendClient>,
+    limit: State<'_, proxy::ProxyLimit>,
+    method: String,
+    path: String,
+    body: Option<String>,
+    headers: Option<HashMap<String, String>>,
+) -> Result<proxy::ProxyResponse, String> {
+    let addr = state
+        .lock_reaped()?
+        .running_addr()
+        .ok_or("Backend is not running")?;
+    let _permit = limit.acquire().await?;
+    proxy::forward(&client, &limit, addr, &method, &path, body, headers).await
+}
+
+/// Tauri command: run `bun install` in the workspace, e.g. after an update changed the
+/// backend's dependencies. Returns once it has started; progress and the outcome arrive as
+/// `backend://install-log`, `backend://install-done` and `backend://install-failed`.
+/// Output is kept in `install.log` next to the backend log.
+#[tauri::command]
+fn install_backend_deps(
+    app: AppHandle,
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<(), String> {
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    let bun = bun_executable(&config)?;
+    let workspace = resolve::resolve_backend_script(config.script_path_mode)?
+        .workspace
+        .ok_or("Cannot locate the workspace root")?;
+    let log_path = config.log_path(&paths).with_file_name("install.log");
+    install::spawn(&app, bun, workspace, log_path, config.max_log_line)
+}
+
+/// Tauri command: run a one-off script from the workspace, e.g. a seed or an export, with
+/// `bun run <script> <args>` and return its exit code and output once it finishes.
+///
+/// Independent of the managed backend: it may run while the backend does and is not
+/// tracked in [`BackendProcess`]. `script` is relative to the workspace root and must stay
+/// inside it. The task is killed after `TOSHIK_TASK_TIMEOUT_MS`; each stream keeps at most
+/// `TOSHIK_MAX_TASK_OUTPUT_BYTES`.
+#[tauri::command]
+async fn run_backend_task(
+    config: State<'_, Mutex<LauncherConfig>>,
+    script: String,
+    args: Vec<String>,
+) -> Result<backend_task::TaskOutput, String> {
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    let bun = bun_executable(&config)?;
+    let workspace = resolve::resolve_backend_script(config.script_path_mode)?
+        .workspace
+        .ok_or("Cannot locate the workspace root")?;
+    let script = backend_task::script_path(&workspace, Path::new(&script))?;
+
+    let mut cmd = Command::new(bun);
+    cmd.arg("run")
+        .arg(&script)
+        .args(&args)
+        .current_dir(&workspace)
+        .envs(&config.settings.backend_env);
+    log::info!("Running backend task {}", script.display());
+    tauri::async_runtime::spawn_blocking(move || {
+        backend_task::run(cmd, config.task_timeout, config.max_task_output)
+    })
+    .await
+    .map_err(|e| format!("Backend task failed: {e}"))?
+}
+
+/// Tauri command: verify the bun toolchain by running `bun --version` and `bun --revision`.
+#[tauri::command]
+fn check_bun(config: State<'_, Mutex<LauncherConfig>>) -> Result<bun::BunInfo, String> {
+    let bun_path = config.lock().map_err(|e| e.to_string())?.bun_path.clone();
+    Ok(bun::check(bun_path.as_deref())?)
+}
+
+/// Tauri command: the run ID of the current backend launch, if one is running.
+#[tauri::command]
+fn current_run_id(state: State<'_, BackendProcess>) -> Result<Option<String>, String> {
+    let guard = state.lock_reaped()?;
+    Ok(guard.launch.as_ref().map(|launch| launch.run_id.clone()))
+}
 
-        // Fallback: try relative to CWD
-        if found.is_none() {
-            let cwd_candidate = std::path::PathBuf::from("packages/backend/src/index.ts");
-            if cwd_candidate.exists() {
-                found = Some(cwd_candidate.canonicalize().unwrap_or(cwd_candidate));
+/// Tauri command: the most recent lifecycle events (up to 100, oldest first), so a webview
+/// that mounted late can catch up on state it missed.
+#[tauri::command]
+fn recent_events(history: State<'_, EventHistory>) -> Vec<events::LauncherEvent> {
+    history.snapshot()
+}
+
+/// Tauri command: background threads the launcher is running (readiness watchers, the
+/// session heartbeat, log pruning, ...), oldest first.
+#[tauri::command]
+fn list_background_tasks(tasks: State<'_, TaskRegistry>) -> Vec<TaskInfo> {
+    tasks.list()
+}
+
+/// Tauri command: ask the background tasks called `name` to stop; returns how many were
+/// running. A task stops at its next check, not immediately.
+#[tauri::command]
+fn cancel_task(tasks: State<'_, TaskRegistry>, name: String) -> usize {
+    tasks.cancel(&name)
+}
+
+/// Snapshot returned by `backend_status`.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendStatus {
+    /// The process exists (or an attached backend is recorded).
+    running: bool,
+    /// The backend accepts connections; requests sent before this may fail.
+    ready: bool,
+    pid: Option<u32>,
+    port: Option<u16>,
+    run_id: Option<String>,
+    last_exit_code: Option<i32>,
+    last_stop_reason: Option<StopReason>,
+    /// Started by `start_backend_verbose`.
+    verbose: bool,
+    /// `run`, `x` or `direct` (`TOSHIK_BUN_INVOCATION`); `None` for attached backends.
+    invocation: Option<bun::Invocation>,
+    /// Why the last start failed, until a start succeeds.
+    last_error: Option<ErrorReport>,
+    /// Unix socket the backend also listens on, with `TOSHIK_TRANSPORT=both`.
+    socket: Option<String>,
+}
+
+/// Tauri command: where the spawned backend's PID and port are written (`<pid>\n<port>\n`);
+/// the file only exists while a backend the app started is running.
+#[tauri::command]
+fn backend_pid_file(paths: State<'_, AppPaths>) -> String {
+    paths.pid_file.display().to_string()
+}
+
+/// Tauri command: every path the backend script is looked for at, in search order, to show
+/// next to a "Cannot locate" error.
+#[tauri::command]
+fn script_search_paths() -> Vec<String> {
+    resolve::candidate_paths()
+        .iter()
+        .map(|path| path.display().to_string())
+        .collect()
+}
+
+/// Tauri command: every file and directory the launcher reads or writes, whether it exists
+/// and whether its directory is readable and writable, to pin down which path a
+/// restricted filesystem is blocking.
+#[tauri::command]
+fn validate_paths(
+    paths: State<'_, AppPaths>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<Vec<paths::PathStatus>, String> {
+    let config = config.lock().map_err(|e| e.to_string())?.clone();
+    let log_path = config.log_path(&paths);
+    let mut checked = match resolve::resolve_backend_script(config.script_path_mode) {
+        Ok(resolved) => {
+            let mut checked = vec![paths::PathStatus::check("script", &resolved.script)];
+            if let Some(ref env_file) = resolved.env_file {
+                checked.push(paths::PathStatus::check("envFile", env_file));
             }
+            checked
         }
+        Err(e) => vec![paths::PathStatus::unknown("script", e)],
+    };
+    let configured = [
+        ("backendEntry", config.backend_entry.as_deref()),
+        ("bunPath", config.bun_path.as_deref()),
+    ];
+    let files = [
+        ("log", log_path.as_path()),
+        ("hookLog", &log_path.with_file_name("hook.log")),
+        ("installLog", &log_path.with_file_name("install.log")),
+        ("settingsFile", &paths.settings_file),
+        ("saltFile", &paths.salt_file),
+        ("vaultFile", &paths.vault_file),
+        ("auditFile", &paths.audit_file),
+        ("pidFile", &paths.pid_file),
+        ("sessionFile", &paths.session_file),
+        ("timingsFile", &paths.timings_file),
+        ("socketDir", &paths.socket_dir),
+    ];
+    let configured = configured
+        .into_iter()
+        .filter_map(|(name, path)| Some((name, path?)));
+    checked.extend(
+        configured
+            .chain(files)
+            .map(|(name, path)| paths::PathStatus::check(name, path)),
+    );
+    Ok(checked)
+}
+
+/// A backend spawned by another copy of the app, as found by `detect_other_instance`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtherInstance {
+    pid: u32,
+    port: u16,
+    /// The port accepts connections, so `attach_backend` would work.
+    listening: bool,
+}
 
-        found.ok_or_else(|| "Cannot locate packages/backend/src/index.ts".to_string())?
+/// Tauri command: whether another copy of the app owns a running backend, going by the PID
+/// file it shares with this one, so the UI can offer to attach instead of starting a
+/// second backend. `None` when the file names this app's own backend, the one left by its
+/// previous session (`recover_previous_session` handles that) or a process that is gone.
+/// Liveness needs Unix; elsewhere the port has to accept connections.
+#[tauri::command]
+fn detect_other_instance(
+    paths: State<'_, AppPaths>,
+    state: State<'_, BackendProcess>,
+    previous: State<'_, PreviousSession>,
+    config: State<'_, Mutex<LauncherConfig>>,
+) -> Result<Option<OtherInstance>, String> {
+    let Some((pid, port)) = pidfile::read(&paths.pid_file) else {
+        return Ok(None);
     };
+    let previous_pid = previous
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .and_then(|session| session.pid);
+    let own = previous_pid == Some(pid) || {
+        let guard = state.lock_reaped()?;
+        let own = [guard.launch.as_ref(), guard.standby.as_ref()]
+            .into_iter()
+            .flatten()
+            .filter_map(|launch| launch.child.as_ref().map(Child::id))
+            .any(|child| child == pid);
+        own
+    };
+    if own || (cfg!(unix) && !pidfile::process_alive(pid)) {
+        return Ok(None);
+    }
+    let host = config.lock().map_err(|e| e.to_string())?.host;
+    let listening =
+        TcpStream::connect_timeout(&SocketAddr::new(host, port), ATTACH_TIMEOUT).is_ok();
+    if !cfg!(unix) && !listening {
+        return Ok(None);
+    }
+    log::info!("Another instance's backend (pid {pid}) is on port {port}, listening: {listening}");
+    Ok(Some(OtherInstance {
+        pid,
+        port,
+        listening,
+    }))
+}
 
-    log::info!(
-        "Starting backend on port {port}, script: {}, log: {}",
-        backend_script.display(),
-        log_path.display()
-    );
+/// Tauri command: drop the cached backend script location, e.g. after moving the workspace,
+/// so the next start resolves it afresh. Returns the path that was cached.
+#[tauri::command]
+fn invalidate_resolution_cache() -> Result<Option<String>, String> {
+    Ok(resolve::invalidate_cache()?.map(|path| path.display().to_string()))
+}
 
-    // Resolve .env path from workspace root (backend_script = <workspace>/packages/backend/src/index.ts)
-    let env_file = backend_script
-        .parent()
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .and_then(|p| p.parent())
-        .map(|root| root.join(".env"));
+/// Tauri command: backends that crashed this session (up to 20, oldest first), with the
+/// redacted environment each was spawned with and, when output was streamed, its last
+/// `TOSHIK_CRASH_LINES` lines.
+#[tauri::command]
+fn crash_history(state: State<'_, BackendProcess>) -> Result<Vec<CrashReport>, String> {
+    Ok(state.lock_reaped()?.crashes.iter().cloned().collect())
+}
+
+/// Tauri command: how long recent launches took to listen, pass their health check and
+/// finish warming up (up to 50, oldest first), kept across app sessions. Cold launches are
+/// the first of their session.
+#[tauri::command]
+fn startup_timings(
+    timings: State<'_, timings::StartupTimings>,
+) -> Result<Vec<timings::StartupTiming>, String> {
+    Ok(timings.list())
+}
+
+/// Tauri command: whether the backend is running, and how the previous one exited.
+#[tauri::command]
+fn backend_status(state: State<'_, BackendProcess>) -> Result<BackendStatus, String> {
+    Ok(status(&*state.lock_reaped()?))
+}
 
-    let mut cmd = Command::new("bun");
-    cmd.arg("run");
+/// Tauri command: bring the recorded state back in line with reality, e.g. after the
+/// machine woke from sleep. A child that died meanwhile is reaped (`backend://stopped`),
+/// and a live one is probed once: `ready` follows the health check, announced with
+/// `backend://ready` or `backend://not-ready` when it changes. Returns the resulting status.
+#[tauri::command]
+async fn reconcile_backend(
+    app: AppHandle,
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    client: State<'_, BackendClient>,
+) -> Result<BackendStatus, String> {
+    let probe = {
+        let before = state
+            .inner
+            .lock()
+            .map_err(|e| e.to_string())?
+            .launch
+            .as_ref()
+            .map(|launch| launch.run_id.clone());
+        let guard = state.lock_reaped()?;
+        match (&guard.launch, before) {
+            (Some(launch), _) => Some((
+                launch.run_id.clone(),
+                SocketAddr::new(launch.host, launch.port),
+                launch.ready,
+            )),
+            (None, Some(run_id)) => {
+                let reason = guard.last_stop_reason.unwrap_or(StopReason::Exited);
+                drop(guard);
+                log::info!("Backend (run_id={run_id}) is gone, found while reconciling");
+                app.state::<TaskRegistry>().cancel_run(&run_id);
+                events::emit_as(
+                    &app,
+                    events::STOPPED,
+                    reason.severity(),
+                    events::StoppedPayload {
+                        run_id: Some(run_id),
+                        reason,
+                    },
+                );
+                None
+            }
+            (None, None) => None,
+        }
+    };
 
-    if let Some(ref env_path) = env_file {
-        if env_path.exists() {
-            cmd.arg(format!("--env-file={}", env_path.display()));
+    if let Some((run_id, addr, was_ready)) = probe {
+        let check = config
+            .lock()
+            .map_err(|e| e.to_string())?
+            .health_check
+            .clone();
+        let healthy = ready::wait_healthy(&client, addr, &check, Duration::ZERO).await;
+        let ready = healthy.is_ok();
+        if ready != was_ready && state.update_launch(&run_id, |launch| launch.ready = ready) {
+            match healthy {
+                Ok(()) => events::emit(
+                    &app,
+                    events::READY,
+                    events::ReadyPayload {
+                        run_id,
+                        port: addr.port(),
+                    },
+                ),
+                Err(error) => {
+                    log::warn!(
+                        "Backend (run_id={run_id}) no longer passes its health check: {error}"
+                    );
+                    events::emit(
+                        &app,
+                        events::NOT_READY,
+                        events::RunErrorPayload {
+                            run_id,
+                            error,
+                            endpoint: None,
+                        },
+                    );
+                }
+            }
         }
     }
+    Ok(status(&*state.lock_reaped()?))
+}
+
+fn status(guard: &BackendState) -> BackendStatus {
+    let launch = guard.launch.as_ref();
+    BackendStatus {
+        running: launch.is_some(),
+        ready: launch.is_some_and(|launch| launch.ready),
+        pid: launch.and_then(|launch| launch.child.as_ref().map(Child::id)),
+        port: launch.map(|launch| launch.port),
+        run_id: launch.map(|launch| launch.run_id.clone()),
+        last_exit_code: guard.last_exit_code,
+        last_stop_reason: guard.last_stop_reason,
+        verbose: launch.is_some_and(|launch| launch.verbose),
+        invocation: launch.and_then(|launch| launch.invocation),
+        last_error: guard.last_error.clone(),
+        socket: launch
+            .and_then(|launch| launch.socket.as_ref())
+            .map(|socket| socket.display().to_string()),
+    }
+}
+
+/// Where the running backend can be reached, as returned by `backend_endpoints`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BackendEndpoints {
+    /// `ip:port`.
+    tcp: String,
+    /// Unix socket path, with `TOSHIK_TRANSPORT=both`.
+    socket: Option<String>,
+}
 
-    let child = cmd
-        .arg(&backend_script)
-        .arg("--port")
-        .arg(port.to_string())
-        .stdout(Stdio::from(log_file))
-        .stderr(Stdio::from(log_file_err))
-        .spawn()
-        .map_err(|e| format!("Failed to spawn bun backend: {e}"))?;
+/// Tauri command: the TCP address and (with `TOSHIK_TRANSPORT=both`) Unix socket of the
+/// running backend; `None` when nothing is running.
+#[tauri::command]
+fn backend_endpoints(state: State<'_, BackendProcess>) -> Result<Option<BackendEndpoints>, String> {
+    let guard = state.lock_reaped()?;
+    Ok(guard.launch.as_ref().map(|launch| BackendEndpoints {
+        tcp: SocketAddr::new(launch.host, launch.port).to_string(),
+        socket: launch
+            .socket
+            .as_ref()
+            .map(|socket| socket.display().to_string()),
+    }))
+}
 
-    let mut guard = state.0.lock().map_err(|e| e.to_string())?;
-    *guard = Some(child);
+/// Everything `status_snapshot` gathers for a bug report.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct StatusSnapshot {
+    status: BackendStatus,
+    crash_history: Vec<CrashReport>,
+    /// Last lines of the backend log, oldest first; empty if it can't be read.
+    log_lines: Vec<String>,
+    log_stats: Option<logs::LogStats>,
+    config: LauncherConfig,
+    /// `None` until bun has been run this session.
+    bun_version: Option<bun::BunVersion>,
+}
 
-    Ok(port)
+/// Tauri command: status, crash history, the last `log_lines` (default 200) log lines, log
+/// sizes, the redacted effective config and bun version in one object, for bug reports.
+#[tauri::command]
+fn status_snapshot(
+    state: State<'_, BackendProcess>,
+    config: State<'_, Mutex<LauncherConfig>>,
+    paths: State<'_, AppPaths>,
+    log_lines: Option<usize>,
+) -> Result<StatusSnapshot, String> {
+    let config = config.lock().map_err(|e| e.to_string())?.redacted();
+    let (status, crash_history) = {
+        let guard = state.lock_reaped()?;
+        (status(&guard), guard.crashes.iter().cloned().collect())
+    };
+    let log_path = config.log_path(&paths);
+    let log_lines = fs::File::open(&log_path)
+        .and_then(|mut file| {
+            follow::tail_lines(&mut file, log_lines.unwrap_or(200), config.max_log_line)
+        })
+        .map(|(lines, _)| {
+            lines
+                .iter()
+                .map(|line| String::from_utf8_lossy(line).trim_end().to_string())
+                .collect()
+        })
+        .unwrap_or_else(|e| {
+            log::warn!("Failed to read {} for a snapshot: {e}", log_path.display());
+            Vec::new()
+        });
+    Ok(StatusSnapshot {
+        status,
+        crash_history,
+        log_lines,
+        log_stats: logs::stats(&log_path).ok(),
+        config,
+        bun_version: bun::detected_version(),
+    })
+}
+
+/// Tauri command: why the last attempt to start the backend failed, with when; `None` once
+/// a start has succeeded since.
+#[tauri::command]
+fn last_error(state: State<'_, BackendProcess>) -> Result<Option<ErrorReport>, String> {
+    Ok(state.lock_reaped()?.last_error.clone())
+}
+
+/// Tauri command: the `ip:port` pairs the backend process is actually listening on, asked
+/// from the OS rather than trusted from its flags or output. Empty when nothing is running,
+/// for attached backends (no known PID) and on platforms without the query.
+#[tauri::command]
+fn backend_listen_addrs(state: State<'_, BackendProcess>) -> Result<Vec<String>, String> {
+    let pid = state
+        .lock_reaped()?
+        .launch
+        .as_ref()
+        .and_then(|launch| launch.child.as_ref().map(Child::id));
+    let Some(pid) = pid else {
+        return Ok(Vec::new());
+    };
+    let addrs = sockets::listen_addrs(pid)
+        .map_err(|e| format!("Failed to query sockets of pid {pid}: {e}"))?;
+    Ok(addrs.iter().map(SocketAddr::to_string).collect())
+}
+
+/// Which ports of the scanned range can be bound, as returned by `port_range_status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct PortRangeStatus {
+    free: Vec<u16>,
+    occupied: Vec<OccupiedPort>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OccupiedPort {
+    port: u16,
+    /// The listening process, when the OS lets us see it (Linux, same user).
+    pid: Option<u32>,
+    process: Option<String>,
+}
+
+/// Tauri command: probe every port of 3001–3010 with a bind on the configured host, without
+/// starting anything, so the UI can warn before a start that would fail.
+#[tauri::command]
+fn port_range_status(config: State<'_, Mutex<LauncherConfig>>) -> Result<PortRangeStatus, String> {
+    let host = config.lock().map_err(|e| e.to_string())?.host;
+    let (free, taken): (Vec<u16>, Vec<u16>) =
+        PORT_RANGE.partition(|&port| TcpListener::bind((host, port)).is_ok());
+    let owners = sockets::port_owners(&taken);
+    let occupied = taken
+        .into_iter()
+        .map(|port| {
+            let pid = owners.get(&port).copied();
+            OccupiedPort {
+                port,
+                pid,
+                process: pid.and_then(sockets::process_name),
+            }
+        })
+        .collect();
+    Ok(PortRangeStatus { free, occupied })
+}
+
+/// Tauri command: whether the backend is running and has passed its readiness probe.
+#[tauri::command]
+fn backend_ready(state: State<'_, BackendProcess>) -> Result<bool, String> {
+    let guard = state.lock_reaped()?;
+    Ok(guard.launch.as_ref().is_some_and(|launch| launch.ready))
+}
+
+/// Tauri command: the last `limit` (default 100) entries of the process-control audit log,
+/// oldest first.
+#[tauri::command]
+fn read_audit_log(audit: State<'_, AuditLog>, limit: Option<usize>) -> Result<Vec<Value>, String> {
+    audit
+        .read(limit.unwrap_or(100))
+        .map_err(|e| format!("Failed to read audit log: {e}"))
+}
+
+/// Tauri command: gracefully stop the backend, then exit the app.
+///
+/// Quitting through here makes the shutdown order deterministic instead of depending on
+/// window-close events. Calling it again while shutdown is in progress does nothing.
+#[tauri::command]
+async fn shutdown_all(app: AppHandle) {
+    if begin_shutdown_blocking(&app).await {
+        app.exit(0);
+    }
+}
+
+/// Tauri command: gracefully stop the backend, then restart the whole app.
+///
+/// The backend is stopped before `restart` so it can't outlive the old process. Like
+/// `shutdown_all`, calls made while shutdown is in progress do nothing.
+#[tauri::command]
+async fn relaunch_app(app: AppHandle) {
+    if begin_shutdown_blocking(&app).await {
+        log::info!("Relaunching the app");
+        app.restart();
+    }
+}
+
+/// [`begin_shutdown`] on a blocking thread, so the window stays responsive while the
+/// backend gets its grace periods. If that thread dies, the app still goes down; the exit
+/// event then stops the backend.
+async fn begin_shutdown_blocking<R: Runtime>(app: &AppHandle<R>) -> bool {
+    let app = app.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        begin_shutdown(&app, &app.state::<BackendProcess>())
+    })
+    .await
+    .unwrap_or_else(|e| {
+        log::warn!("Shutdown task failed: {e}");
+        true
+    })
+}
+
+/// Mark the app as shutting down and stop the backend (and any drain standby), leaving a
+/// detached one running. Returns
+/// `false` if shutdown had already begun, so the caller should do nothing.
+fn begin_shutdown<R: Runtime>(app: &AppHandle<R>, state: &BackendProcess) -> bool {
+    if state.shutting_down.swap(true, Ordering::SeqCst) {
+        log::info!("Shutdown already in progress");
+        return false;
+    }
+    release_detached(app, state);
+    state.discard_standby();
+    stop_backend_process(app, state, StopReason::AppExit);
+    true
+}
+
+/// Run the Stronghold self-test in the background, record the result and announce it.
+fn check_secure_storage<R: Runtime>(app: &AppHandle<R>, salt_file: PathBuf) {
+    let tasks = app.state::<TaskRegistry>();
+    let app = app.clone();
+    let spawned = tasks.spawn("stronghold-check", None, move |_| {
+        let result = secure_storage::self_test(&salt_file);
+        let status = match result {
+            Ok(()) => StorageStatus::Ready,
+            Err(ref e) => StorageStatus::Error(e.clone()),
+        };
+        if let Ok(mut health) = app.state::<StorageHealth>().0.lock() {
+            *health = status;
+        }
+        match result {
+            Ok(()) => events::emit(&app, events::STRONGHOLD_READY, ()),
+            Err(error) => {
+                log::error!("Secure storage self-test failed: {error}");
+                events::emit(
+                    &app,
+                    events::STRONGHOLD_ERROR,
+                    events::StrongholdErrorPayload { error },
+                );
+            }
+        }
+    });
+    if let Err(e) = spawned {
+        log::warn!("Failed to start secure storage check: {e}");
+    }
+}
+
+/// Tauri command: whether secure storage passed its startup self-test
+/// (`pending`/`ready`/`error`), so secret-dependent features can be gated on it.
+#[tauri::command]
+fn stronghold_status(health: State<'_, StorageHealth>) -> Result<StorageStatus, String> {
+    Ok(health.0.lock().map_err(|e| e.to_string())?.clone())
 }
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    logger::init();
+    let config = LauncherConfig::from_env();
+    let client = BackendClient::new(&config.http).expect("could not build HTTP client");
+    let proxy_limit = proxy::ProxyLimit::new(&config.http);
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
         .plugin(backend_cleanup_plugin())
-        .manage(BackendProcess(Mutex::new(None)))
-        .invoke_handler(tauri::generate_handler![start_backend])
+        .manage(BackendProcess::default())
+        .manage(Mutex::new(config))
+        .manage(client)
+        .manage(proxy_limit)
+        .manage(EventHistory::default())
+        .manage(LogFollower::default())
+        .manage(PreviousSession::default())
+        .manage(TaskRegistry::default())
+        .manage(WarmPort::default())
+        .manage(ScheduledRestart::default())
+        .invoke_handler(tauri::generate_handler![
+            start_backend,
+            start_backend_verbose,
+            stop_backend,
+            restart_backend,
+            schedule_restart,
+            cancel_scheduled_restart,
+            upgrade_backend,
+            drain_backend,
+            restart_required,
+            effective_config,
+            config_sources,
+            attach_backend,
+            recover_previous_session,
+            backend_url,
+            backend_port,
+            frontend_ready,
+            shutdown_all,
+            relaunch_app,
+            current_run_id,
+            backend_status,
+            crash_history,
+            startup_timings,
+            backend_endpoints,
+            status_snapshot,
+            backend_pid_file,
+            script_search_paths,
+            validate_paths,
+            flush_logs,
+            run_backend_task,
+            generate_config_template,
+            detect_other_instance,
+            invalidate_resolution_cache,
+            last_error,
+            backend_ready,
+            backend_listen_addrs,
+            port_range_status,
+            recent_events,
+            reconcile_backend,
+            list_background_tasks,
+            cancel_task,
+            check_bun,
+            install_backend_deps,
+            stronghold_status,
+            read_audit_log,
+            backend_version,
+            verify_backend_version,
+            proxy_backend,
+            set_log_path,
+            get_log_path,
+            open_backend_in_browser,
+            set_launcher_log_level,
+            set_backend_env,
+            unset_backend_env,
+            get_backend_env,
+            log_stats,
+            follow_backend_log,
+            unfollow_backend_log
+        ])
         .setup(|app| {
-            // Stronghold needs a salt file for argon2 key derivation.
-            let salt_path = app
-                .path()
-                .app_local_data_dir()
-                .expect("could not resolve app local data path")
-                .join("stronghold-salt.txt");
-            app.handle()
-                .plugin(tauri_plugin_stronghold::Builder::with_argon2(&salt_path).build())?;
+            let paths = AppPaths::resolve(app.handle()).expect("could not resolve app paths");
+
+            // A key that can't be derived is left empty, which Stronghold rejects, so the
+            // webview's `initialize` fails instead of the plugin panicking.
+            let salt_file = paths.salt_file.clone();
+            let stronghold = tauri_plugin_stronghold::Builder::new(move |password| {
+                secure_storage::derive_key(password, &salt_file).unwrap_or_else(|e| {
+                    log::error!("Failed to open the vault: {e}");
+                    Vec::new()
+                })
+            });
+            app.handle().plugin(stronghold.build())?;
+            app.manage(StorageHealth(Mutex::new(StorageStatus::Pending)));
+            check_secure_storage(app.handle(), paths.salt_file.clone());
+
+            let settings = Settings::load(&paths.settings_file);
+            if let Some(ref raw) = settings.launcher_log_level {
+                match logger::parse_level(raw) {
+                    Ok(level) => logger::set_level(level),
+                    Err(e) => log::warn!("Ignoring launcherLogLevel in settings: {e}"),
+                }
+            }
+            if let Ok(mut config) = app.state::<Mutex<LauncherConfig>>().lock() {
+                config.settings = settings;
+            }
+            app.manage(AuditLog::new(paths.audit_file.clone()));
+            app.manage(timings::StartupTimings::load(paths.timings_file.clone()));
+            pidfile::clear_stale(&paths.pid_file);
+            if let Some(previous) = session::read(&paths.session_file) {
+                log::info!(
+                    "Found session state from a previous run (run_id={}, port {})",
+                    previous.run_id,
+                    previous.port
+                );
+                *app.state::<PreviousSession>()
+                    .0
+                    .lock()
+                    .map_err(|e| e.to_string())? = Some(previous);
+            }
+            spawn_heartbeat(app.handle(), paths.session_file.clone());
+            app.manage(paths);
+
+            let config = app
+                .state::<Mutex<LauncherConfig>>()
+                .lock()
+                .ok()
+                .map(|c| c.clone());
+            let config = config.inspect(|config| {
+                let scans = config.force_port.is_none() && !config.ephemeral_port;
+                if config.warm_ports && scans && !config.autostart {
+                    warm_port(app.handle(), config.host);
+                }
+            });
+            if let Some(config) = config.filter(|config| config.autostart) {
+                let app = app.handle().clone();
+                tauri::async_runtime::spawn(async move {
+                    if let Err(error) = launch_blocking(&app, config, None).await {
+                        log::error!("Failed to autostart backend: {error}");
+                        events::emit(
+                            &app,
+                            events::START_FAILED,
+                            events::StartFailedPayload { error },
+                        );
+                    }
+                });
+            }
             Ok(())
         })
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn launch(child: Child) -> Launch {
+        Launch {
+            child: Some(child),
+            run_id: "test-run".into(),
+            host: net::loopback(false),
+            port: 3001,
+            version: None,
+            log_streams: None,
+            ready: false,
+            verbose: false,
+            shutdown: ShutdownTimeouts::default(),
+            env: BTreeMap::from([("LOG_LEVEL".into(), "debug".into())]),
+            invocation: Some(bun::Invocation::Run),
+            pid_file: None,
+            socket: None,
+            started_at: 0,
+            detached: false,
+            shutdown_token: None,
+            container: None,
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn detached_child_leads_its_own_session() {
+        let mut cmd = Command::new("sleep");
+        cmd.arg("5");
+        detach(&mut cmd);
+        let mut child = cmd.spawn().unwrap();
+
+        // SAFETY: getsid(2) only reads process attributes.
+        let sid = unsafe { libc::getsid(child.id() as libc::pid_t) };
+        assert_eq!(sid, child.id() as libc::pid_t);
+        assert_ne!(sid, unsafe { libc::getsid(0) });
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn log_path_is_reported_without_the_opener_plugin() {
+        let app = tauri::test::mock_app();
+        let data_dir = std::env::temp_dir().join("toshik-no-opener");
+        app.manage(AppPaths::layout(&data_dir, &data_dir.join("local")));
+        app.manage(Mutex::new(LauncherConfig::default()));
+        app.manage(BackendProcess::default());
+
+        let path = get_log_path(app.state(), app.state()).unwrap();
+        assert!(path.ends_with("backend.log"), "{path}");
+        let opened = open_backend_in_browser(app.handle().clone(), app.state());
+        assert_eq!(opened, Err(BackendError::OpenerUnavailable.to_string()));
+    }
+
+    #[test]
+    fn workers_are_passed_in_the_configured_variables() {
+        let mut config = LauncherConfig {
+            workers_env: "APP_WORKERS".into(),
+            uv_threadpool: true,
+            ..LauncherConfig::default()
+        };
+        let env = HashMap::from([("APP_WORKERS".to_string(), "1".to_string())]);
+
+        let env = with_workers(&config, Some(4), Some(env)).unwrap().unwrap();
+        assert_eq!(env["APP_WORKERS"], "4");
+        assert_eq!(env["UV_THREADPOOL_SIZE"], "4");
+        assert_eq!(with_workers(&config, None, None).unwrap(), None);
+        assert!(with_workers(&config, Some(0), None).is_err());
+
+        config.auto_workers = true;
+        let env = with_workers(&config, None, None).unwrap().unwrap();
+        assert!(env["APP_WORKERS"].parse::<usize>().unwrap() >= 1);
+    }
+
+    /// What `lock_reaped` does, on a bare state.
+    fn reap(state: &mut BackendState) {
+        if let Some(report) = state.reap_if_exited().and_then(Reaped::finish) {
+            state.record_crash(report);
+        }
+    }
+
+    #[test]
+    fn reap_is_a_no_op_without_a_backend() {
+        let mut state = BackendState::default();
+        reap(&mut state);
+        assert!(state.launch.is_none());
+        assert_eq!(state.last_exit_code, None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reap_keeps_a_running_backend() {
+        let child = Command::new("sleep").arg("5").spawn().unwrap();
+        let mut state = BackendState {
+            launch: Some(launch(child)),
+            ..BackendState::default()
+        };
+
+        reap(&mut state);
+
+        assert_eq!(state.running_port(), Some(3001));
+        let mut child = state.launch.take().unwrap().child.unwrap();
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn reap_clears_an_exited_backend_and_records_its_exit_code() {
+        let child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let mut state = BackendState {
+            launch: Some(launch(child)),
+            ..BackendState::default()
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while state.launch.is_some() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            reap(&mut state);
+        }
+
+        assert!(state.launch.is_none());
+        assert_eq!(state.last_exit_code, Some(3));
+        assert_eq!(state.last_stop_reason, Some(StopReason::Crashed));
+        let crash = state.crashes.back().expect("crash recorded");
+        assert_eq!(
+            (crash.run_id.as_str(), crash.exit_code),
+            ("test-run", Some(3))
+        );
+        assert_eq!(crash.env["LOG_LEVEL"], "debug");
+    }
+
+    /// Whether `pid` still exists (a reaped child no longer does).
+    #[cfg(unix)]
+    fn process_exists(pid: u32) -> bool {
+        // SAFETY: signal 0 only checks for existence and permission.
+        unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exit_during_startup_kills_the_stored_child() {
+        let state = BackendProcess::default();
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+        state.store_launch(launch(child)).unwrap();
+
+        // The exit handler runs while the readiness watcher is still polling.
+        state.shutting_down.store(true, Ordering::SeqCst);
+        assert_eq!(
+            state.terminate(StopReason::AppExit).as_deref(),
+            Some("test-run")
+        );
+
+        assert!(!process_exists(pid));
+        // The watcher finds the slot cleared and leaves it alone.
+        assert!(!state.update_launch("test-run", |launch| launch.ready = true));
+        assert!(state.lock_reaped().unwrap().launch.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn launch_spawned_after_exit_began_is_not_stored() {
+        let state = BackendProcess::default();
+        state.shutting_down.store(true, Ordering::SeqCst);
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        let pid = child.id();
+
+        assert!(state.store_launch(launch(child)).is_err());
+
+        assert!(!process_exists(pid));
+        assert!(state.lock_reaped().unwrap().launch.is_none());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn successful_start_clears_the_last_error() {
+        let state = BackendProcess::default();
+        state.record_error("bun was not found on PATH");
+        let error = state.lock_reaped().unwrap().last_error.clone().unwrap();
+        assert_eq!(error.message, "bun was not found on PATH");
+
+        let child = Command::new("sleep").arg("30").spawn().unwrap();
+        state.store_launch(launch(child)).unwrap();
+        assert!(state.lock_reaped().unwrap().last_error.is_none());
+        state.terminate(StopReason::UserRequested);
+    }
+}
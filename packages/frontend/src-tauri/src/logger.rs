@@ -0,0 +1,67 @@
+use std::io::Write;
+
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// Level used until `settings.json` is loaded, and when it doesn't set one.
+pub(crate) const DEFAULT_LEVEL: LevelFilter = LevelFilter::Info;
+
+/// The launcher's own log output: timestamped lines on stderr, filtered by `log`'s global
+/// max level so `set_launcher_log_level` can change it while the app runs.
+struct LauncherLogger;
+
+static LOGGER: LauncherLogger = LauncherLogger;
+
+impl Log for LauncherLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let ts = chrono::Local::now().format("%Y-%m-%dT%H:%M:%S%.3f");
+        let _ = writeln!(
+            std::io::stderr().lock(),
+            "{ts} {:<5} {}: {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+    }
+
+    fn flush(&self) {
+        let _ = std::io::stderr().flush();
+    }
+}
+
+/// Install the logger at [`DEFAULT_LEVEL`]. Does nothing if a logger is already set.
+pub(crate) fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(DEFAULT_LEVEL);
+    }
+}
+
+/// Parse a level name (`off`, `error`, `warn`, `info`, `debug`, `trace`, any case).
+pub(crate) fn parse_level(raw: &str) -> Result<LevelFilter, String> {
+    raw.trim().parse().map_err(|_| {
+        format!("Invalid log level {raw:?}, expected off, error, warn, info, debug or trace")
+    })
+}
+
+/// Change the level from now on.
+pub(crate) fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn level_names_parse_case_insensitively() {
+        assert_eq!(parse_level("DEBUG"), Ok(LevelFilter::Debug));
+        assert_eq!(parse_level(" off "), Ok(LevelFilter::Off));
+        assert!(parse_level("verbose").is_err());
+    }
+}
@@ -0,0 +1,1128 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, TrySendError};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use chrono::{Local, SecondsFormat};
+use serde::{Serialize, Serializer};
+use serde_json::{Map, Value};
+use tauri::{AppHandle, Runtime};
+
+use crate::events;
+use crate::ready::{self, AnnouncedPort};
+use crate::syslog;
+
+/// Emitted for every line `follow_backend_log` reads from the log file.
+pub(crate) const LOG_EVENT: &str = "backend://log";
+
+/// Emitted with the lines the backend wrote while log streaming is enabled, batched so a
+/// chatty backend can't flood the webview with one event per line.
+pub(crate) const LOG_BATCH_EVENT: &str = "backend://log-batch";
+
+/// Emitted after a batch when lines were dropped instead of emitted, because the webview
+/// fell behind or the batch was over its size cap.
+pub(crate) const LOG_TRUNCATED_EVENT: &str = "backend://log-truncated";
+
+/// A batch is emitted this long after its first line at the latest...
+const BATCH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// ...or as soon as it holds this many lines.
+const BATCH_LINES: usize = 200;
+
+/// Line bytes one batch may carry; lines beyond that are dropped.
+const BATCH_BYTES: usize = 256 * 1024;
+
+/// Lines waiting for the batcher before the readers start dropping them.
+pub(crate) const QUEUE_CAPACITY: usize = 4 * BATCH_LINES;
+
+/// Default cap on a single backend log line.
+pub(crate) const DEFAULT_MAX_LINE: usize = 1024 * 1024;
+
+/// Default ceiling on all log files in the log directory together.
+pub(crate) const DEFAULT_MAX_LOG_DIR: u64 = 50 * 1024 * 1024;
+
+/// Default number of recent backend lines attached to a crash report.
+pub(crate) const DEFAULT_CRASH_LINES: usize = 500;
+
+/// Names of the launcher's log files; each may have rotated `<name>.<suffix>` siblings.
+const LOG_FILE_NAMES: &[&str] = &["backend.log", "launcher.log", "hook.log", "install.log"];
+
+/// Appended to a line cut off at the cap.
+const TRUNCATED_MARKER: &[u8] = "…[truncated]".as_bytes();
+
+/// Windows tools like to start their output with one.
+const UTF8_BOM: &[u8] = b"\xef\xbb\xbf";
+
+/// How the backend's own output is formatted, and so how it is written to `backend.log`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogFormat {
+    /// Write backend lines verbatim.
+    Text,
+    /// The backend prints JSON lines. Each is merged with `source`/`stream`/`runId` fields
+    /// so `backend.log` becomes one JSON log shared with the launcher's own entries; lines
+    /// that fail to parse are kept under a `raw` field.
+    Json,
+}
+
+impl LogFormat {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "text" => Some(Self::Text),
+            "json" => Some(Self::Json),
+            _ => None,
+        }
+    }
+}
+
+/// `TOSHIK_LOG_PREFIX` template used when the variable is just switched on.
+const DEFAULT_PREFIX: &str = "{ts} [{stream}] ";
+
+/// Text put before each backend line written to the file (`TOSHIK_LOG_PREFIX`): `{ts}`
+/// becomes an ISO 8601 local timestamp with milliseconds, `{stream}` becomes `out` or `err`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct LinePrefix(String);
+
+impl LinePrefix {
+    /// `1`/`true`/`yes` select the default `{ts} [{stream}] `; `0`/`false`/`no` or blank
+    /// mean no prefix; anything else is the template itself.
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "0" | "false" | "no" => None,
+            "1" | "true" | "yes" => Some(Self(DEFAULT_PREFIX.into())),
+            _ => Some(Self(raw.to_string())),
+        }
+    }
+
+    fn render(&self, stream: &str) -> String {
+        let tag = match stream {
+            "stdout" => "out",
+            "stderr" => "err",
+            other => other,
+        };
+        let prefix = self.0.replace("{stream}", tag);
+        if !prefix.contains("{ts}") {
+            return prefix;
+        }
+        let now = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+        prefix.replace("{ts}", &now)
+    }
+}
+
+impl Serialize for LinePrefix {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// Banner written when `TOSHIK_LOG_BANNER` is set to `1` without a template of its own.
+const DEFAULT_BANNER: &str = "===== session {run_id} started {ts} port={port} =====";
+
+/// Line written to the log at each launch, before the backend's output, so runs can be told
+/// apart in an appended log (`TOSHIK_LOG_BANNER`). `{run_id}`, `{port}` and `{ts}` (ISO 8601
+/// local time) are filled in.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SessionBanner(String);
+
+impl SessionBanner {
+    /// Same spellings as [`LinePrefix::parse`]: a flag selects the default banner, anything
+    /// else is the template.
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "" | "0" | "false" | "no" => None,
+            "1" | "true" | "yes" => Some(Self(DEFAULT_BANNER.into())),
+            _ => Some(Self(raw.trim_end_matches(['\r', '\n']).to_string())),
+        }
+    }
+
+    fn render(&self, run_id: &str, port: u16) -> String {
+        let banner = self
+            .0
+            .replace("{run_id}", run_id)
+            .replace("{port}", &port.to_string());
+        if !banner.contains("{ts}") {
+            return banner;
+        }
+        let now = Local::now().to_rfc3339_opts(SecondsFormat::Millis, false);
+        banner.replace("{ts}", &now)
+    }
+
+    /// Write the banner for launch `run_id`: as is in a text log, as the message of a
+    /// launcher record in a JSON one.
+    pub(crate) fn write(
+        &self,
+        log_file: &mut File,
+        format: LogFormat,
+        run_id: &str,
+        port: u16,
+    ) -> io::Result<()> {
+        let banner = self.render(run_id, port);
+        match format {
+            LogFormat::Text => writeln!(log_file, "{banner}"),
+            LogFormat::Json => write_launcher_line(log_file, format, run_id, &banner),
+        }
+    }
+}
+
+impl Serialize for SessionBanner {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.0)
+    }
+}
+
+/// How backend lines are written to the log file.
+#[derive(Debug, Clone)]
+pub(crate) struct LineOptions {
+    pub format: LogFormat,
+    /// Longer lines are cut to this many bytes and marked `…[truncated]`.
+    pub max_line: usize,
+    /// Only applied to `Text` lines; JSON records already carry their stream.
+    pub prefix: Option<LinePrefix>,
+    /// Rewrite lines with [`normalize_line`] before they are written or emitted.
+    pub normalize: bool,
+}
+
+impl LineOptions {
+    /// Plain lines, as the backend printed them.
+    pub(crate) fn raw(max_line: usize) -> Self {
+        Self {
+            format: LogFormat::Text,
+            max_line,
+            prefix: None,
+            normalize: false,
+        }
+    }
+}
+
+/// Where backend output is kept (`TOSHIK_LOG_SINK`). Launcher lines always go to the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogSink {
+    File,
+    /// The system log instead of the file; needs the reader threads, like `Json`.
+    Syslog,
+    Both,
+}
+
+impl LogSink {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "file" => Some(Self::File),
+            "syslog" => Some(Self::Syslog),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn file(self) -> bool {
+        self != Self::Syslog
+    }
+
+    pub(crate) fn syslog(self) -> bool {
+        self != Self::File
+    }
+}
+
+/// How `start_backend` opens `backend.log` (`TOSHIK_LOG_MODE=append|truncate`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum LogMode {
+    /// Keep adding to the file across sessions.
+    Append,
+    /// Start each session with an empty file, keeping the previous one as `<log>.prev`.
+    Truncate,
+}
+
+impl LogMode {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "append" => Some(Self::Append),
+            "truncate" => Some(Self::Truncate),
+            _ => None,
+        }
+    }
+}
+
+/// Move a non-empty `log` to `<log>.prev`, replacing an older one, before it is truncated
+/// for a new session.
+pub(crate) fn archive_previous(log: &Path) -> io::Result<()> {
+    match fs::metadata(log) {
+        Ok(metadata) if metadata.len() > 0 => {}
+        Ok(_) => return Ok(()),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    }
+    let mut prev = log.as_os_str().to_owned();
+    prev.push(".prev");
+    // Windows won't rename over an existing file.
+    let _ = fs::remove_file(&prev);
+    fs::rename(log, prev)
+}
+
+/// Payload of [`LOG_EVENT`].
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LogLinePayload<'a> {
+    /// Always `None`: a followed file may hold lines from several runs. Kept so the payload
+    /// has the same shape as a [`LogLine`] plus run ID.
+    pub run_id: Option<&'a str>,
+    /// `file`.
+    pub stream: &'a str,
+    pub line: String,
+}
+
+/// One line in a [`LOG_BATCH_EVENT`] (or an `install-log` event).
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct LogLine {
+    /// `stdout` or `stderr`.
+    pub stream: &'static str,
+    pub line: String,
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogBatchPayload<'a> {
+    run_id: &'a str,
+    lines: &'a [LogLine],
+}
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct LogTruncatedPayload<'a> {
+    run_id: &'a str,
+    dropped_lines: usize,
+    dropped_bytes: usize,
+}
+
+/// Lines dropped since the last batch was emitted.
+#[derive(Default)]
+pub(crate) struct Dropped {
+    lines: AtomicUsize,
+    bytes: AtomicUsize,
+}
+
+impl Dropped {
+    fn add(&self, line: &LogLine) {
+        self.lines.fetch_add(1, Ordering::Relaxed);
+        self.bytes.fetch_add(line.line.len(), Ordering::Relaxed);
+    }
+
+    fn take(&self) -> (usize, usize) {
+        (
+            self.lines.swap(0, Ordering::Relaxed),
+            self.bytes.swap(0, Ordering::Relaxed),
+        )
+    }
+}
+
+/// What the reader threads do with each line besides writing it to the file.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct StreamTaps {
+    pub log_sink: LogSink,
+    /// Watch stdout for the backend's ready line.
+    pub ready_line: bool,
+    /// Lines kept in memory for crash reports; 0 keeps none.
+    pub recent_lines: usize,
+}
+
+/// The last lines the backend printed on either stream, oldest first, shared by the
+/// reader threads.
+pub(crate) struct RecentLines {
+    capacity: usize,
+    lines: Mutex<VecDeque<LogLine>>,
+}
+
+impl RecentLines {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            lines: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub(crate) fn push(&self, line: LogLine) {
+        let mut lines = self.lines.lock().unwrap_or_else(PoisonError::into_inner);
+        if lines.len() == self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back(line);
+    }
+
+    pub(crate) fn snapshot(&self) -> Vec<LogLine> {
+        let lines = self.lines.lock().unwrap_or_else(PoisonError::into_inner);
+        lines.iter().cloned().collect()
+    }
+}
+
+/// Reader threads copying the backend's piped stdout/stderr into `backend.log`, and a
+/// batcher thread emitting the lines as `backend://log-batch` events.
+pub(crate) struct LogStreams {
+    stop: Arc<AtomicBool>,
+    /// The readers, then the batcher (which exits once they have).
+    threads: Vec<JoinHandle<()>>,
+    /// Filled from stdout's ready line; present when asked for and stdout is piped.
+    announced: Option<Arc<AnnouncedPort>>,
+    /// `None` when no lines are kept for crash reports.
+    recent: Option<Arc<RecentLines>>,
+    /// The readers' shared handle to the log file.
+    sink: Arc<Mutex<File>>,
+}
+
+impl LogStreams {
+    /// Start one reader per `(stream name, pipe)`. The readers are the only writers to the
+    /// log file while they run and share one handle behind a mutex, so stdout and stderr
+    /// lines never interleave mid-line. Lines are written as `options` says.
+    ///
+    /// Disk writes never wait for the webview: when the batcher's queue is full, lines are
+    /// dropped from the events only and reported with `backend://log-truncated`.
+    pub(crate) fn spawn<R: Runtime>(
+        app: &AppHandle<R>,
+        run_id: &str,
+        log_file: &File,
+        options: LineOptions,
+        taps: StreamTaps,
+        pipes: Vec<(&'static str, Box<dyn Read + Send>)>,
+    ) -> io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let sink = Arc::new(Mutex::new(log_file.try_clone()?));
+        let dropped = Arc::new(Dropped::default());
+        let (lines, queue) = mpsc::sync_channel(QUEUE_CAPACITY);
+        let StreamTaps {
+            log_sink,
+            ready_line,
+            recent_lines,
+        } = taps;
+        let recent = (recent_lines > 0).then(|| Arc::new(RecentLines::new(recent_lines)));
+        let announced = (ready_line && pipes.iter().any(|(stream, _)| *stream == "stdout"))
+            .then(|| Arc::new(AnnouncedPort::new()));
+        let mut threads = Vec::with_capacity(pipes.len() + 1);
+        for (stream, reader) in pipes {
+            let run_id = run_id.to_string();
+            let sink = Arc::clone(&sink);
+            let stop = Arc::clone(&stop);
+            let dropped = Arc::clone(&dropped);
+            let lines = lines.clone();
+            let options = options.clone();
+            let announced = announced.clone().filter(|_| stream == "stdout");
+            let recent = recent.clone();
+            let handle = thread::Builder::new()
+                .name(format!("backend-{stream}"))
+                .spawn(move || {
+                    let file = log_sink.file().then_some(&*sink);
+                    copy_lines(&run_id, stream, &options, reader, file, |line| {
+                        if log_sink.syslog() {
+                            syslog::send(stream, line);
+                        }
+                        if let Some(ref announced) = announced {
+                            if announced.is_pending() {
+                                if let Some(port) = ready::parse_ready_line(line) {
+                                    announced.announce(port);
+                                }
+                            }
+                        }
+                        let line = LogLine {
+                            stream,
+                            line: String::from_utf8_lossy(line).trim_end().to_string(),
+                        };
+                        if let Some(ref recent) = recent {
+                            recent.push(line.clone());
+                        }
+                        if stop.load(Ordering::SeqCst) {
+                            return;
+                        }
+                        if let Err(TrySendError::Full(line)) = lines.try_send(line) {
+                            dropped.add(&line);
+                        }
+                    });
+                    if let Some(announced) = announced {
+                        announced.close();
+                    }
+                })?;
+            threads.push(handle);
+        }
+        // Only the readers' senders may keep the batcher alive.
+        drop(lines);
+
+        let app = app.clone();
+        let run_id = run_id.to_string();
+        let batcher = thread::Builder::new()
+            .name("backend-log-batch".into())
+            .spawn(move || {
+                batch_lines(&queue, &dropped, |lines, (dropped_lines, dropped_bytes)| {
+                    if !lines.is_empty() {
+                        let payload = LogBatchPayload {
+                            run_id: &run_id,
+                            lines,
+                        };
+                        events::broadcast(&app, LOG_BATCH_EVENT, payload);
+                    }
+                    if dropped_lines > 0 {
+                        log::warn!("Dropped {dropped_lines} backend log lines from events");
+                        let payload = LogTruncatedPayload {
+                            run_id: &run_id,
+                            dropped_lines,
+                            dropped_bytes,
+                        };
+                        events::broadcast(&app, LOG_TRUNCATED_EVENT, payload);
+                    }
+                })
+            })?;
+        threads.push(batcher);
+        Ok(Self {
+            stop,
+            threads,
+            announced,
+            recent,
+            sink,
+        })
+    }
+
+    /// Where the port from the backend's ready line turns up, if one is watched for.
+    pub(crate) fn announced_port(&self) -> Option<Arc<AnnouncedPort>> {
+        self.announced.clone()
+    }
+
+    /// The last lines the backend printed, after giving the readers up to `drain` to finish
+    /// copying what an exited backend left in its pipes. Empty when none are kept.
+    pub(crate) fn recent_lines(&self, drain: Duration) -> Vec<LogLine> {
+        let Some(ref recent) = self.recent else {
+            return Vec::new();
+        };
+        let readers = &self.threads[..self.threads.len().saturating_sub(1)];
+        let deadline = Instant::now() + drain;
+        while !readers.iter().all(JoinHandle::is_finished) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(10));
+        }
+        recent.snapshot()
+    }
+
+    /// The log file the readers write to, for `flush_logs`. Each line goes to it as soon as
+    /// its newline is read, so syncing this handle leaves out only an unfinished line.
+    pub(crate) fn log_file(&self) -> Arc<Mutex<File>> {
+        Arc::clone(&self.sink)
+    }
+
+    /// Tell the readers to stop emitting events. They keep copying to disk until the pipes
+    /// close so the backend's last words still reach the log.
+    pub(crate) fn request_stop(&self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+
+    /// Wait up to `timeout` for the readers to drain and exit. Call after the process has
+    /// been terminated; a reader still blocked after that (e.g. a grandchild holding the
+    /// pipe open) is abandoned rather than hanging shutdown.
+    pub(crate) fn join(self, timeout: Duration) {
+        self.request_stop();
+        let deadline = Instant::now() + timeout;
+        for reader in self.threads {
+            while !reader.is_finished() && Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            if reader.is_finished() {
+                let _ = reader.join();
+            } else {
+                log::warn!(
+                    "Log reader {:?} did not finish within {timeout:?}, abandoning it",
+                    reader.thread().name()
+                );
+            }
+        }
+    }
+}
+
+/// Collect lines from `queue` into batches and hand each to `emit`, with the lines dropped
+/// since the previous one, until every sender is gone. A batch closes after
+/// [`BATCH_INTERVAL`] or [`BATCH_LINES`]; lines past [`BATCH_BYTES`] are dropped.
+pub(crate) fn batch_lines(
+    queue: &Receiver<LogLine>,
+    dropped: &Dropped,
+    mut emit: impl FnMut(&[LogLine], (usize, usize)),
+) {
+    let mut batch = Vec::new();
+    let mut bytes = 0;
+    let mut deadline = None;
+    loop {
+        let received = match deadline {
+            None => queue.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            Some(deadline) => queue.recv_timeout(deadline - Instant::now().min(deadline)),
+        };
+        let done = match received {
+            Ok(line) => {
+                let deadline = *deadline.get_or_insert_with(|| Instant::now() + BATCH_INTERVAL);
+                if bytes + line.line.len() > BATCH_BYTES {
+                    dropped.add(&line);
+                } else {
+                    bytes += line.line.len();
+                    batch.push(line);
+                }
+                if batch.len() < BATCH_LINES && Instant::now() < deadline {
+                    continue;
+                }
+                false
+            }
+            Err(RecvTimeoutError::Timeout) => false,
+            Err(RecvTimeoutError::Disconnected) => true,
+        };
+        let dropped = dropped.take();
+        if !batch.is_empty() || dropped.0 > 0 {
+            emit(&batch, dropped);
+        }
+        batch.clear();
+        bytes = 0;
+        deadline = None;
+        if done {
+            return;
+        }
+    }
+}
+
+/// Like `read_until(b'\n')`, but `buf` never grows past `max` bytes: the rest of an
+/// overlong line is consumed and dropped, except for its newline. Returns the bytes
+/// consumed (0 at EOF) and whether anything was dropped.
+pub(crate) fn read_line_capped(
+    reader: &mut impl BufRead,
+    buf: &mut Vec<u8>,
+    max: usize,
+) -> io::Result<(usize, bool)> {
+    let mut consumed = 0;
+    let mut truncated = false;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            return Ok((consumed, truncated));
+        }
+        let newline = available.iter().position(|&b| b == b'\n');
+        let chunk = newline.map_or(available, |i| &available[..=i]);
+        let keep = chunk.len().min(max.saturating_sub(buf.len()));
+        buf.extend_from_slice(&chunk[..keep]);
+        if keep < chunk.len() {
+            truncated = true;
+            if newline.is_some() {
+                buf.push(b'\n');
+            }
+        }
+        let used = chunk.len();
+        reader.consume(used);
+        consumed += used;
+        if newline.is_some() {
+            return Ok((consumed, truncated));
+        }
+    }
+}
+
+/// Make a line safe to store and render: `\r\n` becomes `\n`, a UTF-8 byte order mark
+/// opening the stream is dropped, and invalid UTF-8 is replaced with U+FFFD rather than
+/// lost.
+pub(crate) fn normalize_line(buf: &mut Vec<u8>, first: bool) {
+    if first && buf.starts_with(UTF8_BOM) {
+        buf.drain(..UTF8_BOM.len());
+    }
+    if buf.ends_with(b"\r\n") {
+        buf.remove(buf.len() - 2);
+    }
+    if let Cow::Owned(fixed) = String::from_utf8_lossy(buf) {
+        *buf = fixed.into_bytes();
+    }
+}
+
+/// Mark a line cut off by [`read_line_capped`].
+pub(crate) fn mark_truncated(buf: &mut Vec<u8>) {
+    if buf.last() == Some(&b'\n') {
+        buf.pop();
+    }
+    buf.extend_from_slice(TRUNCATED_MARKER);
+}
+
+/// Copy `reader` line by line into `sink` (if any) until EOF, calling `on_line` for each raw
+/// line. Every line is written with a single `write_all` under the lock; lines over
+/// `options.max_line` bytes are truncated first, for both. The prefix only goes to the file.
+pub(crate) fn copy_lines(
+    run_id: &str,
+    stream: &str,
+    options: &LineOptions,
+    reader: impl Read,
+    sink: Option<&Mutex<File>>,
+    mut on_line: impl FnMut(&[u8]),
+) {
+    let mut reader = BufReader::new(reader);
+    let mut buf = Vec::new();
+    let mut first = true;
+    loop {
+        buf.clear();
+        let max_line = options.max_line;
+        let read = read_line_capped(&mut reader, &mut buf, max_line);
+        if options.normalize {
+            normalize_line(&mut buf, first);
+        }
+        first = false;
+        match read {
+            Ok((0, _)) => break,
+            Ok((read, true)) => {
+                log::warn!("Backend {stream} printed a {read}-byte line, truncated to {max_line}");
+                mark_truncated(&mut buf);
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::warn!("Failed to read backend {stream}: {e}");
+                break;
+            }
+        }
+        let record = match options.format {
+            LogFormat::Text => {
+                let prefix = options.prefix.as_ref().map(|prefix| prefix.render(stream));
+                let newline: &[u8] = if buf.ends_with(b"\n") { b"" } else { b"\n" };
+                Some([prefix.unwrap_or_default().as_bytes(), &buf, newline].concat())
+            }
+            LogFormat::Json => {
+                json_record(run_id, stream, &buf).map(|record| format!("{record}\n").into_bytes())
+            }
+        };
+        if let (Some(sink), Some(record)) = (sink, record) {
+            let written = match sink.lock() {
+                Ok(mut file) => file.write_all(&record),
+                Err(_) => Err(io::Error::other("log file lock poisoned")),
+            };
+            if let Err(e) = written {
+                log::warn!("Failed to write backend {stream} to backend.log: {e}");
+            }
+        }
+        on_line(&buf);
+    }
+}
+
+/// Merge a backend line into a `source: "backend"` JSON record; `None` for blank lines.
+fn json_record(run_id: &str, stream: &str, line: &[u8]) -> Option<Value> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return None;
+    }
+    let mut record = match serde_json::from_str(line) {
+        Ok(Value::Object(fields)) => fields,
+        _ => Map::from_iter([("raw".to_string(), Value::from(line))]),
+    };
+    record.insert("source".into(), "backend".into());
+    record.insert("stream".into(), stream.into());
+    record.insert("runId".into(), run_id.into());
+    Some(Value::Object(record))
+}
+
+/// Write a launcher-originated line to `backend.log` in the configured format.
+pub(crate) fn write_launcher_line(
+    log_file: &mut File,
+    format: LogFormat,
+    run_id: &str,
+    message: &str,
+) -> io::Result<()> {
+    match format {
+        LogFormat::Text => writeln!(log_file, "[launcher] run_id={run_id} {message}"),
+        LogFormat::Json => {
+            let record = serde_json::json!({
+                "source": "launcher",
+                "runId": run_id,
+                "message": message,
+            });
+            writeln!(log_file, "{record}")
+        }
+    }
+}
+
+/// Disk usage of a log file and its rotated siblings, as returned by `log_stats`.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct LogStats {
+    pub total_bytes: u64,
+    /// Files next to the log named `<log file name>.<suffix>` (e.g. `backend.log.1`).
+    pub rotated_files: usize,
+    /// Oldest and newest modification time across all files, in ms since the Unix epoch.
+    pub oldest_modified: Option<u64>,
+    pub newest_modified: Option<u64>,
+}
+
+/// Sum up `log_path` and its rotated files from metadata alone; a missing log counts as empty.
+pub(crate) fn stats(log_path: &Path) -> io::Result<LogStats> {
+    let mut stats = LogStats::default();
+    let (Some(dir), Some(name)) = (log_path.parent(), log_path.file_name()) else {
+        return Ok(stats);
+    };
+    let rotated_prefix = format!("{}.", name.to_string_lossy());
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(stats),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let rotated = file_name.to_string_lossy().starts_with(&rotated_prefix);
+        if file_name != name && !rotated {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        stats.total_bytes += metadata.len();
+        if rotated {
+            stats.rotated_files += 1;
+        }
+        if let Some(modified) = metadata.modified().ok().and_then(unix_millis) {
+            stats.oldest_modified =
+                Some(stats.oldest_modified.map_or(modified, |t| t.min(modified)));
+            stats.newest_modified =
+                Some(stats.newest_modified.map_or(modified, |t| t.max(modified)));
+        }
+    }
+    Ok(stats)
+}
+
+/// Delete the oldest rotated log files next to `active_log` until all log files there total
+/// at most `cap` bytes. Only rotated files (`backend.log.1`, ...) are candidates: files
+/// still being appended to, the active log above all, are counted but never deleted, so
+/// the total can stay above `cap`. Returns the bytes freed.
+pub(crate) fn prune(active_log: &Path, cap: u64) -> io::Result<u64> {
+    let Some(dir) = active_log.parent() else {
+        return Ok(0);
+    };
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    let active_name = active_log.file_name();
+    let mut total = 0;
+    let mut rotated = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        let file_name = entry.file_name();
+        let name = file_name.to_string_lossy();
+        let is_rotated = |base: &str| name.strip_prefix(base).is_some_and(|s| s.starts_with('.'));
+        let current =
+            Some(file_name.as_os_str()) == active_name || LOG_FILE_NAMES.contains(&&*name);
+        let old = active_name.is_some_and(|active| is_rotated(&active.to_string_lossy()))
+            || LOG_FILE_NAMES.iter().any(|base| is_rotated(base));
+        if !current && !old {
+            continue;
+        }
+        let metadata = entry.metadata()?;
+        if !metadata.is_file() {
+            continue;
+        }
+        total += metadata.len();
+        if !current {
+            let modified = metadata.modified().unwrap_or(UNIX_EPOCH);
+            rotated.push((modified, entry.path(), metadata.len()));
+        }
+    }
+
+    rotated.sort();
+    let mut freed = 0;
+    for (_, path, len) in rotated {
+        if total - freed <= cap {
+            break;
+        }
+        match fs::remove_file(&path) {
+            Ok(()) => {
+                log::info!("Deleted {} to keep logs under {cap} bytes", path.display());
+                freed += len;
+            }
+            Err(e) => log::warn!("Failed to delete {}: {e}", path.display()),
+        }
+    }
+    Ok(freed)
+}
+
+fn unix_millis(time: SystemTime) -> Option<u64> {
+    Some(time.duration_since(UNIX_EPOCH).ok()?.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` distinct lines of `width` bytes, long enough that unsynchronized writers
+    /// would be split by the kernel or the pipe buffer.
+    fn lines(stream: &str, count: usize, width: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                let prefix = format!("{stream}-{i:05}-");
+                let fill = stream.chars().next().unwrap().to_string();
+                format!("{prefix}{}", fill.repeat(width - prefix.len()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn concurrent_streams_do_not_interleave_lines() {
+        let path = std::env::temp_dir().join(format!("toshik-logs-{}.log", uuid::Uuid::new_v4()));
+        let sink = Arc::new(Mutex::new(File::create(&path).unwrap()));
+        let stdout = lines("stdout", 500, 8192);
+        let stderr = lines("stderr", 500, 8192);
+
+        let writers: Vec<_> = [("stdout", stdout.clone()), ("stderr", stderr.clone())]
+            .into_iter()
+            .map(|(stream, lines)| {
+                let sink = Arc::clone(&sink);
+                let input = lines.join("\n") + "\n";
+                thread::spawn(move || {
+                    copy_lines(
+                        "run",
+                        stream,
+                        &LineOptions::raw(DEFAULT_MAX_LINE),
+                        input.as_bytes(),
+                        Some(&sink),
+                        |_| {},
+                    )
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+
+        let written = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        let mut written: Vec<&str> = written.lines().collect();
+        let mut expected: Vec<&str> = stdout.iter().chain(&stderr).map(String::as_str).collect();
+        written.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(written, expected);
+    }
+
+    #[test]
+    fn lines_are_batched_and_oversized_batches_dropped() {
+        let (lines, queue) = mpsc::sync_channel(QUEUE_CAPACITY);
+        for i in 0..450 {
+            let line = format!("line {i}");
+            lines
+                .send(LogLine {
+                    stream: "stdout",
+                    line,
+                })
+                .unwrap();
+        }
+        let huge = "x".repeat(BATCH_BYTES + 1);
+        lines
+            .send(LogLine {
+                stream: "stderr",
+                line: huge,
+            })
+            .unwrap();
+        drop(lines);
+
+        let mut batches = Vec::new();
+        batch_lines(&queue, &Dropped::default(), |batch, dropped| {
+            batches.push((batch.len(), dropped));
+        });
+        assert_eq!(
+            batches,
+            [(200, (0, 0)), (200, (0, 0)), (50, (1, BATCH_BYTES + 1))]
+        );
+    }
+
+    #[test]
+    fn previous_session_is_archived_once_non_empty() {
+        let dir = std::env::temp_dir().join(format!("toshik-archive-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let log = dir.join("backend.log");
+        let prev = dir.join("backend.log.prev");
+
+        archive_previous(&log).unwrap();
+        fs::write(&log, "").unwrap();
+        archive_previous(&log).unwrap();
+        assert!(!prev.exists(), "nothing to keep from an empty log");
+
+        fs::write(&log, "first session\n").unwrap();
+        archive_previous(&log).unwrap();
+        fs::write(&log, "second session\n").unwrap();
+        archive_previous(&log).unwrap();
+        assert!(!log.exists());
+        assert_eq!(fs::read_to_string(&prev).unwrap(), "second session\n");
+        assert_eq!(LogMode::parse("Truncate"), Some(LogMode::Truncate));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recent_lines_keep_only_the_newest() {
+        let recent = Arc::new(RecentLines::new(50));
+        let writers: Vec<_> = ["stdout", "stderr"]
+            .into_iter()
+            .map(|stream| {
+                let recent = Arc::clone(&recent);
+                thread::spawn(move || {
+                    for i in 0..200 {
+                        recent.push(LogLine {
+                            stream,
+                            line: i.to_string(),
+                        });
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let kept = recent.snapshot();
+        assert_eq!(kept.len(), 50);
+        assert!(kept
+            .iter()
+            .all(|line| line.line.parse::<u32>().unwrap() >= 100));
+
+        recent.push(LogLine {
+            stream: "stderr",
+            line: "panic: boom".into(),
+        });
+        assert_eq!(recent.snapshot().last().unwrap().line, "panic: boom");
+        assert_eq!(recent.snapshot().len(), 50);
+    }
+
+    #[test]
+    fn prune_deletes_oldest_rotated_files_but_never_the_active_log() {
+        let dir = std::env::temp_dir().join(format!("toshik-prune-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let active = dir.join("backend.log");
+        let write = |name: &str, age_secs: u64| {
+            let path = dir.join(name);
+            fs::write(&path, vec![b'x'; 1000]).unwrap();
+            let mtime = SystemTime::now() - Duration::from_secs(age_secs);
+            File::options()
+                .write(true)
+                .open(&path)
+                .unwrap()
+                .set_modified(mtime)
+                .unwrap();
+        };
+        write("backend.log", 0);
+        write("backend.log.1", 10);
+        write("backend.log.2", 20);
+        write("hook.log.1", 30);
+        write("unrelated.txt", 40);
+
+        let freed = prune(&active, 2000).unwrap();
+        let mut left: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        left.sort();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(freed, 2000);
+        assert_eq!(left, ["backend.log", "backend.log.1", "unrelated.txt"]);
+    }
+
+    #[test]
+    fn prefixed_lines_carry_a_timestamp_and_stream_tag() {
+        let path = std::env::temp_dir().join(format!("toshik-prefix-{}.log", uuid::Uuid::new_v4()));
+        let sink = Mutex::new(File::create(&path).unwrap());
+        let options = LineOptions {
+            prefix: LinePrefix::parse("yes"),
+            ..LineOptions::raw(DEFAULT_MAX_LINE)
+        };
+        let mut events = Vec::new();
+        copy_lines(
+            "run",
+            "stderr",
+            &options,
+            &b"boom\n"[..],
+            Some(&sink),
+            |line| events.push(line.to_vec()),
+        );
+
+        let written = fs::read_to_string(&path).unwrap();
+        let (ts, rest) = written.split_once(' ').unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(ts).is_ok(), "{ts}");
+        assert_eq!(rest, "[err] boom\n");
+        assert_eq!(events, [b"boom\n".to_vec()], "events get the raw line");
+        assert_eq!(LinePrefix::parse("0"), None);
+        assert_eq!(
+            LinePrefix::parse("<{stream}> ").unwrap().render("stdout"),
+            "<out> "
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn session_banner_names_the_run_and_port() {
+        let path = std::env::temp_dir().join(format!("toshik-banner-{}.log", uuid::Uuid::new_v4()));
+        let mut file = File::create(&path).unwrap();
+        let banner = SessionBanner::parse("1").unwrap();
+        banner
+            .write(&mut file, LogFormat::Text, "run-1", 3004)
+            .unwrap();
+        SessionBanner::parse("--- {run_id} ---\n")
+            .unwrap()
+            .write(&mut file, LogFormat::Json, "run-2", 3005)
+            .unwrap();
+
+        let written = fs::read_to_string(&path).unwrap();
+        let (text, json) = written.split_once('\n').unwrap();
+        let ts = text
+            .strip_prefix("===== session run-1 started ")
+            .and_then(|rest| rest.strip_suffix(" port=3004 ====="))
+            .unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(ts).is_ok(), "{ts}");
+        let record: Value = serde_json::from_str(json.trim_end()).unwrap();
+        assert_eq!(record["message"], "--- run-2 ---");
+        assert_eq!(record["runId"], "run-2");
+        assert_eq!(SessionBanner::parse(" no "), None);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn normalized_lines_lose_crlf_bom_and_invalid_utf8() {
+        let path = std::env::temp_dir().join(format!("toshik-crlf-{}.log", uuid::Uuid::new_v4()));
+        let sink = Mutex::new(File::create(&path).unwrap());
+        let options = LineOptions {
+            normalize: true,
+            ..LineOptions::raw(DEFAULT_MAX_LINE)
+        };
+        let input = [UTF8_BOM, b"ready\r\n", b"caf\xe9\r\n", b"tail"].concat();
+        let mut events = Vec::new();
+        copy_lines("run", "stdout", &options, &input[..], Some(&sink), |line| {
+            events.push(line.to_vec())
+        });
+
+        assert_eq!(
+            fs::read_to_string(&path).unwrap(),
+            "ready\ncaf\u{fffd}\ntail\n"
+        );
+        assert_eq!(
+            events,
+            [
+                b"ready\n".to_vec(),
+                "caf\u{fffd}\n".as_bytes().to_vec(),
+                b"tail".to_vec()
+            ]
+        );
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn overlong_lines_are_truncated_and_the_rest_skipped() {
+        let input = format!("{}\nshort\n", "x".repeat(100));
+        let mut reader = BufReader::with_capacity(16, input.as_bytes());
+        let mut buf = Vec::new();
+        assert_eq!(
+            read_line_capped(&mut reader, &mut buf, 10).unwrap(),
+            (101, true)
+        );
+        mark_truncated(&mut buf);
+        assert_eq!(buf, [b"xxxxxxxxxx".as_slice(), TRUNCATED_MARKER].concat());
+
+        buf.clear();
+        assert_eq!(
+            read_line_capped(&mut reader, &mut buf, 10).unwrap(),
+            (6, false)
+        );
+        assert_eq!(buf, b"short\n");
+    }
+}
@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+use serde::Serialize;
+
+use crate::error::BackendError;
+
+/// Ports below this need `TOSHIK_ALLOW_PRIVILEGED_PORTS=1`.
+pub(crate) const MIN_UNPRIVILEGED_PORT: u16 = 1024;
+
+/// Where the backend listens (`TOSHIK_TRANSPORT`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Transport {
+    /// TCP on the loopback host only (default).
+    Tcp,
+    /// TCP plus a Unix domain socket, passed to bun as `--socket`. Unix only.
+    Both,
+}
+
+impl Transport {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "tcp" => Some(Self::Tcp),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+}
+
+/// Loopback address the backend binds to and the launcher connects to: `::1` with
+/// `TOSHIK_IPV6=1`, `127.0.0.1` otherwise.
+pub(crate) fn loopback(ipv6: bool) -> IpAddr {
+    if ipv6 {
+        IpAddr::V6(Ipv6Addr::LOCALHOST)
+    } else {
+        IpAddr::V4(Ipv4Addr::LOCALHOST)
+    }
+}
+
+/// Check a port before it is handed to bun or connected to: `1024..=65535`, or
+/// `1..=65535` when privileged ports are allowed.
+pub(crate) fn validate_port(port: u32, allow_privileged: bool) -> Result<u16, BackendError> {
+    let reason = match u16::try_from(port) {
+        Ok(0) | Err(_) => "must be between 1 and 65535",
+        Ok(port) if port < MIN_UNPRIVILEGED_PORT && !allow_privileged => {
+            "ports below 1024 need TOSHIK_ALLOW_PRIVILEGED_PORTS=1"
+        }
+        Ok(port) => return Ok(port),
+    };
+    Err(BackendError::InvalidPort { port, reason })
+}
+
+/// `http://<addr>`, with IPv6 literals bracketed (`http://[::1]:3001`).
+pub(crate) fn base_url(addr: SocketAddr) -> String {
+    format!("http://{addr}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_port_checks_the_boundaries() {
+        assert!(validate_port(0, true).is_err());
+        assert!(validate_port(1, false).is_err());
+        assert_eq!(validate_port(1, true).unwrap(), 1);
+        assert!(validate_port(1023, false).is_err());
+        assert_eq!(validate_port(1023, true).unwrap(), 1023);
+        assert_eq!(validate_port(1024, false).unwrap(), 1024);
+        assert_eq!(validate_port(65535, false).unwrap(), 65535);
+        assert!(validate_port(65536, true).is_err());
+        assert_eq!(
+            validate_port(80, false).unwrap_err().to_string(),
+            "Invalid port 80: ports below 1024 need TOSHIK_ALLOW_PRIVILEGED_PORTS=1"
+        );
+    }
+
+    #[test]
+    fn base_url_brackets_ipv6_literals() {
+        let v6 = SocketAddr::new(loopback(true), 3001);
+        assert_eq!(base_url(v6), "http://[::1]:3001");
+        let v4 = SocketAddr::new(loopback(false), 3001);
+        assert_eq!(base_url(v4), "http://127.0.0.1:3001");
+    }
+}
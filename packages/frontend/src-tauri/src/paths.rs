@@ -0,0 +1,205 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::settings::SETTINGS_FILE;
+
+const LOG_FILE: &str = "backend.log";
+const SALT_FILE: &str = "stronghold-salt.txt";
+const AUDIT_FILE: &str = "audit.log";
+const PID_FILE: &str = "backend.pid";
+const SOCKET_DIR: &str = "sockets";
+const SESSION_FILE: &str = "session.json";
+const TIMINGS_FILE: &str = "startup-timings.json";
+const VAULT_FILE: &str = "secrets.hold";
+
+/// Files the launcher keeps in Tauri's app directories, resolved once during setup.
+#[derive(Debug)]
+pub(crate) struct AppPaths {
+    /// Default backend log; `set_log_path` can override it.
+    pub log_file: PathBuf,
+    /// Stronghold's argon2 salt.
+    pub salt_file: PathBuf,
+    pub settings_file: PathBuf,
+    /// Process-control audit trail.
+    pub audit_file: PathBuf,
+    /// PID and port of the spawned backend, for external tooling.
+    pub pid_file: PathBuf,
+    /// Unix sockets of backends started with `TOSHIK_TRANSPORT=both`, one per run.
+    pub socket_dir: PathBuf,
+    /// Heartbeat of the running backend, read back by `recover_previous_session`.
+    pub session_file: PathBuf,
+    /// Rolling history behind `startup_timings`.
+    pub timings_file: PathBuf,
+    /// The webview's Stronghold vault (`src/lib/stronghold.ts`). It is opened as
+    /// `${appDataDir()}secrets.hold`, with no separator, so it sits next to the app data
+    /// directory rather than in it.
+    pub vault_file: PathBuf,
+}
+
+impl AppPaths {
+    /// Logs and settings live in `app_data_dir`, the salt in `app_local_data_dir`. Where the
+    /// two coincide (common on Linux) the files are split into `logs/` and `secure/`
+    /// subdirectories so they can't collide; an existing salt is moved along so vaults
+    /// stay readable.
+    pub(crate) fn resolve<R: Runtime>(app: &AppHandle<R>) -> Result<Self, String> {
+        let data_dir = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("Failed to resolve app data dir: {e}"))?;
+        let local_data_dir = app
+            .path()
+            .app_local_data_dir()
+            .map_err(|e| format!("Failed to resolve app local data dir: {e}"))?;
+        Ok(Self::layout(&data_dir, &local_data_dir))
+    }
+
+    pub(crate) fn layout(data_dir: &Path, local_data_dir: &Path) -> Self {
+        let settings_file = data_dir.join(SETTINGS_FILE);
+        let audit_file = data_dir.join(AUDIT_FILE);
+        let pid_file = data_dir.join(PID_FILE);
+        let socket_dir = data_dir.join(SOCKET_DIR);
+        let session_file = data_dir.join(SESSION_FILE);
+        let timings_file = data_dir.join(TIMINGS_FILE);
+        let mut vault_file = data_dir.as_os_str().to_os_string();
+        vault_file.push(VAULT_FILE);
+        let vault_file = PathBuf::from(vault_file);
+        if data_dir != local_data_dir {
+            return Self {
+                log_file: data_dir.join(LOG_FILE),
+                salt_file: local_data_dir.join(SALT_FILE),
+                settings_file,
+                audit_file,
+                pid_file,
+                socket_dir,
+                session_file,
+                timings_file,
+                vault_file,
+            };
+        }
+
+        log::warn!(
+            "App data and local data dirs are both {}, namespacing logs/ and secure/",
+            data_dir.display()
+        );
+        let salt_file = data_dir.join("secure").join(SALT_FILE);
+        migrate(&data_dir.join(SALT_FILE), &salt_file);
+        Self {
+            log_file: data_dir.join("logs").join(LOG_FILE),
+            salt_file,
+            settings_file,
+            audit_file,
+            pid_file,
+            socket_dir,
+            session_file,
+            timings_file,
+            vault_file,
+        }
+    }
+}
+
+/// One path the launcher reads or writes, as reported by `validate_paths`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PathStatus {
+    /// What the path is for, e.g. `log` or `pidFile`.
+    pub name: &'static str,
+    /// `None` when it couldn't be determined; `problem` says why.
+    pub path: Option<PathBuf>,
+    pub exists: bool,
+    /// The parent directory can be listed. A parent that doesn't exist yet is created on
+    /// demand, so its nearest existing ancestor is checked instead.
+    pub parent_readable: bool,
+    /// A file could be created in the parent directory (or that ancestor).
+    pub parent_writable: bool,
+    pub problem: Option<String>,
+}
+
+impl PathStatus {
+    /// Check `path` on disk. Writability is tested by creating and removing a probe file,
+    /// since permission bits don't account for read-only mounts or ACLs.
+    pub(crate) fn check(name: &'static str, path: &Path) -> Self {
+        let parent = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        let parent = parent.unwrap_or(Path::new("."));
+        let parent = parent
+            .ancestors()
+            .find(|dir| dir.exists())
+            .unwrap_or(parent);
+        let parent_readable = fs::read_dir(parent).is_ok();
+        let probe = parent.join(format!(".toshik-write-test-{}", uuid::Uuid::new_v4()));
+        let written = fs::File::create_new(&probe);
+        let parent_writable = written.is_ok();
+        if parent_writable {
+            let _ = fs::remove_file(&probe);
+        }
+        let problem = match written {
+            Err(e) if parent_readable => Some(format!("{} is not writable: {e}", parent.display())),
+            Err(e) => Some(format!("{} is not accessible: {e}", parent.display())),
+            Ok(_) => None,
+        };
+        Self {
+            name,
+            path: Some(path.to_path_buf()),
+            exists: path.exists(),
+            parent_readable,
+            parent_writable,
+            problem,
+        }
+    }
+
+    /// A path that couldn't be worked out at all.
+    pub(crate) fn unknown(name: &'static str, problem: String) -> Self {
+        Self {
+            name,
+            path: None,
+            exists: false,
+            parent_readable: false,
+            parent_writable: false,
+            problem: Some(problem),
+        }
+    }
+}
+
+/// Move `from` to `to` (creating its directory) unless `to` already exists.
+fn migrate(from: &Path, to: &Path) {
+    let result = to
+        .parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|()| {
+            if from.exists() && !to.exists() {
+                fs::rename(from, to)?;
+            }
+            Ok(())
+        });
+    if let Err(e) = result {
+        log::warn!("Failed to move {} to {}: {e}", from.display(), to.display());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn status_reports_existence_and_parent_access() {
+        let dir = std::env::temp_dir().join(format!("toshik-paths-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("backend.log"), "").unwrap();
+
+        let existing = PathStatus::check("log", &dir.join("backend.log"));
+        assert!(existing.exists && existing.parent_readable && existing.parent_writable);
+        assert_eq!(existing.problem, None);
+        let pending = PathStatus::check("pidFile", &dir.join("backend.pid"));
+        assert!(!pending.exists && pending.parent_writable);
+
+        let nested = PathStatus::check("socketDir", &dir.join("sockets/run.sock"));
+        assert!(!nested.exists && nested.parent_writable);
+        let blocked = PathStatus::check("log", &dir.join("backend.log/inner.log"));
+        assert!(!blocked.parent_writable);
+        assert!(blocked.problem.is_some());
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,100 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Write `<pid>\n<port>\n`, so `kill $(head -1 backend.pid)` works from scripts.
+pub(crate) fn write(path: &Path, pid: u32, port: u16) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, format!("{pid}\n{port}\n"))
+}
+
+/// The PID recorded in `path`, if it exists and is well-formed.
+fn read_pid(path: &Path) -> Option<u32> {
+    fs::read_to_string(path)
+        .ok()?
+        .lines()
+        .next()?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// The PID and port recorded in `path`, if both are there and well-formed.
+pub(crate) fn read(path: &Path) -> Option<(u32, u16)> {
+    let raw = fs::read_to_string(path).ok()?;
+    let mut lines = raw.lines().map(str::trim);
+    let pid = lines.next()?.parse().ok()?;
+    let port = lines.next()?.parse().ok()?;
+    Some((pid, port))
+}
+
+/// Delete `path` if it still names `pid`; a newer backend may have replaced it already.
+pub(crate) fn remove_if_owned(path: &Path, pid: u32) {
+    if read_pid(path) != Some(pid) {
+        return;
+    }
+    if let Err(e) = fs::remove_file(path) {
+        log::warn!("Failed to remove {}: {e}", path.display());
+    }
+}
+
+/// On startup, delete a PID file left by a previous session whose process is gone. One
+/// naming a live process is kept (and reported); the next spawn overwrites it either way.
+pub(crate) fn clear_stale(path: &Path) {
+    let Some(pid) = read_pid(path) else {
+        if path.exists() {
+            log::info!("Removing unreadable {}", path.display());
+            let _ = fs::remove_file(path);
+        }
+        return;
+    };
+    if process_alive(pid) {
+        log::warn!(
+            "{} names pid {pid}, which is still running; a backend from a previous session may be left over",
+            path.display()
+        );
+    } else {
+        log::info!("Removing stale {} (pid {pid} is gone)", path.display());
+        let _ = fs::remove_file(path);
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn process_alive(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks for existence and permission.
+    unsafe { libc::kill(pid as libc::pid_t, 0) == 0 }
+}
+
+/// Without a cheap liveness check every leftover file is treated as stale.
+#[cfg(not(unix))]
+pub(crate) fn process_alive(_pid: u32) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn stale_files_are_cleared_and_foreign_ones_kept() {
+        let path = std::env::temp_dir().join(format!("toshik-{}.pid", uuid::Uuid::new_v4()));
+        write(&path, std::process::id(), 3001).unwrap();
+        assert_eq!(read_pid(&path), Some(std::process::id()));
+        assert_eq!(read(&path), Some((std::process::id(), 3001)));
+
+        clear_stale(&path);
+        assert!(path.exists(), "names a live process");
+        remove_if_owned(&path, std::process::id() + 1);
+        assert!(path.exists(), "owned by another pid");
+
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let dead = child.id();
+        child.wait().unwrap();
+        write(&path, dead, 3002).unwrap();
+        clear_stale(&path);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,232 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use reqwest::{Method, Response, Url};
+use serde::Serialize;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+use crate::error::BackendError;
+use crate::http::{BackendClient, HttpConfig};
+use crate::net;
+
+/// Caps how many `proxy_backend` calls reach the backend at once, so a burst from the
+/// webview queues instead of flooding the backend or running out of sockets, and how much
+/// of a response each may bring back over IPC.
+pub(crate) struct ProxyLimit {
+    permits: Semaphore,
+    queue_timeout: Duration,
+    max_response: usize,
+    read_timeout: Duration,
+}
+
+impl ProxyLimit {
+    pub(crate) fn new(config: &HttpConfig) -> Self {
+        Self {
+            permits: Semaphore::new(config.proxy_concurrency.max(1)),
+            queue_timeout: config.proxy_queue_timeout,
+            max_response: config.proxy_max_response,
+            read_timeout: config.proxy_read_timeout,
+        }
+    }
+
+    /// Wait for a slot, held until the permit is dropped; `ProxyThrottled` if none frees
+    /// up within the queue timeout.
+    pub(crate) async fn acquire(&self) -> Result<SemaphorePermit<'_>, BackendError> {
+        match tokio::time::timeout(self.queue_timeout, self.permits.acquire()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            // The semaphore is never closed.
+            Ok(Err(_)) | Err(_) => Err(BackendError::ProxyThrottled(self.queue_timeout)),
+        }
+    }
+}
+
+/// Backend reply relayed to the webview by `proxy_backend`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ProxyResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String,
+}
+
+/// Build `http://<addr><path>`, refusing anything that would reach another host.
+fn backend_url(addr: SocketAddr, path: &str) -> Result<Url, String> {
+    if !path.starts_with('/') || path.starts_with("//") {
+        return Err(format!(
+            "Proxy path must be an absolute path on the backend, got {path:?}"
+        ));
+    }
+    let url = Url::parse(&format!("{}{path}", net::base_url(addr)))
+        .map_err(|e| format!("Invalid proxy path {path:?}: {e}"))?;
+    // `host:port` as the URL sees it must be exactly the backend's socket address.
+    let target = url
+        .host_str()
+        .zip(url.port())
+        .map(|(host, port)| format!("{host}:{port}"));
+    if target != Some(addr.to_string()) {
+        return Err("Proxy requests may only target the local backend".into());
+    }
+    Ok(url)
+}
+
+/// Read `response`'s body chunk by chunk, giving up as soon as it passes `limit` bytes.
+async fn read_body(mut response: Response, limit: usize) -> Result<Vec<u8>, String> {
+    if response
+        .content_length()
+        .is_some_and(|len| len > limit as u64)
+    {
+        return Err(BackendError::ProxyResponseTooLarge(limit).to_string());
+    }
+    let mut body = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .map_err(|e| format!("Failed to read proxy response: {e}"))?
+    {
+        if body.len() + chunk.len() > limit {
+            return Err(BackendError::ProxyResponseTooLarge(limit).to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body)
+}
+
+/// Perform `method path` against the backend at `addr` from Rust, sidestepping the
+/// webview's CORS and mixed-content rules. The body is capped and timed by `limit`.
+pub(crate) async fn forward(
+    client: &BackendClient,
+    limit: &ProxyLimit,
+    addr: SocketAddr,
+    method: &str,
+    path: &str,
+    body: Option<String>,
+    headers: Option<HashMap<String, String>>,
+) -> Result<ProxyResponse, String> {
+    let url = backend_url(addr, path)?;
+    let method = Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+        .map_err(|_| format!("Invalid HTTP method {method:?}"))?;
+
+    let mut request = client.request(method, url);
+    for (name, value) in headers.unwrap_or_default() {
+        request = request.header(name, value);
+    }
+    if let Some(body) = body {
+        request = request.body(body);
+    }
+
+    let request = request
+        .build()
+        .map_err(|e| format!("Invalid proxy request: {e}"))?;
+    let response = client
+        .send(request)
+        .await
+        .map_err(|e| format!("Proxy request failed: {e}"))?;
+    let status = response.status().as_u16();
+    let headers = response
+        .headers()
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let body = tokio::time::timeout(limit.read_timeout, read_body(response, limit.max_response))
+        .await
+        .map_err(|_| BackendError::ProxyReadTimeout(limit.read_timeout).to_string())??;
+    Ok(ProxyResponse {
+        status,
+        headers,
+        body: String::from_utf8_lossy(&body).into_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOCAL: SocketAddr =
+        SocketAddr::new(std::net::IpAddr::V4(std::net::Ipv4Addr::LOCALHOST), 3005);
+
+    #[test]
+    fn backend_url_accepts_local_paths() {
+        let url = backend_url(LOCAL, "/api/items?q=1").unwrap();
+        assert_eq!(url.as_str(), "http://127.0.0.1:3005/api/items?q=1");
+    }
+
+    #[test]
+    fn backend_url_rejects_other_hosts() {
+        for path in ["http://example.com/", "//example.com/x", "@example.com", ""] {
+            assert!(
+                backend_url(LOCAL, path).is_err(),
+                "{path:?} should be rejected"
+            );
+        }
+        let url = backend_url(LOCAL, "/@example.com").unwrap();
+        assert_eq!(url.host_str(), Some("127.0.0.1"));
+    }
+
+    #[test]
+    fn excess_calls_are_throttled_after_the_queue_timeout() {
+        let limit = ProxyLimit::new(&HttpConfig {
+            proxy_concurrency: 1,
+            proxy_queue_timeout: Duration::from_millis(20),
+            ..HttpConfig::default()
+        });
+        tauri::async_runtime::block_on(async {
+            let held = limit.acquire().await.unwrap();
+            assert!(matches!(
+                limit.acquire().await,
+                Err(BackendError::ProxyThrottled(_))
+            ));
+            drop(held);
+            assert!(limit.acquire().await.is_ok());
+        });
+    }
+
+    /// Answer each connection on a local port with `reply`, split over two writes.
+    fn serve(reply: String, connections: usize) -> SocketAddr {
+        use std::io::{Read, Write};
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                let mut stream = stream.unwrap();
+                let _ = stream.read(&mut [0; 1024]);
+                let (head, tail) = reply.split_at(reply.len() - 8);
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.flush();
+                let _ = stream.write_all(tail.as_bytes());
+            }
+        });
+        addr
+    }
+
+    #[test]
+    fn oversized_responses_are_refused_while_streaming() {
+        let body = "x".repeat(64);
+        // No Content-Length: the size is only known by counting what arrives.
+        let addr = serve(
+            format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n{body}"),
+            2,
+        );
+        let client = BackendClient::new(&HttpConfig::default()).unwrap();
+        let fetch = |max_response| {
+            let limit = ProxyLimit::new(&HttpConfig {
+                proxy_max_response: max_response,
+                ..HttpConfig::default()
+            });
+            tauri::async_runtime::block_on(forward(&client, &limit, addr, "get", "/", None, None))
+        };
+
+        assert_eq!(fetch(64).unwrap().body, body);
+        assert_eq!(
+            fetch(63).unwrap_err(),
+            BackendError::ProxyResponseTooLarge(63).to_string()
+        );
+    }
+
+    #[test]
+    fn backend_url_brackets_ipv6_loopback() {
+        let addr = SocketAddr::new(net::loopback(true), 3005);
+        let url = backend_url(addr, "/health").unwrap();
+        assert_eq!(url.as_str(), "http://[::1]:3005/health");
+    }
+}
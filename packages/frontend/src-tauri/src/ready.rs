@@ -0,0 +1,634 @@
+use std::fmt;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::sync::{Condvar, Mutex, PoisonError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use regex::Regex;
+use reqwest::{Method, Url};
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::http::BackendClient;
+use crate::net;
+
+/// How long a freshly spawned backend gets to start accepting connections.
+pub(crate) const READY_TIMEOUT: Duration = Duration::from_secs(15);
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Pause between health checks while a restarted backend is still unhealthy.
+const HEALTH_RETRY_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Connect and read timeout of a single TCP or WebSocket probe.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a backend with `TOSHIK_READY_LINE` gets to print its ready line before the
+/// stored port is probed instead.
+pub(crate) const READY_LINE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How a backend is asked whether it is healthy (`TOSHIK_READINESS_PROBE`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum ProbeKind {
+    /// GET the health path and match the body (default).
+    Http,
+    /// Accepting a TCP connection is enough, for backends without HTTP.
+    Tcp,
+    /// Request a WebSocket upgrade on the health path; `101 Switching Protocols` is ready.
+    WebSocket,
+}
+
+impl ProbeKind {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "http" => Some(Self::Http),
+            "tcp" => Some(Self::Tcp),
+            "websocket" | "ws" => Some(Self::WebSocket),
+            _ => None,
+        }
+    }
+}
+
+/// How a restarted backend's health response body is judged.
+#[derive(Debug, Clone)]
+pub(crate) enum BodyMatcher {
+    Exact(String),
+    Contains(String),
+    Regex(Regex),
+}
+
+impl BodyMatcher {
+    /// Parse `exact:<body>`, `contains:<text>` or `regex:<pattern>`; a bare value means
+    /// `contains`.
+    pub(crate) fn parse(raw: &str) -> Result<Self, String> {
+        Ok(match raw.split_once(':') {
+            Some(("exact", body)) => Self::Exact(body.to_string()),
+            Some(("contains", text)) => Self::Contains(text.to_string()),
+            Some(("regex", pattern)) => {
+                Self::Regex(Regex::new(pattern).map_err(|e| format!("invalid regex: {e}"))?)
+            }
+            _ => Self::Contains(raw.to_string()),
+        })
+    }
+
+    fn matches(&self, body: &str) -> bool {
+        match self {
+            Self::Exact(expected) => body.trim() == expected,
+            Self::Contains(text) => body.contains(text.as_str()),
+            Self::Regex(regex) => regex.is_match(body),
+        }
+    }
+}
+
+impl fmt::Display for BodyMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Exact(body) => write!(f, "exact:{body}"),
+            Self::Contains(text) => write!(f, "contains:{text}"),
+            Self::Regex(regex) => write!(f, "regex:{regex}"),
+        }
+    }
+}
+
+impl Serialize for BodyMatcher {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// What a restart has to pass before it counts as successful.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HealthCheck {
+    /// `TOSHIK_READINESS_PROBE=http|tcp|websocket`, default http.
+    pub probe: ProbeKind,
+    /// `TOSHIK_HEALTH_PATH`, default `/health`. Also the WebSocket probe's endpoint.
+    pub path: String,
+    /// `TOSHIK_HEALTH_MATCH`, default `contains:"status":"ok"`. Only used by the HTTP probe.
+    pub matcher: BodyMatcher,
+}
+
+impl Default for HealthCheck {
+    fn default() -> Self {
+        Self {
+            probe: ProbeKind::Http,
+            path: "/health".into(),
+            matcher: BodyMatcher::Contains(r#""status":"ok""#.into()),
+        }
+    }
+}
+
+/// One stage of `TOSHIK_READY_ENDPOINTS`: a path that has to answer 2xx before the backend
+/// is declared ready.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ReadyEndpoint {
+    pub path: String,
+    /// How long the path is polled, each request included, before the launch is declared
+    /// not ready. Defaults to [`READY_TIMEOUT`].
+    #[serde(rename = "timeoutMs", serialize_with = "crate::http::millis")]
+    pub timeout: Duration,
+}
+
+impl ReadyEndpoint {
+    /// Comma-separated paths, each optionally followed by `:<milliseconds>`, e.g.
+    /// `/health:2000,/ready:10000`. A suffix that isn't a number is part of the path.
+    pub(crate) fn parse_list(raw: &str) -> Vec<Self> {
+        raw.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (path, timeout) = match entry.rsplit_once(':') {
+                    Some((path, millis)) => match millis.trim().parse() {
+                        Ok(millis) => (path, Duration::from_millis(millis)),
+                        Err(_) => (entry, READY_TIMEOUT),
+                    },
+                    None => (entry, READY_TIMEOUT),
+                };
+                Self {
+                    path: format!("/{}", path.trim().trim_start_matches('/')),
+                    timeout,
+                }
+            })
+            .collect()
+    }
+}
+
+/// The readiness endpoint a launch got stuck on.
+#[derive(Debug)]
+pub(crate) struct EndpointFailure {
+    pub path: String,
+    pub error: String,
+}
+
+/// Poll until something accepts TCP connections on `addr`. Returns `false` if nothing did
+/// within `timeout`.
+pub(crate) fn wait_for_port(addr: SocketAddr, timeout: Duration) -> bool {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if TcpStream::connect_timeout(&addr, POLL_INTERVAL).is_ok() {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[derive(Deserialize)]
+struct ReadyLine {
+    event: String,
+    port: u16,
+}
+
+/// Port from a `{"event":"ready","port":3005}` line, the backend's machine-readable way of
+/// saying it listens. Plain text, other JSON and port 0 yield `None`.
+pub(crate) fn parse_ready_line(line: &[u8]) -> Option<u16> {
+    let line = line.trim_ascii();
+    if !line.starts_with(b"{") {
+        return None;
+    }
+    let ready: ReadyLine = serde_json::from_slice(line).ok()?;
+    (ready.event == "ready" && ready.port != 0).then_some(ready.port)
+}
+
+#[derive(Clone, Copy)]
+enum Announcement {
+    Pending,
+    Port(u16),
+    /// Stdout closed without a ready line.
+    Closed,
+}
+
+/// Hands the port from the backend's ready line from the stdout reader to the readiness
+/// watcher. Only the first line counts.
+pub(crate) struct AnnouncedPort {
+    state: Mutex<Announcement>,
+    changed: Condvar,
+}
+
+impl AnnouncedPort {
+    pub(crate) fn new() -> Self {
+        Self {
+            state: Mutex::new(Announcement::Pending),
+            changed: Condvar::new(),
+        }
+    }
+
+    fn settle(&self, announcement: Announcement) {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        if matches!(*state, Announcement::Pending) {
+            *state = announcement;
+            self.changed.notify_all();
+        }
+    }
+
+    pub(crate) fn is_pending(&self) -> bool {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        matches!(*state, Announcement::Pending)
+    }
+
+    pub(crate) fn announce(&self, port: u16) {
+        self.settle(Announcement::Port(port));
+    }
+
+    /// Stdout has ended; wake the watcher rather than have it sit out the timeout.
+    pub(crate) fn close(&self) {
+        self.settle(Announcement::Closed);
+    }
+
+    /// The announced port, waiting up to `timeout` for it. `None` if stdout closed first
+    /// or nothing came in time.
+    pub(crate) fn wait(&self, timeout: Duration) -> Option<u16> {
+        let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let (state, _) = self
+            .changed
+            .wait_timeout_while(state, timeout, |state| {
+                matches!(state, Announcement::Pending)
+            })
+            .unwrap_or_else(PoisonError::into_inner);
+        match *state {
+            Announcement::Port(port) => Some(port),
+            Announcement::Pending | Announcement::Closed => None,
+        }
+    }
+}
+
+/// Poll until the backend accepts connections on TCP `addr` and, if given, the Unix
+/// `socket`: on either when `require_all` is false, on both when it is true. Returns
+/// `false` if that didn't happen within `timeout`.
+pub(crate) fn wait_for_endpoints(
+    addr: SocketAddr,
+    socket: Option<&Path>,
+    require_all: bool,
+    timeout: Duration,
+) -> bool {
+    let Some(socket) = socket else {
+        return wait_for_port(addr, timeout);
+    };
+    let deadline = Instant::now() + timeout;
+    let (mut tcp_up, mut socket_up) = (false, false);
+    loop {
+        tcp_up = tcp_up || TcpStream::connect_timeout(&addr, POLL_INTERVAL).is_ok();
+        socket_up = socket_up || socket_accepts(socket);
+        let ready = if require_all {
+            tcp_up && socket_up
+        } else {
+            tcp_up || socket_up
+        };
+        if ready {
+            return true;
+        }
+        if Instant::now() >= deadline {
+            return false;
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+#[cfg(unix)]
+fn socket_accepts(socket: &Path) -> bool {
+    std::os::unix::net::UnixStream::connect(socket).is_ok()
+}
+
+#[cfg(not(unix))]
+fn socket_accepts(_socket: &Path) -> bool {
+    false
+}
+
+/// [`wait_for_port`] on a blocking thread, for async callers; fails with a description.
+pub(crate) async fn wait_listening(addr: SocketAddr, timeout: Duration) -> Result<(), String> {
+    let listening = tauri::async_runtime::spawn_blocking(move || wait_for_port(addr, timeout))
+        .await
+        .map_err(|e| e.to_string())?;
+    if !listening {
+        return Err(format!(
+            "did not accept connections on port {} within {timeout:?}",
+            addr.port()
+        ));
+    }
+    Ok(())
+}
+
+/// Poll with the configured probe until it passes (for HTTP: 2xx with a matching body), or
+/// fail with the last problem seen once `timeout` has passed.
+pub(crate) async fn wait_healthy(
+    client: &BackendClient,
+    addr: SocketAddr,
+    check: &HealthCheck,
+    timeout: Duration,
+) -> Result<(), String> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        let outcome = match check.probe {
+            ProbeKind::Http => health(client, addr, check).await,
+            ProbeKind::Tcp => blocking(move || tcp_connect(addr)).await,
+            ProbeKind::WebSocket => {
+                let path = check.path.clone();
+                blocking(move || websocket_upgrade(addr, &path)).await
+            }
+        };
+        let error = match outcome {
+            Ok(()) => return Ok(()),
+            Err(error) => error,
+        };
+        if Instant::now() >= deadline {
+            return Err(error);
+        }
+        tokio::time::sleep(HEALTH_RETRY_INTERVAL).await;
+    }
+}
+
+async fn health(
+    client: &BackendClient,
+    addr: SocketAddr,
+    check: &HealthCheck,
+) -> Result<(), String> {
+    let url = Url::parse(&format!("{}{}", net::base_url(addr), check.path))
+        .map_err(|e| format!("Invalid health path {:?}: {e}", check.path))?;
+    let request = client
+        .request(Method::GET, url)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let body = client
+        .send(request)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("{} failed: {e}", check.path))?
+        .text()
+        .await
+        .map_err(|e| format!("Failed to read {} response: {e}", check.path))?;
+    if !check.matcher.matches(&body) {
+        return Err(format!(
+            "{} answered {body:?}, expected {}",
+            check.path, check.matcher
+        ));
+    }
+    Ok(())
+}
+
+/// Check `endpoints` in order, polling each until it answers 2xx or its own timeout runs
+/// out, so a cheap liveness check can gate a slower one that needs the database. Fails
+/// with the first endpoint that didn't pass.
+pub(crate) async fn wait_for_ready_endpoints(
+    client: &BackendClient,
+    addr: SocketAddr,
+    endpoints: &[ReadyEndpoint],
+) -> Result<(), EndpointFailure> {
+    for endpoint in endpoints {
+        let deadline = Instant::now() + endpoint.timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let error = match answers_ok(client, addr, &endpoint.path, remaining).await {
+                Ok(()) => break,
+                Err(error) => error,
+            };
+            if Instant::now() >= deadline {
+                return Err(EndpointFailure {
+                    path: endpoint.path.clone(),
+                    error: format!(
+                        "{} did not answer 2xx within {:?}: {error}",
+                        endpoint.path, endpoint.timeout
+                    ),
+                });
+            }
+            tokio::time::sleep(HEALTH_RETRY_INTERVAL.min(remaining)).await;
+        }
+    }
+    Ok(())
+}
+
+/// One GET of `path` given at most `timeout` (but never less than a poll interval).
+async fn answers_ok(
+    client: &BackendClient,
+    addr: SocketAddr,
+    path: &str,
+    timeout: Duration,
+) -> Result<(), String> {
+    let url = Url::parse(&format!("{}{path}", net::base_url(addr)))
+        .map_err(|e| format!("invalid path: {e}"))?;
+    let request = client
+        .request(Method::GET, url)
+        .timeout(timeout.max(POLL_INTERVAL))
+        .build()
+        .map_err(|e| e.to_string())?;
+    client
+        .send(request)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// Run a socket probe on a blocking thread.
+async fn blocking(
+    probe: impl FnOnce() -> Result<(), String> + Send + 'static,
+) -> Result<(), String> {
+    tauri::async_runtime::spawn_blocking(probe)
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn tcp_connect(addr: SocketAddr) -> Result<(), String> {
+    TcpStream::connect_timeout(&addr, PROBE_TIMEOUT)
+        .map(drop)
+        .map_err(|e| format!("TCP connect to port {} failed: {e}", addr.port()))
+}
+
+/// Ask for a WebSocket upgrade on `path` and require `101 Switching Protocols`. The
+/// connection is dropped right after the status line; the accept key is not verified.
+fn websocket_upgrade(addr: SocketAddr, path: &str) -> Result<(), String> {
+    let mut stream = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT)
+        .map_err(|e| format!("TCP connect to port {} failed: {e}", addr.port()))?;
+    stream
+        .set_read_timeout(Some(PROBE_TIMEOUT))
+        .and_then(|()| stream.set_write_timeout(Some(PROBE_TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+    write!(
+        stream,
+        "GET {path} HTTP/1.1\r\nHost: {addr}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13\r\n\r\n"
+    )
+    .map_err(|e| format!("WebSocket upgrade request to {path} failed: {e}"))?;
+    let mut status = String::new();
+    BufReader::new(&stream)
+        .read_line(&mut status)
+        .map_err(|e| format!("No WebSocket upgrade response from {path}: {e}"))?;
+    match status.split_whitespace().nth(1) {
+        Some("101") => Ok(()),
+        _ => Err(format!(
+            "{path} answered {:?} to a WebSocket upgrade, expected 101",
+            status.trim_end()
+        )),
+    }
+}
+
+/// GET `path` on the backend and require a 2xx, so lazily compiled routes are warm before
+/// the backend is declared ready.
+pub(crate) async fn warmup(
+    client: &BackendClient,
+    addr: SocketAddr,
+    path: &str,
+) -> Result<(), String> {
+    let url = Url::parse(&format!("{}{path}", net::base_url(addr)))
+        .map_err(|e| format!("Invalid warmup path {path:?}: {e}"))?;
+    let request = client
+        .request(Method::GET, url)
+        .build()
+        .map_err(|e| e.to_string())?;
+    client
+        .send(request)
+        .await
+        .and_then(|response| response.error_for_status())
+        .map_err(|e| format!("Warmup request to {path} failed: {e}"))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn body_matchers() {
+        let body = r#"{"status":"ok","uptime":1.5}"#;
+        assert!(HealthCheck::default().matcher.matches(body));
+        assert!(BodyMatcher::parse("exact:ok").unwrap().matches("ok\n"));
+        assert!(BodyMatcher::parse(r#"regex:"uptime":\d"#)
+            .unwrap()
+            .matches(body));
+        assert!(!BodyMatcher::parse("contains:degraded")
+            .unwrap()
+            .matches(body));
+        assert!(BodyMatcher::parse("regex:(").is_err());
+    }
+
+    #[test]
+    fn ready_line_is_parsed_and_handed_to_the_watcher() {
+        assert_eq!(
+            parse_ready_line(b"{\"event\":\"ready\",\"port\":3005}\r\n"),
+            Some(3005)
+        );
+        assert_eq!(
+            parse_ready_line(br#" {"port":3002,"event":"ready","pid":7}"#),
+            Some(3002)
+        );
+        assert_eq!(
+            parse_ready_line(br#"{"event":"starting","port":3005}"#),
+            None
+        );
+        assert_eq!(parse_ready_line(br#"{"event":"ready","port":0}"#), None);
+        assert_eq!(parse_ready_line(br#"{"event":"ready","port":70000}"#), None);
+        assert_eq!(parse_ready_line(br#"{"event":"ready"}"#), None);
+        assert_eq!(
+            parse_ready_line(b"backend running on http://localhost:3005"),
+            None
+        );
+
+        let announced = std::sync::Arc::new(AnnouncedPort::new());
+        let reader = std::sync::Arc::clone(&announced);
+        let handle = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            reader.announce(3007);
+            reader.announce(3008);
+            reader.close();
+        });
+        assert_eq!(announced.wait(Duration::from_secs(5)), Some(3007));
+        handle.join().unwrap();
+
+        let closed = AnnouncedPort::new();
+        closed.close();
+        let started = Instant::now();
+        assert_eq!(closed.wait(Duration::from_secs(5)), None);
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(AnnouncedPort::new().wait(Duration::from_millis(20)), None);
+    }
+
+    /// A server answering one connection with `response`, after reading the request head.
+    fn answer_once(response: &'static str) -> SocketAddr {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap() > 0 && !line.ends_with("\r\n\r\n") {}
+            (&stream).write_all(response.as_bytes()).unwrap();
+        });
+        addr
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn either_endpoint_is_enough_unless_both_are_required() {
+        let dir = std::env::temp_dir().join(format!("toshik-sock-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join("backend.sock");
+        let _listener = std::os::unix::net::UnixListener::bind(&socket).unwrap();
+        // Bound and dropped, so nothing listens on this TCP port.
+        let closed = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap();
+
+        let timeout = Duration::from_millis(300);
+        assert!(wait_for_endpoints(closed, Some(&socket), false, timeout));
+        assert!(!wait_for_endpoints(closed, Some(&socket), true, timeout));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn ready_endpoints_are_checked_in_order_and_the_stuck_one_reported() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let stream = stream.unwrap();
+                let mut request = String::new();
+                BufReader::new(&stream).read_line(&mut request).unwrap();
+                let status = if request.starts_with("GET /health ") {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                let response =
+                    format!("HTTP/1.1 {status}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                let _ = (&stream).write_all(response.as_bytes());
+            }
+        });
+        let client = BackendClient::new(&crate::http::HttpConfig::default()).unwrap();
+        let wait = |raw: &str| {
+            let endpoints = ReadyEndpoint::parse_list(raw);
+            tauri::async_runtime::block_on(wait_for_ready_endpoints(&client, addr, &endpoints))
+        };
+
+        assert!(wait("health:300").is_ok());
+        let failure = wait("/health:300, /ready:300").unwrap_err();
+        assert_eq!(failure.path, "/ready");
+        assert!(failure.error.contains("503"), "{}", failure.error);
+        assert_eq!(
+            ReadyEndpoint::parse_list("/a:b,,/c"),
+            [
+                ReadyEndpoint {
+                    path: "/a:b".into(),
+                    timeout: READY_TIMEOUT,
+                },
+                ReadyEndpoint {
+                    path: "/c".into(),
+                    timeout: READY_TIMEOUT,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn websocket_probe_requires_switching_protocols() {
+        let addr = answer_once("HTTP/1.1 101 Switching Protocols\r\n\r\n");
+        assert!(websocket_upgrade(addr, "/ws").is_ok());
+
+        let addr = answer_once("HTTP/1.1 404 Not Found\r\n\r\n");
+        let error = websocket_upgrade(addr, "/ws").unwrap_err();
+        assert!(error.contains("404"), "{error}");
+    }
+}
@@ -0,0 +1,273 @@
+use std::path::{Component, Path, PathBuf};
+use std::sync::Mutex;
+
+/// Backend entry point relative to the workspace root.
+const BACKEND_SCRIPT: &str = "packages/backend/src/index.ts";
+
+/// The last resolution and the mode it was made with, reused while the script still exists.
+static CACHE: Mutex<Option<(PathMode, ResolvedScript)>> = Mutex::new(None);
+
+/// How a located script path is turned into the path handed to bun.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum PathMode {
+    /// Resolve symlinks; the `.env` is looked up next to the real workspace.
+    Canonical,
+    /// Keep the path as found (only `..` is collapsed), so a symlinked `packages` directory
+    /// still derives the `.env` from the workspace it is linked into. Existence is still
+    /// verified by canonicalizing.
+    Logical,
+}
+
+impl PathMode {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "canonical" => Some(Self::Canonical),
+            "logical" => Some(Self::Logical),
+            _ => None,
+        }
+    }
+}
+
+/// Where the backend entry point was found.
+#[derive(Debug, Clone)]
+pub(crate) struct ResolvedScript {
+    pub script: PathBuf,
+    /// The monorepo root the script belongs to, where `bun install` runs.
+    pub workspace: Option<PathBuf>,
+    /// `<workspace>/.env`, whether or not it exists.
+    pub env_file: Option<PathBuf>,
+}
+
+/// Locate `packages/backend/src/index.ts` relative to the executable, falling back to the CWD.
+///
+/// The result is cached; a cached script that no longer exists (the workspace moved, a branch
+/// switch removed it) is dropped and resolved again.
+pub(crate) fn resolve_backend_script(mode: PathMode) -> Result<ResolvedScript, String> {
+    let mut cache = CACHE.lock().map_err(|e| e.to_string())?;
+    match cache.as_ref() {
+        Some((cached_mode, resolved)) if *cached_mode == mode && resolved.script.exists() => {
+            return Ok(resolved.clone());
+        }
+        Some((_, resolved)) => log::info!(
+            "Discarding cached backend script {}, resolving again",
+            resolved.script.display()
+        ),
+        None => {}
+    }
+    *cache = None;
+    let (candidates, skipped) = search();
+    let resolved =
+        resolve_from(&candidates, mode).ok_or_else(|| not_found(&candidates, &skipped))?;
+    *cache = Some((mode, resolved.clone()));
+    Ok(resolved)
+}
+
+/// Forget the cached resolution, so the next spawn searches the candidates again. Returns
+/// the script that was cached, if any.
+pub(crate) fn invalidate_cache() -> Result<Option<PathBuf>, String> {
+    let cached = CACHE.lock().map_err(|e| e.to_string())?.take();
+    if let Some((_, ref resolved)) = cached {
+        log::info!(
+            "Invalidated cached backend script {}",
+            resolved.script.display()
+        );
+    }
+    Ok(cached.map(|(_, resolved)| resolved.script))
+}
+
+/// The error for a search that found nothing, listing every place looked at and every
+/// base directory that couldn't be determined.
+fn not_found(candidates: &[PathBuf], skipped: &[String]) -> String {
+    let mut message = format!("Cannot locate {BACKEND_SCRIPT}, tried:");
+    for candidate in candidates {
+        message.push_str(&format!("\n  {}", candidate.display()));
+    }
+    for reason in skipped {
+        message.push_str(&format!("\nskipped: {reason}"));
+    }
+    message
+}
+
+/// Where the backend script is looked for, in order: relative to the executable, then to
+/// the CWD.
+pub(crate) fn candidate_paths() -> Vec<PathBuf> {
+    search().0
+}
+
+/// [`candidate_paths`], plus why a base directory was left out. `current_exe()` fails in
+/// some sandboxes and containers; the search then goes on from the CWD instead of quietly
+/// coming up short.
+fn search() -> (Vec<PathBuf>, Vec<String>) {
+    let mut candidates = Vec::new();
+    let mut skipped = Vec::new();
+    // Try to resolve relative to the current executable's grandparent (workspace root).
+    match std::env::current_exe() {
+        Ok(exe) => {
+            if let Some(dir) = exe.parent() {
+                // In development, Cargo builds into src-tauri/target/debug, so the
+                // workspace root is a few levels up; try each.
+                candidates.extend([
+                    // dev build: target/debug/toshik-babe-engine -> ../../packages/backend/src/index.ts
+                    dir.join("../../..").join(BACKEND_SCRIPT),
+                    dir.join("../../../..").join(BACKEND_SCRIPT),
+                    dir.join("../../../../..").join(BACKEND_SCRIPT),
+                ]);
+            }
+        }
+        Err(e) => {
+            log::warn!("Cannot determine the executable's path ({e}), searching from the CWD only");
+            skipped.push(format!(
+                "paths next to the executable, current_exe() failed: {e}"
+            ));
+        }
+    }
+
+    // Fallback: try relative to CWD
+    match std::env::current_dir() {
+        Ok(cwd) => candidates.push(cwd.join(BACKEND_SCRIPT)),
+        Err(e) => {
+            log::warn!("Cannot determine the working directory: {e}");
+            skipped.push(format!("paths under the CWD, current_dir() failed: {e}"));
+        }
+    }
+    (candidates, skipped)
+}
+
+/// Pick the first candidate that exists and derive the `.env` location from it.
+fn resolve_from(candidates: &[PathBuf], mode: PathMode) -> Option<ResolvedScript> {
+    candidates.iter().find_map(|candidate| {
+        let canonical = candidate.canonicalize().ok()?;
+        let script = match mode {
+            PathMode::Canonical => canonical,
+            PathMode::Logical => normalize_lexically(candidate),
+        };
+        // script = <workspace>/packages/backend/src/index.ts
+        let workspace = script.ancestors().nth(4).map(Path::to_path_buf);
+        let env_file = workspace.as_ref().map(|root| root.join(".env"));
+        Some(ResolvedScript {
+            script,
+            workspace,
+            env_file,
+        })
+    })
+}
+
+/// Collapse `.` and `..` components without touching the filesystem.
+fn normalize_lexically(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// `<tmp>/real/packages/backend/src/index.ts` plus `<tmp>/workspace/.env`, with
+    /// `<tmp>/workspace/packages` symlinked to `<tmp>/real/packages`.
+    #[cfg(unix)]
+    fn symlinked_workspace() -> PathBuf {
+        let root = std::env::temp_dir().join(format!("toshik-resolve-{}", uuid::Uuid::new_v4()));
+        let real_src = root.join("real/packages/backend/src");
+        fs::create_dir_all(&real_src).unwrap();
+        fs::write(real_src.join("index.ts"), "").unwrap();
+        fs::create_dir_all(root.join("workspace")).unwrap();
+        fs::write(root.join("workspace/.env"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("real/packages"), root.join("workspace/packages"))
+            .unwrap();
+        root.canonicalize().unwrap()
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn logical_mode_keeps_symlinked_workspace_for_env_file() {
+        let root = symlinked_workspace();
+        let candidate = root.join("workspace/packages/backend/src/../src/index.ts");
+
+        let resolved = resolve_from(&[candidate], PathMode::Logical).unwrap();
+
+        assert_eq!(
+            resolved.script,
+            root.join("workspace/packages/backend/src/index.ts")
+        );
+        assert_eq!(resolved.env_file, Some(root.join("workspace/.env")));
+        assert!(resolved.env_file.unwrap().exists());
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn canonical_mode_resolves_through_symlink() {
+        let root = symlinked_workspace();
+        let candidate = root.join("workspace/packages/backend/src/index.ts");
+
+        let resolved = resolve_from(&[candidate], PathMode::Canonical).unwrap();
+
+        assert_eq!(
+            resolved.script,
+            root.join("real/packages/backend/src/index.ts")
+        );
+        assert_eq!(resolved.env_file, Some(root.join("real/.env")));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn workspace_with_spaces_and_unicode_resolves() {
+        let root = std::env::temp_dir()
+            .join(format!("toshik resolve {}", uuid::Uuid::new_v4()))
+            .join("мой проект");
+        let src = root.join("packages/backend/src");
+        fs::create_dir_all(&src).unwrap();
+        fs::write(src.join("index.ts"), "").unwrap();
+        let root = root.canonicalize().unwrap();
+
+        let resolved = resolve_from(&[root.join(BACKEND_SCRIPT)], PathMode::Canonical).unwrap();
+
+        assert_eq!(resolved.script, root.join(BACKEND_SCRIPT));
+        assert_eq!(resolved.env_file, Some(root.join(".env")));
+        fs::remove_dir_all(root.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn not_found_lists_every_candidate() {
+        let candidates = [PathBuf::from("/opt/app/a"), PathBuf::from("/home/user/b")];
+        assert_eq!(
+            not_found(&candidates, &[]),
+            format!("Cannot locate {BACKEND_SCRIPT}, tried:\n  /opt/app/a\n  /home/user/b")
+        );
+        let skipped = ["paths next to the executable, current_exe() failed: denied".into()];
+        assert!(not_found(&candidates[1..], &skipped).ends_with(
+            "\n  /home/user/b\nskipped: paths next to the executable, current_exe() failed: denied"
+        ));
+    }
+
+    #[test]
+    fn missing_candidates_are_skipped() {
+        let missing = std::env::temp_dir().join("toshik-resolve-missing/index.ts");
+        assert!(resolve_from(&[missing], PathMode::Logical).is_none());
+    }
+
+    #[test]
+    fn normalize_lexically_collapses_dot_components() {
+        assert_eq!(
+            normalize_lexically(Path::new("/a/b/./c/../../d")),
+            PathBuf::from("/a/d")
+        );
+        assert_eq!(
+            normalize_lexically(Path::new("../x")),
+            PathBuf::from("../x")
+        );
+    }
+}
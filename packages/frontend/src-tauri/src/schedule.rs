@@ -0,0 +1,127 @@
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+/// Name of the timer thread in the task registry.
+pub(crate) const TASK_NAME: &str = "scheduled-restart";
+
+/// What `schedule_restart` does while another restart is pending
+/// (`TOSHIK_RESTART_SCHEDULE_POLICY=replace|reject`, default replace).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SchedulePolicy {
+    /// Cancel the pending one and schedule the new one.
+    Replace,
+    /// Keep the pending one and fail.
+    Reject,
+}
+
+impl SchedulePolicy {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "replace" => Some(Self::Replace),
+            "reject" => Some(Self::Reject),
+            _ => None,
+        }
+    }
+}
+
+/// A restart waiting for its time, as returned by `schedule_restart`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct PendingRestart {
+    /// Tells a replaced schedule's timer that it is no longer the one due.
+    #[serde(skip)]
+    pub id: u64,
+    /// Milliseconds since the Unix epoch.
+    pub at: u64,
+    /// Blue-green (`drain_backend`) rather than stop-and-start (`restart_backend`).
+    pub drain: bool,
+}
+
+/// The one restart that may be pending at a time.
+#[derive(Default)]
+pub(crate) struct ScheduledRestart(Mutex<Slot>);
+
+#[derive(Default)]
+struct Slot {
+    next_id: u64,
+    pending: Option<PendingRestart>,
+}
+
+impl ScheduledRestart {
+    /// Make a restart due at `at` the pending one. Returns it and, under
+    /// [`SchedulePolicy::Replace`], the one it displaced.
+    pub(crate) fn schedule(
+        &self,
+        at: u64,
+        drain: bool,
+        policy: SchedulePolicy,
+    ) -> Result<(PendingRestart, Option<PendingRestart>), String> {
+        let mut slot = self.0.lock().map_err(|e| e.to_string())?;
+        if slot.pending.is_some() && policy == SchedulePolicy::Reject {
+            return Err("A restart is already scheduled; cancel it first".into());
+        }
+        slot.next_id += 1;
+        let restart = PendingRestart {
+            id: slot.next_id,
+            at,
+            drain,
+        };
+        Ok((restart, slot.pending.replace(restart)))
+    }
+
+    /// Drop the pending restart, if any, and return it.
+    pub(crate) fn cancel(&self) -> Option<PendingRestart> {
+        self.0.lock().ok()?.pending.take()
+    }
+
+    /// Take the pending restart if it is still `id`, i.e. its timer may fire it.
+    pub(crate) fn take_if(&self, id: u64) -> Option<PendingRestart> {
+        let mut slot = self.0.lock().ok()?;
+        match slot.pending {
+            Some(pending) if pending.id == id => slot.pending.take(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_one_restart_is_pending() {
+        let schedule = ScheduledRestart::default();
+        let (first, replaced) = schedule
+            .schedule(1_000, false, SchedulePolicy::Replace)
+            .unwrap();
+        assert_eq!(replaced, None);
+
+        assert!(schedule
+            .schedule(2_000, true, SchedulePolicy::Reject)
+            .is_err());
+        let (second, replaced) = schedule
+            .schedule(2_000, true, SchedulePolicy::Replace)
+            .unwrap();
+        assert_eq!(replaced, Some(first));
+
+        assert_eq!(
+            schedule.take_if(first.id),
+            None,
+            "replaced timer must not fire"
+        );
+        assert_eq!(schedule.take_if(second.id), Some(second));
+        assert_eq!(schedule.cancel(), None);
+
+        let (third, _) = schedule
+            .schedule(3_000, false, SchedulePolicy::Reject)
+            .unwrap();
+        assert_eq!(schedule.cancel(), Some(third));
+        assert_eq!(schedule.take_if(third.id), None);
+        assert_eq!(
+            SchedulePolicy::parse(" Reject"),
+            Some(SchedulePolicy::Reject)
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri_plugin_stronghold::stronghold::Stronghold;
+
+const PROBE_CLIENT: &[u8] = b"toshik-self-test";
+const PROBE_KEY: &[u8] = b"probe";
+
+/// Password and client the webview opens its vault with (`src/lib/stronghold.ts`).
+const VAULT_PASSWORD: &str = "toshik-babe-secrets";
+const VAULT_CLIENT: &[u8] = b"toshik-babe";
+
+/// Length of the argon2 salt, and of the key derived with it.
+const SALT_LEN: usize = 32;
+
+/// Tries for each salt file operation that fails with a transient error, e.g. on a
+/// networked or roaming home directory.
+const SALT_IO_ATTEMPTS: u32 = 3;
+
+/// Pause between salt file attempts.
+const SALT_IO_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// Outcome of the secure storage self-test, as returned by `stronghold_status`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase", tag = "state", content = "error")]
+pub(crate) enum StorageStatus {
+    /// The self-test has not finished yet.
+    Pending,
+    Ready,
+    Error(String),
+}
+
+/// Managed result of [`self_test`], filled in once it completes.
+pub(crate) struct StorageHealth(pub Mutex<StorageStatus>);
+
+/// Check that Stronghold can actually be used: the salt file is readable (or can be
+/// created) and a throwaway snapshot can be written and read back.
+///
+/// The snapshot lives in a temporary directory with a random key, so no user vault or
+/// password is involved.
+pub(crate) fn self_test(salt_file: &Path) -> Result<(), String> {
+    check_salt(salt_file)?;
+
+    let dir = std::env::temp_dir().join(format!("toshik-stronghold-{}", uuid::Uuid::new_v4()));
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let result = round_trip(&dir.join("probe.stronghold"));
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+/// The Argon2 key for `password` with the salt in `salt_file`, generated there first if
+/// missing. Derives what the plugin's `KeyDerivation::argon2` does, so existing vaults still
+/// open, but retries transient salt IO and returns an error where that panics.
+pub(crate) fn derive_key(password: &str, salt_file: &Path) -> Result<Vec<u8>, String> {
+    let salt = if salt_file.is_file() {
+        read_salt(salt_file)?
+    } else {
+        let salt = new_salt();
+        retry_transient("write the salt file", || fs::write(salt_file, salt))
+            .map_err(|e| format!("Failed to write {}: {e}", salt_file.display()))?;
+        salt
+    };
+    argon2::hash_raw(password.as_bytes(), &salt, &argon2::Config::default())
+        .map_err(|e| format!("Failed to derive the vault key: {e}"))
+}
+
+fn read_salt(salt_file: &Path) -> Result<[u8; SALT_LEN], String> {
+    let salt = retry_transient("read the salt file", || fs::read(salt_file))
+        .map_err(|e| format!("Failed to read {}: {e}", salt_file.display()))?;
+    salt.try_into().map_err(|salt: Vec<u8>| {
+        format!(
+            "{} is {} bytes, expected {SALT_LEN}",
+            salt_file.display(),
+            salt.len()
+        )
+    })
+}
+
+fn new_salt() -> [u8; SALT_LEN] {
+    let mut salt = [0; SALT_LEN];
+    salt[..16].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    salt[16..].copy_from_slice(uuid::Uuid::new_v4().as_bytes());
+    salt
+}
+
+/// Check that the salt file can be read, or created where it is missing.
+fn check_salt(salt_file: &Path) -> Result<(), String> {
+    if salt_file.is_file() {
+        return read_salt(salt_file).map(drop);
+    }
+    let dir = salt_file
+        .parent()
+        .ok_or("Salt file has no parent directory")?;
+    let probe = dir.join(format!(".toshik-probe-{}", uuid::Uuid::new_v4()));
+    retry_transient("create the salt directory", || {
+        fs::create_dir_all(dir)?;
+        fs::write(&probe, b"")
+    })
+    .map_err(|e| format!("Salt directory is not writable: {e}"))?;
+    let _ = fs::remove_file(probe);
+    Ok(())
+}
+
+/// Run `op`, trying again after a short pause while it fails with an error that may go
+/// away on its own. Other errors, and the last transient one, are returned.
+fn retry_transient<T>(what: &str, mut op: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let mut attempt = 1;
+    loop {
+        match op() {
+            Err(e) if is_transient(&e) && attempt < SALT_IO_ATTEMPTS => {
+                log::warn!(
+                    "Failed to {what} (attempt {attempt}/{SALT_IO_ATTEMPTS}): {e}, retrying"
+                );
+                attempt += 1;
+                thread::sleep(SALT_IO_RETRY_DELAY);
+            }
+            result => return result,
+        }
+    }
+}
+
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// Read the store entries named by `secrets` (variable name -> store key, e.g.
+/// `GIGACHAT_API_KEY` -> `apikey:gigachat`) from the webview's vault. Entries that are
+/// missing are left out with a warning; a vault that doesn't exist yet yields nothing.
+pub(crate) fn read_secrets(
+    vault: &Path,
+    salt_file: &Path,
+    secrets: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    if secrets.is_empty() || !vault.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let key = derive_key(VAULT_PASSWORD, salt_file)?;
+    read_store(vault, key, secrets)
+}
+
+fn read_store(
+    vault: &Path,
+    key: Vec<u8>,
+    secrets: &BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    let stronghold = Stronghold::new(vault, key)
+        .map_err(|e| format!("Failed to open {}: {e}", vault.display()))?;
+    let client = stronghold
+        .load_client(VAULT_CLIENT)
+        .map_err(|e| format!("Failed to load the vault client: {e}"))?;
+    let mut values = BTreeMap::new();
+    for (name, store_key) in secrets {
+        let value = client
+            .store()
+            .get(store_key.as_bytes())
+            .map_err(|e| format!("Failed to read {store_key} from the vault: {e}"))?;
+        match value.map(String::from_utf8) {
+            Some(Ok(value)) => {
+                values.insert(name.clone(), value);
+            }
+            Some(Err(_)) => log::warn!("Vault entry {store_key} for {name} is not UTF-8"),
+            None => log::warn!("Vault has no {store_key} for {name}"),
+        }
+    }
+    Ok(values)
+}
+
+fn round_trip(snapshot: &Path) -> Result<(), String> {
+    let key = [
+        *uuid::Uuid::new_v4().as_bytes(),
+        *uuid::Uuid::new_v4().as_bytes(),
+    ]
+    .concat();
+
+    let stronghold = Stronghold::new(snapshot, key.clone()).map_err(|e| e.to_string())?;
+    let client = stronghold
+        .create_client(PROBE_CLIENT)
+        .map_err(|e| e.to_string())?;
+    client
+        .store()
+        .insert(PROBE_KEY.to_vec(), b"ok".to_vec(), None)
+        .map_err(|e| e.to_string())?;
+    stronghold
+        .write_client(PROBE_CLIENT)
+        .map_err(|e| e.to_string())?;
+    stronghold.save().map_err(|e| e.to_string())?;
+    drop(stronghold);
+
+    let reopened = Stronghold::new(snapshot, key).map_err(|e| e.to_string())?;
+    let value = reopened
+        .load_client(PROBE_CLIENT)
+        .and_then(|client| client.store().get(PROBE_KEY))
+        .map_err(|e| e.to_string())?;
+    if value.as_deref() != Some(b"ok".as_slice()) {
+        return Err("Stronghold snapshot did not read back what was written".into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writing a real snapshot takes minutes in debug builds, so only the paths that never
+    /// open one are covered here.
+    #[test]
+    fn no_vault_or_no_names_yields_no_secrets() {
+        let dir = std::env::temp_dir().join(format!("toshik-vault-{}", uuid::Uuid::new_v4()));
+        let secrets = BTreeMap::from([(
+            "GIGACHAT_API_KEY".to_string(),
+            "apikey:gigachat".to_string(),
+        )]);
+        let salt = dir.join("salt");
+        assert!(read_secrets(&dir.join("secrets.hold"), &salt, &secrets)
+            .unwrap()
+            .is_empty());
+        assert!(read_secrets(&dir, &salt, &BTreeMap::new())
+            .unwrap()
+            .is_empty());
+        assert!(!salt.exists(), "no salt is created for nothing to read");
+    }
+
+    #[test]
+    fn derived_key_matches_the_plugin_and_a_bad_salt_is_an_error() {
+        let dir = std::env::temp_dir().join(format!("toshik-salt-{}", uuid::Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let salt = dir.join("salt.txt");
+
+        let key = derive_key(VAULT_PASSWORD, &salt).unwrap();
+        assert_eq!(fs::read(&salt).unwrap().len(), SALT_LEN);
+        assert_eq!(
+            key,
+            tauri_plugin_stronghold::kdf::KeyDerivation::argon2(VAULT_PASSWORD, &salt)
+        );
+
+        fs::write(&salt, b"short").unwrap();
+        let error = derive_key(VAULT_PASSWORD, &salt).unwrap_err();
+        assert!(error.contains("is 5 bytes, expected 32"), "{error}");
+        let missing_dir = dir.join("missing").join("salt.txt");
+        assert!(derive_key(VAULT_PASSWORD, &missing_dir).is_err());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn transient_errors_are_retried_and_others_are_not() {
+        let mut calls = 0;
+        let result = retry_transient("test", || {
+            calls += 1;
+            if calls < SALT_IO_ATTEMPTS {
+                Err(io::Error::from(io::ErrorKind::TimedOut))
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), SALT_IO_ATTEMPTS);
+
+        let mut calls = 0;
+        let result: io::Result<()> = retry_transient("test", || {
+            calls += 1;
+            Err(io::Error::from(io::ErrorKind::PermissionDenied))
+        });
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,97 @@
+use std::fs;
+use std::io;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// How often the running backend's state is written to `session.json`.
+pub(crate) const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The running backend as last recorded by the heartbeat, so a launch after an app crash
+/// can find a backend that outlived it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct SessionState {
+    pub run_id: String,
+    pub host: IpAddr,
+    pub port: u16,
+    /// `None` for attached backends.
+    pub pid: Option<u32>,
+    /// Milliseconds since the Unix epoch at which the backend was started.
+    pub started_at: u64,
+    /// Restarts (and drains) in the session that wrote the file.
+    pub restart_count: u32,
+    /// Started with `TOSHIK_DETACHED`, so it is expected to outlive the app.
+    #[serde(default)]
+    pub detached: bool,
+    /// Milliseconds since the Unix epoch of the write; a stale one means the app died.
+    pub heartbeat_at: u64,
+}
+
+/// Write `state` to `path` through a temporary file, so a crash mid-write never leaves a
+/// truncated file behind.
+pub(crate) fn write(path: &Path, state: &SessionState) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(state).map_err(io::Error::other)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path)
+}
+
+/// The state recorded in `path`, if it exists and is well-formed.
+pub(crate) fn read(path: &Path) -> Option<SessionState> {
+    let raw = match fs::read_to_string(path) {
+        Ok(raw) => raw,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            log::warn!("Failed to read {}: {e}", path.display());
+            return None;
+        }
+    };
+    serde_json::from_str(&raw)
+        .inspect_err(|e| log::warn!("Ignoring invalid {}: {e}", path.display()))
+        .ok()
+}
+
+/// Delete `path`, e.g. once the backend it describes has stopped.
+pub(crate) fn remove(path: &Path) {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => {
+            log::warn!("Failed to remove {}: {e}", path.display());
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_round_trips_and_garbage_is_ignored() {
+        let path =
+            std::env::temp_dir().join(format!("toshik-session-{}.json", uuid::Uuid::new_v4()));
+        let state = SessionState {
+            run_id: "run".into(),
+            host: crate::net::loopback(false),
+            port: 3004,
+            pid: Some(42),
+            started_at: 1_700_000_000_000,
+            restart_count: 2,
+            detached: true,
+            heartbeat_at: 1_700_000_005_000,
+        };
+        write(&path, &state).unwrap();
+        assert_eq!(read(&path), Some(state));
+
+        fs::write(&path, "{ truncated").unwrap();
+        assert_eq!(read(&path), None);
+        remove(&path);
+        assert!(!path.exists());
+        assert_eq!(read(&path), None);
+    }
+}
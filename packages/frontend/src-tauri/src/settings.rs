@@ -0,0 +1,48 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+/// File in the app data directory holding the settings below.
+pub(crate) const SETTINGS_FILE: &str = "settings.json";
+
+/// Launcher settings changed at runtime through commands, persisted across app restarts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub(crate) struct Settings {
+    /// Where backend output is written instead of `<app data>/backend.log` (`set_log_path`).
+    pub log_path: Option<PathBuf>,
+    /// Variables applied to every backend spawn (`set_backend_env`); per-launch `env`
+    /// passed to `start_backend` overrides them.
+    pub backend_env: BTreeMap<String, String>,
+    /// Level of the launcher's own log output (`set_launcher_log_level`), e.g. `debug`.
+    pub launcher_log_level: Option<String>,
+}
+
+impl Settings {
+    /// Read `path`, falling back to defaults when it is missing or unreadable.
+    pub(crate) fn load(path: &Path) -> Self {
+        match fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw).unwrap_or_else(|e| {
+                log::warn!("Ignoring invalid {}: {e}", path.display());
+                Self::default()
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Write the settings to `path`, creating its directory if needed.
+    pub(crate) fn save(&self, path: &Path) -> io::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let json = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(path, json)
+    }
+}
@@ -0,0 +1,140 @@
+use std::collections::BTreeMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Mutex, PoisonError};
+
+/// What [`which`] answered, per shell and name: each lookup starts a login shell, whose rc
+/// files can take seconds.
+static FOUND: Mutex<BTreeMap<(PathBuf, String), Option<PathBuf>>> = Mutex::new(BTreeMap::new());
+
+/// The user's login shell (`$SHELL`), for `TOSHIK_LOGIN_SHELL`. Always `None` on Windows,
+/// which has no equivalent: environments there come from the registry, which GUI apps
+/// already see.
+pub(crate) fn login_shell() -> Option<PathBuf> {
+    if cfg!(windows) {
+        return None;
+    }
+    let shell = PathBuf::from(std::env::var_os("SHELL")?);
+    shell.is_absolute().then_some(shell)
+}
+
+/// `arg` as a single POSIX shell word: single-quoted, with embedded quotes spliced in as
+/// `'\''`.
+#[cfg(unix)]
+pub(crate) fn quote(arg: &OsStr) -> OsString {
+    use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+    let mut quoted = Vec::with_capacity(arg.len() + 2);
+    quoted.push(b'\'');
+    for &byte in arg.as_bytes() {
+        if byte == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(byte);
+        }
+    }
+    quoted.push(b'\'');
+    OsString::from_vec(quoted)
+}
+
+/// `exec <program> <args>…` with every word quoted, for `sh -c`.
+#[cfg(unix)]
+pub(crate) fn exec_line(program: &OsStr, args: &[OsString]) -> OsString {
+    let mut line = OsString::from("exec ");
+    line.push(quote(program));
+    for arg in args {
+        line.push(" ");
+        line.push(quote(arg));
+    }
+    line
+}
+
+/// Where `name` is found on the `PATH` of an interactive login of `shell`, e.g. bun put
+/// there by nvm or asdf from rc files a GUI-launched app never reads.
+#[cfg(unix)]
+pub(crate) fn which(shell: &Path, name: &str) -> Option<PathBuf> {
+    let mut lookup = OsString::from("command -v ");
+    lookup.push(quote(OsStr::new(name)));
+    let output = Command::new(shell)
+        .arg("-lc")
+        .arg(lookup)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()?;
+    let found = String::from_utf8(output.stdout).ok()?;
+    // rc files may print banners first; the lookup's answer is the last line.
+    let found = PathBuf::from(found.lines().last()?.trim());
+    (output.status.success() && found.is_absolute()).then_some(found)
+}
+
+#[cfg(not(unix))]
+pub(crate) fn which(_shell: &Path, _name: &str) -> Option<PathBuf> {
+    None
+}
+
+/// [`which`], asking each shell only once per session. A path found earlier that has since
+/// gone (e.g. a bun that a version manager replaced) is looked up again.
+pub(crate) fn which_cached(shell: &Path, name: &str) -> Option<PathBuf> {
+    let key = (shell.to_path_buf(), name.to_string());
+    let found = || FOUND.lock().unwrap_or_else(PoisonError::into_inner);
+    let cached = found().get(&key).cloned();
+    if let Some(cached) = cached.filter(|path| path.as_ref().is_none_or(|path| path.exists())) {
+        return cached;
+    }
+    let path = which(shell, name);
+    found().insert(key, path.clone());
+    path
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quoted_arguments_survive_the_shell() {
+        let args: Vec<OsString> = [
+            "[%s]\n",
+            "two words",
+            "it's",
+            "$HOME",
+            "`id`",
+            "",
+            "a\"b\\c",
+        ]
+        .into_iter()
+        .map(OsString::from)
+        .collect();
+        let script = exec_line(OsStr::new("printf"), &args);
+        let output = Command::new("sh").arg("-c").arg(script).output().unwrap();
+        assert_eq!(
+            String::from_utf8(output.stdout).unwrap(),
+            "[two words]\n[it's]\n[$HOME]\n[`id`]\n[]\n[a\"b\\c]\n"
+        );
+
+        let sh = Path::new("/bin/sh");
+        assert!(which(sh, "sh").is_some_and(|path| path.is_absolute()));
+        assert_eq!(which(sh, "toshik-no-such-tool"), None);
+    }
+
+    #[test]
+    fn lookups_are_cached_per_shell_until_the_path_goes() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("toshik-shell-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let (shell, tool) = (dir.join("fake-sh"), dir.join("bun"));
+        std::fs::write(&tool, "").unwrap();
+        std::fs::write(&shell, format!("#!/bin/sh\necho {}\n", tool.display())).unwrap();
+        std::fs::set_permissions(&shell, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        assert_eq!(which_cached(&shell, "bun").as_ref(), Some(&tool));
+        // Cached: the shell isn't started again.
+        std::fs::write(&shell, "#!/bin/sh\nexit 1\n").unwrap();
+        assert_eq!(which_cached(&shell, "bun").as_ref(), Some(&tool));
+        std::fs::remove_file(&tool).unwrap();
+        assert_eq!(which_cached(&shell, "bun"), None);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,159 @@
+use std::net::SocketAddr;
+
+/// TCP sockets process `pid` is listening on, as the OS reports them.
+///
+/// Read from procfs on Linux. Elsewhere there is no equivalent without extra dependencies,
+/// so the result is empty (and a note is logged).
+#[cfg(target_os = "linux")]
+pub(crate) fn listen_addrs(pid: u32) -> std::io::Result<Vec<SocketAddr>> {
+    let inodes = socket_inodes(pid)?;
+    let mut addrs: Vec<SocketAddr> = ["tcp", "tcp6"]
+        .iter()
+        .flat_map(|table| listeners(&format!("/proc/{pid}/net/{table}")))
+        .filter(|(_, inode)| inodes.contains(inode))
+        .map(|(addr, _)| addr)
+        .collect();
+    addrs.sort();
+    addrs.dedup();
+    Ok(addrs)
+}
+
+/// The process listening on each of `ports` that has a listener, where the owner can be
+/// seen: processes of other users hide their fds, so those ports are left out.
+///
+/// Read from procfs on Linux; empty elsewhere.
+#[cfg(target_os = "linux")]
+pub(crate) fn port_owners(ports: &[u16]) -> std::collections::HashMap<u16, u32> {
+    use std::collections::HashMap;
+
+    let by_inode: HashMap<String, u16> = ["tcp", "tcp6"]
+        .iter()
+        .flat_map(|table| listeners(&format!("/proc/net/{table}")))
+        .filter(|(addr, _)| ports.contains(&addr.port()))
+        .map(|(addr, inode)| (inode, addr.port()))
+        .collect();
+    let mut owners = HashMap::new();
+    if by_inode.is_empty() {
+        return owners;
+    }
+    let Ok(procs) = std::fs::read_dir("/proc") else {
+        return owners;
+    };
+    for pid in procs.filter_map(|entry| entry.ok()?.file_name().to_str()?.parse::<u32>().ok()) {
+        let Ok(inodes) = socket_inodes(pid) else {
+            continue;
+        };
+        for inode in inodes {
+            if let Some(&port) = by_inode.get(&inode) {
+                owners.entry(port).or_insert(pid);
+            }
+        }
+    }
+    owners
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn port_owners(_ports: &[u16]) -> std::collections::HashMap<u16, u32> {
+    std::collections::HashMap::new()
+}
+
+/// Short name of process `pid` (`/proc/<pid>/comm`), where it can be read.
+pub(crate) fn process_name(pid: u32) -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        let comm = std::fs::read_to_string(format!("/proc/{pid}/comm")).ok()?;
+        Some(comm.trim_end().to_string())
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = pid;
+        None
+    }
+}
+
+/// Socket inodes owned by process `pid`, from its `socket:[<inode>]` fd links.
+#[cfg(target_os = "linux")]
+fn socket_inodes(pid: u32) -> std::io::Result<std::collections::HashSet<String>> {
+    let mut inodes = std::collections::HashSet::new();
+    for entry in std::fs::read_dir(format!("/proc/{pid}/fd"))? {
+        let Ok(target) = std::fs::read_link(entry?.path()) else {
+            continue;
+        };
+        let target = target.to_string_lossy();
+        if let Some(inode) = target
+            .strip_prefix("socket:[")
+            .and_then(|rest| rest.strip_suffix(']'))
+        {
+            inodes.insert(inode.to_string());
+        }
+    }
+    Ok(inodes)
+}
+
+/// Listening sockets in a procfs TCP table, with their inodes. A missing table (no IPv6
+/// support in the kernel) has none.
+#[cfg(target_os = "linux")]
+fn listeners(table: &str) -> Vec<(SocketAddr, String)> {
+    let Ok(raw) = std::fs::read_to_string(table) else {
+        return Vec::new();
+    };
+    raw.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            // `sl local_address rem_address st ... inode`; state 0A is LISTEN.
+            let (local, state, inode) = (fields.get(1)?, fields.get(3)?, fields.get(9)?);
+            if *state != "0A" {
+                return None;
+            }
+            Some((parse_proc_addr(local)?, inode.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn listen_addrs(pid: u32) -> std::io::Result<Vec<SocketAddr>> {
+    log::info!("Listening sockets of pid {pid} can't be queried on this platform");
+    Ok(Vec::new())
+}
+
+/// Decode a procfs `ADDR:PORT` pair. The address is printed as 32-bit words in host byte
+/// order (one for IPv4, four for IPv6), the port as a plain hex number.
+#[cfg(target_os = "linux")]
+fn parse_proc_addr(raw: &str) -> Option<SocketAddr> {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    let (addr, port) = raw.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+    let words = (0..addr.len() / 8)
+        .map(|i| u32::from_str_radix(addr.get(i * 8..i * 8 + 8)?, 16).ok())
+        .collect::<Option<Vec<u32>>>()?;
+    let bytes: Vec<u8> = words.iter().flat_map(|word| word.to_ne_bytes()).collect();
+    let ip = match bytes.len() {
+        4 => Ipv4Addr::from(<[u8; 4]>::try_from(bytes).ok()?).into(),
+        16 => Ipv6Addr::from(<[u8; 16]>::try_from(bytes).ok()?).into(),
+        _ => return None,
+    };
+    Some(SocketAddr::new(ip, port))
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn finds_a_listener_of_this_process() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        assert!(listen_addrs(std::process::id()).unwrap().contains(&addr));
+    }
+
+    #[test]
+    fn finds_the_owner_of_a_listening_port() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        assert_eq!(port_owners(&[port]).get(&port), Some(&std::process::id()));
+    }
+}
@@ -0,0 +1,32 @@
+/// Whether this platform has a system log the launcher can write to.
+pub(crate) const SUPPORTED: bool = cfg!(unix);
+
+/// Send one backend line to syslog(3), tagged `toshik-backend`; stderr lines are logged as
+/// warnings, stdout lines as info. macOS routes syslog into the unified log.
+#[cfg(unix)]
+pub(crate) fn send(stream: &str, line: &[u8]) {
+    use std::ffi::CString;
+    use std::sync::Once;
+
+    static OPEN: Once = Once::new();
+    OPEN.call_once(|| {
+        // SAFETY: the ident is a static C string, as openlog(3) keeps the pointer.
+        unsafe { libc::openlog(c"toshik-backend".as_ptr(), libc::LOG_PID, libc::LOG_USER) };
+    });
+
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim_end().replace('\0', "");
+    let Ok(message) = CString::new(line) else {
+        return;
+    };
+    let priority = if stream == "stderr" {
+        libc::LOG_WARNING
+    } else {
+        libc::LOG_INFO
+    };
+    // SAFETY: a constant `%s` format with one valid NUL-terminated argument.
+    unsafe { libc::syslog(priority, c"%s".as_ptr(), message.as_ptr()) };
+}
+
+#[cfg(not(unix))]
+pub(crate) fn send(_stream: &str, _line: &[u8]) {}
@@ -0,0 +1,148 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::Serialize;
+
+/// Background threads started by the launcher, so they can be listed and cancelled.
+///
+/// Cancellation is cooperative: each task gets a flag it checks between steps, so one
+/// blocked in I/O or a sleep finishes that first. Log readers and the log follower are not
+/// in here; they belong to their launch and `follow_backend_log` and stop with those.
+#[derive(Default)]
+pub(crate) struct TaskRegistry(Mutex<Vec<Task>>);
+
+struct Task {
+    name: String,
+    /// The backend run it works for, if any; cancelled when that backend is stopped.
+    run_id: Option<String>,
+    started_at: u64,
+    cancel: Arc<AtomicBool>,
+    thread: JoinHandle<()>,
+}
+
+/// A running task, as returned by `list_background_tasks`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TaskInfo {
+    pub name: String,
+    pub run_id: Option<String>,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Asked to stop but still finishing its current step.
+    pub cancelled: bool,
+}
+
+impl TaskRegistry {
+    /// Run `task` on a thread called `name`. It receives its cancellation flag.
+    pub(crate) fn spawn(
+        &self,
+        name: &str,
+        run_id: Option<&str>,
+        task: impl FnOnce(&AtomicBool) + Send + 'static,
+    ) -> io::Result<()> {
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flag = Arc::clone(&cancel);
+        let thread = thread::Builder::new()
+            .name(name.into())
+            .spawn(move || task(&flag))?;
+        let started_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default();
+        if let Ok(mut tasks) = self.0.lock() {
+            tasks.retain(|task| !task.thread.is_finished());
+            tasks.push(Task {
+                name: name.into(),
+                run_id: run_id.map(str::to_string),
+                started_at,
+                cancel,
+                thread,
+            });
+        }
+        Ok(())
+    }
+
+    /// Tasks that have not finished yet, oldest first.
+    pub(crate) fn list(&self) -> Vec<TaskInfo> {
+        let Ok(mut tasks) = self.0.lock() else {
+            return Vec::new();
+        };
+        tasks.retain(|task| !task.thread.is_finished());
+        tasks
+            .iter()
+            .map(|task| TaskInfo {
+                name: task.name.clone(),
+                run_id: task.run_id.clone(),
+                started_at: task.started_at,
+                cancelled: task.cancel.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+
+    /// Ask every running task called `name` to stop; returns how many there were.
+    pub(crate) fn cancel(&self, name: &str) -> usize {
+        self.cancel_where(|task| task.name == name)
+    }
+
+    /// Ask every task working for backend `run_id` to stop.
+    pub(crate) fn cancel_run(&self, run_id: &str) -> usize {
+        self.cancel_where(|task| task.run_id.as_deref() == Some(run_id))
+    }
+
+    fn cancel_where(&self, matches: impl Fn(&Task) -> bool) -> usize {
+        let Ok(tasks) = self.0.lock() else {
+            return 0;
+        };
+        let mut cancelled = 0;
+        for task in tasks.iter().filter(|task| !task.thread.is_finished()) {
+            if matches(task) {
+                log::info!("Cancelling background task {}", task.name);
+                task.cancel.store(true, Ordering::SeqCst);
+                cancelled += 1;
+            }
+        }
+        cancelled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    fn wait_until(mut done: impl FnMut() -> bool) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !done() {
+            assert!(Instant::now() < deadline, "timed out");
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn tasks_are_listed_until_cancelled() {
+        let tasks = TaskRegistry::default();
+        let until_cancelled = |cancel: &AtomicBool| {
+            while !cancel.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(10));
+            }
+        };
+        tasks
+            .spawn("watcher", Some("run-1"), until_cancelled)
+            .unwrap();
+        tasks.spawn("heartbeat", None, until_cancelled).unwrap();
+        let names = |tasks: &TaskRegistry| -> Vec<String> {
+            tasks.list().into_iter().map(|task| task.name).collect()
+        };
+        assert_eq!(names(&tasks), ["watcher", "heartbeat"]);
+
+        assert_eq!(tasks.cancel_run("run-2"), 0);
+        assert_eq!(tasks.cancel_run("run-1"), 1);
+        wait_until(|| names(&tasks) == ["heartbeat"]);
+        assert_eq!(tasks.cancel("heartbeat"), 1);
+        wait_until(|| tasks.list().is_empty());
+        assert_eq!(tasks.cancel("heartbeat"), 0);
+    }
+}
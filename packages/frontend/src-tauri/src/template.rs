@@ -0,0 +1,420 @@
+use std::fmt::Write;
+
+use serde_json::{Map, Value};
+
+use crate::config::{self, LauncherConfig};
+
+/// One line on what each field of the serialized config does, keyed like
+/// [`LauncherConfig::sources`].
+const FIELD_HELP: &[(&str, &str)] = &[
+    ("minBunVersion", "Oldest bun allowed to start the backend."),
+    (
+        "bunPath",
+        "bun executable, or a directory with it, instead of the one on PATH.",
+    ),
+    (
+        "bunInvocation",
+        "How the backend is started: run, x or direct.",
+    ),
+    (
+        "useLoginShell",
+        "Find bun and start the backend through $SHELL -lc, for nvm/asdf setups.",
+    ),
+    (
+        "backendEntry",
+        "Script, package or executable started instead of the default backend.",
+    ),
+    (
+        "verifyIntegrity",
+        "Check a direct executable against the embedded SHA-256; the variable turns it off.",
+    ),
+    (
+        "isolatedEnv",
+        "Start the backend with a cleared environment.",
+    ),
+    (
+        "envAllowlist",
+        "Variables an isolated backend still inherits.",
+    ),
+    (
+        "vaultSecrets",
+        "Variables filled from the Stronghold vault, as name = store key.",
+    ),
+    (
+        "streamLogs",
+        "Pipe backend output through reader threads and emit log events.",
+    ),
+    (
+        "normalizeOutput",
+        "Turn CRLF into LF and invalid UTF-8 into U+FFFD; the variable turns it off.",
+    ),
+    ("maxLogLine", "Longest backend log line kept, in bytes."),
+    ("maxLogDir", "Ceiling on all log files together, in bytes."),
+    (
+        "crashLines",
+        "Recent streamed backend lines attached to crash reports.",
+    ),
+    (
+        "logSink",
+        "Where backend output goes: file, syslog or both.",
+    ),
+    (
+        "logMode",
+        "append, or truncate to start each session's log empty (previous kept as .prev).",
+    ),
+    (
+        "logPrefix",
+        "Timestamp and stream tag template put before each line.",
+    ),
+    ("logBanner", "Line written at each launch to separate runs."),
+    (
+        "quiet",
+        "Discard the backend's stdout; ignored with readyLine.",
+    ),
+    (
+        "backendLogFormat",
+        "Format of the backend's own output: text or json.",
+    ),
+    (
+        "startOnFrontendReady",
+        "Start the backend once the webview reports it has mounted.",
+    ),
+    ("autostart", "Start the backend during app setup."),
+    ("workersEnv", "Variable the worker count is passed in."),
+    (
+        "autoWorkers",
+        "Without a worker count, use one worker per logical CPU.",
+    ),
+    (
+        "uvThreadpool",
+        "Also set UV_THREADPOOL_SIZE to the worker count.",
+    ),
+    (
+        "warmPorts",
+        "Look for a free port in the background during setup.",
+    ),
+    ("allowPrivilegedPorts", "Accept ports below 1024."),
+    ("detached", "Leave the backend running when the app exits."),
+    (
+        "restartSchedulePolicy",
+        "What schedule_restart does while one is pending: replace or reject.",
+    ),
+    (
+        "hangDiagnostics",
+        "Write a hang report before restarting a backend that fails its health check.",
+    ),
+    (
+        "hangSignal",
+        "Signal sent after the hang report so the runtime can dump: usr1, usr2 or quit.",
+    ),
+    (
+        "scriptPathMode",
+        "Resolve the backend script canonically or keep it as found.",
+    ),
+    (
+        "ephemeralPort",
+        "Let the OS pick the backend's port instead of scanning 3001-3010.",
+    ),
+    (
+        "host",
+        "Loopback address the backend listens on; the variable picks ::1.",
+    ),
+    (
+        "migrationCommand",
+        "Shell command upgrade_backend runs between stop and start.",
+    ),
+    (
+        "taskTimeoutMs",
+        "Time a run_backend_task script may run, in milliseconds.",
+    ),
+    (
+        "maxTaskOutput",
+        "Bytes of each output stream run_backend_task returns.",
+    ),
+    ("transport", "tcp, or both to also listen on a Unix socket."),
+    (
+        "readyRequireAll",
+        "With both transports, wait for TCP and the socket.",
+    ),
+    (
+        "readyLine",
+        "Take the port from the backend's {\"event\":\"ready\"} stdout line.",
+    ),
+    (
+        "readyEndpoints",
+        "Paths that must answer 2xx, in order, before the backend is ready.",
+    ),
+    ("forcePort", "Use exactly this port; debug builds only."),
+    (
+        "warmupPath",
+        "Path fetched once the backend listens, before it counts as ready.",
+    ),
+    (
+        "warmupRequired",
+        "Never declare the backend ready without a 2xx warmup response.",
+    ),
+    (
+        "postStartHook",
+        "Shell command run once the backend listens.",
+    ),
+    (
+        "postStartHookFatal",
+        "Stop the backend when the hook fails; the variable makes it non-fatal.",
+    ),
+    (
+        "container.image",
+        "Image run with docker/podman instead of bun; its entrypoint is the backend.",
+    ),
+    ("container.runtime", "docker, podman or a path to either."),
+    (
+        "container.args",
+        "Extra run arguments put before the image.",
+    ),
+    (
+        "http.connectTimeoutMs",
+        "Time allowed to connect to the backend, in milliseconds.",
+    ),
+    (
+        "http.readTimeoutMs",
+        "Time allowed between reads of a response, in milliseconds.",
+    ),
+    (
+        "http.getRetries",
+        "Extra attempts for GETs that fail to connect or time out.",
+    ),
+    (
+        "http.proxyConcurrency",
+        "proxy_backend calls in flight at once.",
+    ),
+    (
+        "http.proxyQueueTimeoutMs",
+        "Time a queued proxy_backend call waits, in milliseconds.",
+    ),
+    (
+        "http.proxyMaxResponse",
+        "Largest response body proxy_backend relays, in bytes.",
+    ),
+    (
+        "http.proxyReadTimeoutMs",
+        "Time allowed to read a proxied body, in milliseconds.",
+    ),
+    (
+        "shutdown.termGraceMs",
+        "Time between SIGTERM and SIGKILL, in milliseconds.",
+    ),
+    (
+        "shutdown.killReapMs",
+        "Time to wait for a killed backend to be reaped, in milliseconds.",
+    ),
+    (
+        "shutdownTokenHeader",
+        "Header the shutdown token is sent in.",
+    ),
+    (
+        "healthCheck.probe",
+        "Probe a restarted backend must pass: http, tcp or websocket.",
+    ),
+    ("healthCheck.path", "Endpoint the health probe requests."),
+    (
+        "healthCheck.matcher",
+        "exact:, contains: or regex: test of the health response body.",
+    ),
+    (
+        "settings.logPath",
+        "Where backend output is written instead of the default log.",
+    ),
+    (
+        "settings.backendEnv",
+        "Variables applied to every backend spawn.",
+    ),
+    (
+        "settings.launcherLogLevel",
+        "Level of the launcher's own log output.",
+    ),
+];
+
+/// A commented TOML reference of the effective value of every field, with what it does and
+/// the variable that sets it. Nothing reads it back: it documents the variables to set. Secret-looking `settings.backendEnv`
+/// values are redacted; fields without a value stay commented out.
+pub(crate) fn render(config: &LauncherConfig) -> String {
+    let mut out = String::from(
+        "# The launcher's effective configuration, for reference only: the launcher doesn't read\n\
+         # this file. Keys match `effective_config`; set a field with the TOSHIK_* variable\n\
+         # noted above it.\n",
+    );
+    let Ok(Value::Object(fields)) = serde_json::to_value(config.redacted()) else {
+        return out;
+    };
+    let (sections, top): (Vec<_>, Vec<_>) = fields
+        .iter()
+        .partition(|(key, _)| config::SECTIONS.contains(&key.as_str()));
+    for (key, value) in top {
+        write_field(&mut out, key, key, value);
+    }
+    for (section, value) in sections {
+        let _ = write!(out, "\n[{}]\n", toml_key(section));
+        for (key, value) in value.as_object().into_iter().flatten() {
+            write_field(&mut out, &format!("{section}.{key}"), key, value);
+        }
+    }
+    out
+}
+
+fn write_field(out: &mut String, path: &str, key: &str, value: &Value) {
+    out.push('\n');
+    if let Some((_, help)) = FIELD_HELP.iter().find(|(field, _)| *field == path) {
+        let _ = writeln!(out, "# {help}");
+    }
+    match config::env_var(path) {
+        Some(var) => {
+            let _ = writeln!(out, "# env: {var}");
+        }
+        None if path.starts_with("settings.") => out.push_str("# set through commands\n"),
+        None => {}
+    }
+    match value {
+        Value::Null => {
+            let _ = writeln!(out, "# {} =", toml_key(key));
+        }
+        value => {
+            let _ = writeln!(out, "{} = {}", toml_key(key), toml_value(value));
+        }
+    }
+}
+
+/// `key` bare if TOML allows it, quoted otherwise.
+fn toml_key(key: &str) -> String {
+    let bare = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    if bare {
+        key.to_string()
+    } else {
+        toml_string(key)
+    }
+}
+
+/// `value` as a TOML basic string.
+fn toml_string(value: &str) -> String {
+    let mut out = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04X}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// `value` in inline TOML; maps become inline tables. Nulls inside arrays and maps, which
+/// TOML can't hold, are dropped.
+fn toml_value(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::Bool(flag) => flag.to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::String(text) => toml_string(text),
+        Value::Array(items) => {
+            let items: Vec<_> = items
+                .iter()
+                .filter(|item| !item.is_null())
+                .map(toml_value)
+                .collect();
+            format!("[{}]", items.join(", "))
+        }
+        Value::Object(map) => inline_table(map),
+    }
+}
+
+fn inline_table(map: &Map<String, Value>) -> String {
+    if map.is_empty() {
+        return "{}".into();
+    }
+    let entries: Vec<_> = map
+        .iter()
+        .filter(|(_, value)| !value.is_null())
+        .map(|(key, value)| format!("{} = {}", toml_key(key), toml_value(value)))
+        .collect();
+    format!("{{ {} }}", entries.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn template_documents_every_field_and_redacts_secrets() {
+        let mut config = LauncherConfig::default();
+        config
+            .settings
+            .backend_env
+            .insert("API_TOKEN".into(), "hunter2".into());
+        config
+            .settings
+            .backend_env
+            .insert("GREETING".into(), "say \"hi\"".into());
+        let template = render(&config);
+
+        for field in config.sources().keys() {
+            assert!(
+                FIELD_HELP.iter().any(|(documented, _)| documented == field),
+                "{field} has no help line"
+            );
+        }
+        assert!(!template.contains("hunter2"));
+        assert!(template.contains(r#"backendEnv = { API_TOKEN = "#));
+        assert!(template.contains(r#"GREETING = "say \"hi\"" }"#));
+        assert!(template.contains("# env: TOSHIK_HTTP_RETRIES\ngetRetries = 2\n"));
+        assert!(template.contains("\n[healthCheck]\n"));
+        assert!(template.contains("the launcher doesn't read\n"));
+        assert!(template.contains("\n# forcePort =\n"));
+    }
+
+    #[test]
+    fn credentials_on_command_lines_are_redacted() {
+        let mut config = LauncherConfig {
+            post_start_hook: Some(
+                r#"curl -H "Authorization: Bearer abc123" http://localhost/seed"#.into(),
+            ),
+            migration_command: Some("bun migrate --db-password s3cret --verbose".into()),
+            ..LauncherConfig::default()
+        };
+        config.container.args = ["-e", "API_KEY=k3y", "--env-file", ".env", "-e", "DEBUG=1"]
+            .map(String::from)
+            .into();
+        let template = render(&config);
+
+        for secret in ["abc123", "s3cret", "k3y"] {
+            assert!(!template.contains(secret), "{secret} leaked:\n{template}");
+        }
+        let redacted = config.redacted();
+        assert_eq!(
+            redacted.post_start_hook.as_deref(),
+            Some(r#"curl -H "Authorization: Bearer ********" http://localhost/seed"#)
+        );
+        assert_eq!(
+            redacted.migration_command.as_deref(),
+            Some("bun migrate --db-password ******** --verbose")
+        );
+        assert_eq!(
+            redacted.container.args,
+            [
+                "-e",
+                "API_KEY=********",
+                "--env-file",
+                ".env",
+                "-e",
+                "DEBUG=1"
+            ]
+        );
+    }
+}
@@ -0,0 +1,117 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+/// Launches kept in `startup-timings.json`, oldest dropped first.
+const HISTORY_CAPACITY: usize = 50;
+
+/// How long one launch took to come up, as returned by `startup_timings`. Phases are
+/// milliseconds since the process was spawned.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StartupTiming {
+    pub run_id: String,
+    /// Milliseconds since the Unix epoch at which it was spawned.
+    pub started_at: u64,
+    /// The first launch of its app session; later ones are restarts or drains.
+    pub cold: bool,
+    /// Until it accepted connections.
+    pub listening_ms: u64,
+    /// Until the health check first passed, polled once it was declared ready; `None` if
+    /// it never did within the readiness timeout.
+    pub healthy_ms: Option<u64>,
+    /// Until the warmup request succeeded; `None` without `TOSHIK_WARMUP_PATH`.
+    pub warmed_ms: Option<u64>,
+}
+
+/// Rolling startup history, persisted so regressions can be compared across app sessions.
+pub(crate) struct StartupTimings {
+    path: PathBuf,
+    history: Mutex<VecDeque<StartupTiming>>,
+}
+
+impl StartupTimings {
+    /// The history recorded in `path`, or an empty one if it is missing or unreadable.
+    pub(crate) fn load(path: PathBuf) -> Self {
+        let history = match fs::read_to_string(&path) {
+            Ok(raw) => serde_json::from_str(&raw)
+                .inspect_err(|e| log::warn!("Ignoring invalid {}: {e}", path.display()))
+                .unwrap_or_default(),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => {
+                log::warn!("Failed to read {}: {e}", path.display());
+                VecDeque::new()
+            }
+        };
+        Self {
+            path,
+            history: Mutex::new(history),
+        }
+    }
+
+    pub(crate) fn record(&self, timing: StartupTiming) {
+        let Ok(mut history) = self.history.lock() else {
+            return;
+        };
+        while history.len() >= HISTORY_CAPACITY {
+            history.pop_front();
+        }
+        history.push_back(timing);
+        if let Err(e) = write(&self.path, &history) {
+            log::warn!("Failed to write {}: {e}", self.path.display());
+        }
+    }
+
+    /// Oldest first.
+    pub(crate) fn list(&self) -> Vec<StartupTiming> {
+        self.history
+            .lock()
+            .map(|history| history.iter().cloned().collect())
+            .unwrap_or_default()
+    }
+}
+
+/// Write through a temporary file, like `session.json`.
+fn write(path: &Path, history: &VecDeque<StartupTiming>) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let json = serde_json::to_string_pretty(history).map_err(io::Error::other)?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, json)?;
+    fs::rename(&tmp, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_is_bounded_and_survives_a_reload() {
+        let dir = std::env::temp_dir().join(format!("toshik-timings-{}", uuid::Uuid::new_v4()));
+        let path = dir.join("startup-timings.json");
+        let timings = StartupTimings::load(path.clone());
+        assert!(timings.list().is_empty());
+        for i in 0..HISTORY_CAPACITY as u64 + 5 {
+            timings.record(StartupTiming {
+                run_id: format!("run-{i}"),
+                started_at: 1_000 + i,
+                cold: i == 0,
+                listening_ms: 300 + i,
+                healthy_ms: Some(350 + i),
+                warmed_ms: None,
+            });
+        }
+
+        let reloaded = StartupTimings::load(path).list();
+        assert_eq!(reloaded, timings.list());
+        assert_eq!(reloaded.len(), HISTORY_CAPACITY);
+        assert_eq!(reloaded[0].run_id, "run-5");
+        assert!(!reloaded[0].cold);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
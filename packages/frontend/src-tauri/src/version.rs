@@ -0,0 +1,77 @@
+use serde::Serialize;
+
+use crate::bun::BunVersion;
+
+/// How `verify_backend_version` compares the running version with the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum VersionMatch {
+    /// The strings are equal, ignoring surrounding whitespace (default).
+    Exact,
+    /// Semver-compatible, like a caret requirement: same major (same minor below 1.0) and
+    /// no older than expected. Falls back to `Exact` unless both parse as versions.
+    Compatible,
+}
+
+impl VersionMatch {
+    pub(crate) fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_ascii_lowercase().as_str() {
+            "exact" => Some(Self::Exact),
+            "compatible" | "semver" => Some(Self::Compatible),
+            _ => None,
+        }
+    }
+}
+
+/// Result of `verify_backend_version`.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct VersionCheck {
+    pub matches: bool,
+    pub running: String,
+    pub expected: String,
+}
+
+pub(crate) fn check(running: String, expected: String, mode: VersionMatch) -> VersionCheck {
+    let exact = running.trim() == expected.trim();
+    let matches = match mode {
+        VersionMatch::Exact => exact,
+        VersionMatch::Compatible => {
+            match (BunVersion::parse(&running), BunVersion::parse(&expected)) {
+                (Some(running), Some(expected)) => running.is_compatible_with(expected),
+                _ => exact,
+            }
+        }
+    };
+    VersionCheck {
+        matches,
+        running,
+        expected,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compatible_mode_follows_caret_rules() {
+        let matches = |running: &str, expected: &str, mode| {
+            check(running.into(), expected.into(), mode).matches
+        };
+        assert!(matches("1.4.2", " 1.4.2\n", VersionMatch::Exact));
+        assert!(!matches("1.4.3", "1.4.2", VersionMatch::Exact));
+
+        assert!(matches("1.4.3", "1.4.2", VersionMatch::Compatible));
+        assert!(matches("v1.9.0", "1.4.2", VersionMatch::Compatible));
+        assert!(!matches("1.4.1", "1.4.2", VersionMatch::Compatible));
+        assert!(!matches("2.0.0", "1.4.2", VersionMatch::Compatible));
+        assert!(matches("0.3.9", "0.3.1", VersionMatch::Compatible));
+        assert!(!matches("0.4.0", "0.3.1", VersionMatch::Compatible));
+        assert!(matches("dev-abc", "dev-abc", VersionMatch::Compatible));
+        assert!(!matches("dev-abc", "1.0.0", VersionMatch::Compatible));
+        assert_eq!(
+            VersionMatch::parse("SemVer"),
+            Some(VersionMatch::Compatible)
+        );
+    }
+}
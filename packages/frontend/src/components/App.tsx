@@ -60,12 +60,16 @@ export function App(): React.JSX.Element {
   // Track whether initial data has been requested for this connection.
   const initialRequestedRef = useRef(false);
 
-  // In Tauri mode, call the Rust start_backend command on mount.
+  // In Tauri mode, use a backend the launcher already started (autostart mode); otherwise
+  // tell it we have mounted, and it either starts the backend itself (deferred-start mode)
+  // or leaves it to us via start_backend.
   useEffect(() => {
     if (!IS_TAURI) return;
     let cancelled = false;
 
-    invoke<number>("start_backend")
+    invoke<number | null>("backend_port")
+      .then((port) => port ?? invoke<number | null>("frontend_ready"))
+      .then((port) => port ?? invoke<number>("start_backend"))
       .then((port) => {
         if (!cancelled) setBackendPort(port);
       })